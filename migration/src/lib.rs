@@ -1,12 +1,42 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20261019_000002_create_chat_settings;
+mod m20261019_000003_create_bot_state;
+mod m20261019_000004_create_permissions;
+mod m20261019_000005_add_user_messages;
+mod m20261019_000006_create_reports;
+mod m20261019_000007_add_flood_settings;
+mod m20261019_000008_create_filters;
+mod m20261019_000009_add_link_settings;
+mod m20261019_000010_add_captcha_settings;
+mod m20261019_000011_add_greeting_settings;
+mod m20261019_000012_create_global_bans;
+mod m20261019_000013_create_jobs;
+mod m20261019_000014_add_night_settings;
+mod m20261019_000015_add_quota_settings;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261019_000002_create_chat_settings::Migration),
+            Box::new(m20261019_000003_create_bot_state::Migration),
+            Box::new(m20261019_000004_create_permissions::Migration),
+            Box::new(m20261019_000005_add_user_messages::Migration),
+            Box::new(m20261019_000006_create_reports::Migration),
+            Box::new(m20261019_000007_add_flood_settings::Migration),
+            Box::new(m20261019_000008_create_filters::Migration),
+            Box::new(m20261019_000009_add_link_settings::Migration),
+            Box::new(m20261019_000010_add_captcha_settings::Migration),
+            Box::new(m20261019_000011_add_greeting_settings::Migration),
+            Box::new(m20261019_000012_create_global_bans::Migration),
+            Box::new(m20261019_000013_create_jobs::Migration),
+            Box::new(m20261019_000014_add_night_settings::Migration),
+            Box::new(m20261019_000015_add_quota_settings::Migration),
+        ]
     }
 }
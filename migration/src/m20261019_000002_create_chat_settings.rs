@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ChatSettings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ChatSettings::ChatId)
+                            .big_integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ChatSettings::WarnLimit).big_integer())
+                    .col(ColumnDef::new(ChatSettings::CommandPrefix).string())
+                    .col(ColumnDef::new(ChatSettings::Locale).string())
+                    .col(ColumnDef::new(ChatSettings::WelcomeText).text())
+                    .col(
+                        ColumnDef::new(ChatSettings::Modules)
+                            .json()
+                            .not_null()
+                            .default(Expr::cust("'{}'::json")),
+                    )
+                    .col(
+                        ColumnDef::new(ChatSettings::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ChatSettings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ChatSettings {
+    Table,
+    ChatId,
+    WarnLimit,
+    CommandPrefix,
+    Locale,
+    WelcomeText,
+    Modules,
+    UpdatedAt,
+}
@@ -0,0 +1,130 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Roles::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Roles::Name)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Roles::Permissions).json().not_null())
+                    .col(
+                        ColumnDef::new(Roles::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::CustomRole).string())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_users_custom_role")
+                            .from_tbl(Users::Table)
+                            .from_col(Users::CustomRole)
+                            .to_tbl(Roles::Table)
+                            .to_col(Roles::Name)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PermissionOverrides::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PermissionOverrides::UserId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PermissionOverrides::Permission)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PermissionOverrides::Allowed)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(PermissionOverrides::UserId)
+                            .col(PermissionOverrides::Permission),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_permission_overrides_user_id")
+                            .from(PermissionOverrides::Table, PermissionOverrides::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PermissionOverrides::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_foreign_key(Alias::new("fk_users_custom_role"))
+                    .drop_column(Users::CustomRole)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Roles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+    CustomRole,
+}
+
+#[derive(Iden)]
+enum Roles {
+    Table,
+    Name,
+    Permissions,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum PermissionOverrides {
+    Table,
+    UserId,
+    Permission,
+    Allowed,
+}
@@ -0,0 +1,93 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Reports::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Reports::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Reports::ChatId).big_integer().not_null())
+                    .col(ColumnDef::new(Reports::MessageId).integer().not_null())
+                    .col(ColumnDef::new(Reports::ReporterId).big_integer().not_null())
+                    .col(ColumnDef::new(Reports::TargetId).big_integer().not_null())
+                    .col(ColumnDef::new(Reports::Reason).string())
+                    .col(ColumnDef::new(Reports::Status).string().not_null())
+                    .col(ColumnDef::new(Reports::ResolvedBy).big_integer())
+                    .col(
+                        ColumnDef::new(Reports::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(Reports::ResolvedAt).timestamp_with_time_zone())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_reports_reporter_id")
+                            .from(Reports::Table, Reports::ReporterId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_reports_target_id")
+                            .from(Reports::Table, Reports::TargetId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_reports_reporter_id")
+                    .table(Reports::Table)
+                    .col(Reports::ReporterId)
+                    .col(Reports::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Reports::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Reports {
+    Table,
+    Id,
+    ChatId,
+    MessageId,
+    ReporterId,
+    TargetId,
+    Reason,
+    Status,
+    ResolvedBy,
+    CreatedAt,
+    ResolvedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
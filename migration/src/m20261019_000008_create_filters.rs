@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Filters::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Filters::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Filters::ChatId).big_integer().not_null())
+                    .col(ColumnDef::new(Filters::Kind).string().not_null())
+                    .col(ColumnDef::new(Filters::Pattern).text().not_null())
+                    .col(ColumnDef::new(Filters::Action).string().not_null())
+                    .col(ColumnDef::new(Filters::Reply).text())
+                    .col(ColumnDef::new(Filters::CreatedBy).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(Filters::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_filters_created_by")
+                            .from(Filters::Table, Filters::CreatedBy)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_filters_chat_id_pattern")
+                    .table(Filters::Table)
+                    .col(Filters::ChatId)
+                    .col(Filters::Pattern)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Filters::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Filters {
+    Table,
+    Id,
+    ChatId,
+    Kind,
+    Pattern,
+    Action,
+    Reply,
+    CreatedBy,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
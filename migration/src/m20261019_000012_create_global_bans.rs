@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GlobalBans::Table)
+                    .if_not_exists()
+                    // Not a foreign key: imported bans may name users the bot has never seen.
+                    .col(
+                        ColumnDef::new(GlobalBans::UserId)
+                            .big_integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(GlobalBans::Reason).string())
+                    .col(ColumnDef::new(GlobalBans::BannedBy).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(GlobalBans::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_global_bans_banned_by")
+                            .from(GlobalBans::Table, GlobalBans::BannedBy)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GlobalBans::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum GlobalBans {
+    Table,
+    UserId,
+    Reason,
+    BannedBy,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
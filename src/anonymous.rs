@@ -0,0 +1,130 @@
+use crate::command::{execute, Command, Context};
+use crate::config::Config;
+use crate::error::reply::alert;
+use crate::error::{Error, HandlerError};
+use crate::i18n::I18n;
+use crate::register::register;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use crate::tr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId};
+
+const CALLBACK: &str = "anon:";
+/// How long an anonymous admin has to confirm a command.
+const CONFIRM_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A command waiting for confirmation, with the time it was sent.
+type Entry = (Message, Command, Instant);
+
+/// Commands sent by anonymous admins, waiting for someone to confirm them.
+#[derive(Default)]
+pub struct Pending {
+    commands: Mutex<HashMap<(ChatId, MessageId), Entry>>,
+}
+
+impl Pending {
+    fn insert(&self, msg: Message, cmd: Command) {
+        let mut commands = self.commands.lock().unwrap();
+        commands.retain(|_, (_, _, at)| at.elapsed() < CONFIRM_TTL);
+        commands.insert((msg.chat.id, msg.id), (msg, cmd, Instant::now()));
+    }
+
+    fn take(&self, chat: ChatId, id: MessageId) -> Option<(Message, Command)> {
+        let (msg, cmd, at) = self.commands.lock().unwrap().remove(&(chat, id))?;
+        (at.elapsed() < CONFIRM_TTL).then_some((msg, cmd))
+    }
+}
+
+/// Whether `msg` was sent by an admin who posts anonymously, i.e. as the chat itself.
+pub fn is_anonymous_admin(msg: &Message) -> bool {
+    msg.sender_chat.as_ref().is_some_and(|c| c.id == msg.chat.id)
+}
+
+/// Asks the anonymous author of `msg` to reveal themselves before `cmd` runs.
+pub async fn ask(
+    bot: &Bot,
+    i18n: &I18n,
+    repo: &Repo,
+    pending: &Pending,
+    msg: Message,
+    cmd: Command,
+) -> Result<(), HandlerError> {
+    let settings = repo.get_chat_settings(msg.chat.id.0).await.ok();
+    let locale = i18n.locale(settings.and_then(|s| s.locale).as_deref(), None);
+    let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        tr!(i18n, locale, "anon-confirm-button"),
+        format!("{CALLBACK}{}", msg.id),
+    )]]);
+    bot.send_message(msg.chat.id, tr!(i18n, locale, "anon-confirm"))
+        .reply_markup(keyboard)
+        .reply_to(msg.id)
+        .await
+        .map_err(|e| HandlerError::new(&msg, locale, e))?;
+    pending.insert(msg, cmd);
+    Ok(())
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(CALLBACK))
+}
+
+/// Runs the pending command on behalf of the chat administrator who pressed the button.
+pub async fn callback(
+    bot: Bot,
+    q: CallbackQuery,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+    pending: Arc<Pending>,
+) -> Result<(), HandlerError> {
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        return Ok(());
+    };
+    let chat = message.chat.id;
+    let settings = repo.get_chat_settings(chat.0).await.ok();
+    let locale = i18n.locale(
+        settings.and_then(|s| s.locale).as_deref(),
+        q.from.language_code.as_deref(),
+    );
+    let id = q
+        .data
+        .as_deref()
+        .and_then(|d| d.strip_prefix(CALLBACK))
+        .and_then(|id| id.parse().ok())
+        .map(MessageId);
+    let result: Result<Result<(Message, Command), &str>, Error> = async {
+        let member = bot.get_chat_member(chat, q.from.id).await?;
+        if !member.kind.is_privileged() {
+            return Ok(Err("anon-not-admin"));
+        }
+        register(&repo, &roles, &q.from).await?;
+        Ok(id.and_then(|id| pending.take(chat, id)).ok_or("anon-expired"))
+    }
+    .await;
+    let (msg, cmd) = match result {
+        Ok(Ok(pending)) => pending,
+        Ok(Err(key)) => {
+            bot.answer_callback_query(q.id.clone())
+                .text(tr!(i18n, locale, key))
+                .show_alert(true)
+                .await
+                .ok();
+            return Ok(());
+        }
+        Err(err) => {
+            alert(&bot, &i18n, locale, &q, err).await;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(q.id.clone()).await.ok();
+    bot.delete_message(chat, message.id).await.ok();
+    let sender = Some(q.from.id.0 as i64);
+    let ctx = Context::new(&bot, &i18n, &repo, &roles, &config, &msg, sender).await?;
+    execute(&ctx, cmd).await
+}
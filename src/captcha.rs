@@ -0,0 +1,339 @@
+use crate::config::Config;
+use crate::error::reply::alert;
+use crate::error::{Error, HandlerError};
+use crate::{gban, greetings};
+use crate::i18n::{I18n, Locale};
+use crate::models::chat_settings::{CaptchaKind, CaptchaSettings, Module};
+use crate::models::jobs::{self, Kind};
+use crate::permission::Permission;
+use crate::register::register;
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use crate::tr;
+use rand::seq::SliceRandom;
+use chrono::Utc;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{
+    Chat, ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, User,
+};
+use teloxide::utils::html;
+
+const CALLBACK: &str = "captcha:";
+/// Emoji offered by the emoji challenge, with the names of their messages `captcha-emoji-<name>`.
+const EMOJI: &[(&str, &str)] = &[
+    ("🐱", "cat"),
+    ("🐶", "dog"),
+    ("🦊", "fox"),
+    ("🐼", "panda"),
+    ("🐸", "frog"),
+    ("🐵", "monkey"),
+    ("🐧", "penguin"),
+    ("🐙", "octopus"),
+];
+/// Buttons of the math and emoji challenges.
+const OPTIONS: usize = 4;
+
+/// Text, buttons and expected answer of a new challenge for `user`.
+fn challenge(
+    i18n: &I18n,
+    locale: Locale,
+    kind: CaptchaKind,
+    user: &User,
+    timeout: u64,
+) -> (String, InlineKeyboardMarkup, String) {
+    let mut rng = rand::thread_rng();
+    let mention = html::user_mention(user.id, &user.full_name());
+    let button = |text: String, answer: &str| {
+        InlineKeyboardButton::callback(text, format!("{CALLBACK}{}:{answer}", user.id))
+    };
+    match kind {
+        CaptchaKind::Button => {
+            let text = tr!(i18n, locale, "captcha-button", user = mention, seconds = timeout);
+            let keyboard = InlineKeyboardMarkup::new([[button(
+                tr!(i18n, locale, "captcha-button-label"),
+                "ok",
+            )]]);
+            (text, keyboard, "ok".to_owned())
+        }
+        CaptchaKind::Math => {
+            let (a, b) = (rng.gen_range(1..10), rng.gen_range(1..10));
+            let sum: i32 = a + b;
+            let mut options = vec![sum];
+            while options.len() < OPTIONS {
+                let option = sum + rng.gen_range(-5..=5);
+                if option > 0 && !options.contains(&option) {
+                    options.push(option);
+                }
+            }
+            options.shuffle(&mut rng);
+            let text = tr!(
+                i18n,
+                locale,
+                "captcha-math",
+                user = mention,
+                a = a,
+                b = b,
+                seconds = timeout
+            );
+            let row: Vec<_> = options
+                .iter()
+                .map(|o| button(o.to_string(), &o.to_string()))
+                .collect();
+            (text, InlineKeyboardMarkup::new([row]), sum.to_string())
+        }
+        CaptchaKind::Emoji => {
+            let options: Vec<_> = EMOJI.choose_multiple(&mut rng, OPTIONS).collect();
+            let (_, name) = options.choose(&mut rng).expect("options are not empty");
+            let text = tr!(
+                i18n,
+                locale,
+                "captcha-emoji",
+                user = mention,
+                emoji = tr!(i18n, locale, &format!("captcha-emoji-{name}")),
+                seconds = timeout
+            );
+            let row: Vec<_> = options
+                .iter()
+                .map(|(emoji, name)| button(emoji.to_string(), name))
+                .collect();
+            (text, InlineKeyboardMarkup::new([row]), name.to_string())
+        }
+    }
+}
+
+/// Restricts every new member and sends them a challenge, kicking whoever does not solve it in
+/// time, and welcomes those who pass.
+///
+/// Globally banned users are banned instead. Bots and known moderators are let through, and
+/// everyone is welcomed right away in chats without the captcha. Challenges are kept as jobs, so
+/// they are answered and expire across restarts, and anything the repository fails to tell is
+/// taken as a reason to challenge.
+pub async fn join(
+    bot: Bot,
+    msg: Message,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let chat = msg.chat.id;
+    let settings = match repo.get_chat_settings(chat.0).await {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            tracing::error!("Failed to get the settings of chat {}, challenging anyway: {}", chat, e);
+            None
+        }
+    };
+    let locale = i18n.locale(
+        settings.as_ref().and_then(|s| s.locale.as_deref()),
+        msg.from.as_ref().and_then(|u| u.language_code.as_deref()),
+    );
+    let captcha = match &settings {
+        Some(settings) => settings
+            .module_enabled(Module::Captcha)
+            .then(|| settings.captcha()),
+        None => Some(CaptchaSettings::default()),
+    };
+    let result: Result<(), Error> = async {
+        for user in msg.new_chat_members().unwrap_or_default() {
+            if user.is_bot {
+                continue;
+            }
+            match gban::enforce(&bot, &repo, chat, user).await {
+                Ok(Some(ban)) => {
+                    let mut text = tr!(
+                        i18n,
+                        locale,
+                        "gban-joined",
+                        user = html::user_mention(user.id, &user.full_name())
+                    );
+                    if let Some(reason) = &ban.reason {
+                        let reason = html::escape(reason);
+                        text.push('\n');
+                        text.push_str(&tr!(i18n, locale, "moderation-reason", reason = reason));
+                    }
+                    bot.send_message(chat, text).parse_mode(ParseMode::Html).await?;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::error!("Failed to check the global ban of {}: {}", user.id, e),
+            }
+            let trusted = match repo.get_permissions(user.id.0 as i64, Some(chat.0)).await {
+                Ok(permissions) => permissions.contains(&Permission::BypassCaptcha),
+                Err(RepoError::NotFound { .. }) => false,
+                Err(e) => {
+                    tracing::error!("Failed to get the permissions of {}: {}", user.id, e);
+                    false
+                }
+            };
+            let Some(captcha) = captcha.as_ref().filter(|_| !trusted) else {
+                welcome(&bot, &repo, &config, &msg.chat, user).await;
+                continue;
+            };
+            let kind = captcha.kind.unwrap_or(config.captcha.kind);
+            let timeout = captcha.timeout.unwrap_or(config.captcha.timeout);
+            // Restricted first, so that whatever fails next leaves them muted rather than in.
+            bot.restrict_chat_member(chat, user.id, ChatPermissions::empty())
+                .await?;
+            register(&repo, &roles, user).await?;
+            for old in take(&repo, chat, user.id, None).await? {
+                bot.delete_message(chat, MessageId(old.message)).await.ok();
+            }
+            let (text, keyboard, answer) = challenge(&i18n, locale, kind, user, timeout);
+            let sent = bot
+                .send_message(chat, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?;
+            let payload = jobs::Captcha {
+                answer,
+                message: sent.id.0,
+            };
+            let expires = Utc::now() + Duration::from_secs(timeout);
+            repo.schedule_job(
+                Kind::Captcha,
+                chat.0,
+                user.id.0 as i64,
+                serde_json::to_value(payload).expect("payload serializes"),
+                expires.fixed_offset(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+    .await;
+    result.map_err(|err| HandlerError::new(&msg, locale, err))
+}
+
+/// Sends the welcome message, logging rather than returning failures.
+async fn welcome(bot: &Bot, repo: &Repo, config: &Config, chat: &Chat, user: &User) {
+    if let Err(e) = greetings::welcome(bot, repo, config, chat, user).await {
+        tracing::error!("Failed to welcome {} in chat {}: {}", user.id, chat.id, e);
+    }
+}
+
+/// Claims the pending challenges of `user`, only the one in `message` if given, so that neither
+/// their answer nor their expiry acts on them again.
+async fn take(
+    repo: &Repo,
+    chat: ChatId,
+    user: UserId,
+    message: Option<MessageId>,
+) -> Result<Vec<jobs::Captcha>, Error> {
+    let mut taken = Vec::new();
+    for job in repo
+        .get_jobs(Kind::Captcha, Some(chat.0), Some(user.0 as i64))
+        .await?
+    {
+        let challenge = job.captcha();
+        if message.is_none_or(|m| m.0 == challenge.message) && repo.finish_job(job.id).await? {
+            taken.push(challenge);
+        }
+    }
+    Ok(taken)
+}
+
+/// Lifts the restriction of a member who passed, logs it and welcomes them.
+async fn pass(
+    bot: &Bot,
+    repo: &Repo,
+    config: &Config,
+    chat: &Chat,
+    user: &User,
+    message: MessageId,
+) -> Result<(), Error> {
+    bot.delete_message(chat.id, message).await.ok();
+    let permissions = bot
+        .get_chat(chat.id)
+        .await?
+        .permissions()
+        .unwrap_or_else(ChatPermissions::all);
+    bot.restrict_chat_member(chat.id, user.id, permissions).await?;
+    repo.captcha_result(user.id.0 as i64, chat.id.0, true).await?;
+    welcome(bot, repo, config, chat, user).await;
+    Ok(())
+}
+
+/// Kicks a member who failed or did not answer their challenge, and logs it.
+///
+/// Run by the job of the challenge when it expires.
+pub async fn kick(
+    bot: &Bot,
+    repo: &Repo,
+    chat: ChatId,
+    user: UserId,
+    message: MessageId,
+) -> Result<(), Error> {
+    bot.delete_message(chat, message).await.ok();
+    bot.ban_chat_member(chat, user).await?;
+    bot.unban_chat_member(chat, user).await?;
+    repo.captcha_result(user.0 as i64, chat.0, false).await?;
+    Ok(())
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(CALLBACK))
+}
+
+/// Checks the answer a new member picked.
+pub async fn callback(
+    bot: Bot,
+    q: CallbackQuery,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        return Ok(());
+    };
+    let chat = message.chat.id;
+    let settings = repo.get_chat_settings(chat.0).await.ok();
+    let locale = i18n.locale(
+        settings.and_then(|s| s.locale).as_deref(),
+        q.from.language_code.as_deref(),
+    );
+    let Some((user, answer)) = q
+        .data
+        .as_deref()
+        .and_then(|d| d.strip_prefix(CALLBACK))
+        .and_then(|d| d.split_once(':'))
+        .and_then(|(user, answer)| Some((UserId(user.parse().ok()?), answer)))
+    else {
+        return Ok(());
+    };
+    let key = if user != q.from.id {
+        Some("captcha-not-yours")
+    } else {
+        let result = match take(&repo, chat, user, Some(message.id)).await {
+            Ok(taken) => match taken.into_iter().next() {
+                Some(challenge) if challenge.answer == answer => {
+                    pass(&bot, &repo, &config, &message.chat, &q.from, message.id)
+                        .await
+                        .map(|_| None)
+                }
+                Some(_) => kick(&bot, &repo, chat, user, message.id)
+                    .await
+                    .map(|_| Some("captcha-wrong")),
+                None => Ok(Some("captcha-expired")),
+            },
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(key) => key,
+            Err(err) => {
+                alert(&bot, &i18n, locale, &q, err).await;
+                return Ok(());
+            }
+        }
+    };
+    let answer = bot.answer_callback_query(q.id.clone());
+    match key {
+        Some(key) => answer.text(tr!(i18n, locale, key)).show_alert(true).await.ok(),
+        None => answer.await.ok(),
+    };
+    Ok(())
+}
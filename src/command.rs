@@ -1,30 +1,223 @@
 use teloxide::macros::BotCommands;
 use teloxide::prelude::*;
 use teloxide::sugar::request::RequestReplyExt;
-use teloxide::types::ParseMode;
-use crate::error::Error;
+use teloxide::types::{Me, ParseMode};
+use teloxide::utils::command::BotCommands as _;
+use crate::config::Config;
+use crate::error::{Error, HandlerError};
+use crate::i18n::{I18n, Locale};
+use crate::models::prelude::{ChatSettingsModel, UserModel};
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use crate::target::{self, Targets};
+use crate::{anonymous, filter, gban, greetings, moderation, nickname, ownership, profile, quota, reminders, report, role, schedule, settings, tr};
+use std::sync::Arc;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule  = "snake_case")]
 pub enum Command {
     Help,
     Start,
+    Settings(String),
+    TransferOwnership(String),
+    Role(String),
+    Warn(String),
+    Dwarn(String),
+    Unwarn(String),
+    Ban(String),
+    Dban(String),
+    Unban(String),
+    Del,
+    Purge,
+    #[command(alias = "info")]
+    Profile(String),
+    Nick(String),
+    Report(String),
+    Filter(String),
+    Welcome(String),
+    Goodbye(String),
+    Gban(String),
+    Ungban(String),
+    Gbans(String),
+    Schedule(String),
+    Schedules,
+    Unschedule(String),
+    Remindme(String),
+    Reminders,
+    Unremind(String),
+    Quota(String),
 }
 
-pub async fn handle_commands(bot: Bot, msg: Message, cmd: Command) -> Result<(), Error> {
+/// Everything a command handler needs to answer a message.
+pub struct Context<'a> {
+    pub bot: &'a Bot,
+    pub i18n: &'a I18n,
+    pub repo: &'a Repo,
+    pub roles: &'a RoleSelector,
+    pub config: &'a Config,
+    pub msg: &'a Message,
+    /// Who the command runs as; differs from the author for confirmed anonymous admins.
+    pub sender: Option<i64>,
+    pub settings: ChatSettingsModel,
+    pub locale: Locale,
+}
+
+impl<'a> Context<'a> {
+    /// Loads the chat's settings and locale for a command from `msg` run as `sender`.
+    pub async fn new(
+        bot: &'a Bot,
+        i18n: &'a I18n,
+        repo: &'a Repo,
+        roles: &'a RoleSelector,
+        config: &'a Config,
+        msg: &'a Message,
+        sender: Option<i64>,
+    ) -> Result<Self, HandlerError> {
+        let user_locale = msg.from.as_ref().and_then(|u| u.language_code.as_deref());
+        let settings = match repo.get_chat_settings(msg.chat.id.0).await {
+            Ok(settings) => settings,
+            Err(e) => return Err(HandlerError::new(msg, i18n.locale(None, user_locale), e)),
+        };
+        let locale = i18n.locale(settings.locale.as_deref(), user_locale);
+        Ok(Self {
+            bot,
+            i18n,
+            repo,
+            roles,
+            config,
+            msg,
+            sender,
+            settings,
+            locale,
+        })
+    }
+
+    /// Id of the user the command runs as.
+    pub fn sender_id(&self) -> Option<i64> {
+        self.sender
+    }
+
+    /// Users the command with arguments `args` is aimed at, looked up after `skip` of their words.
+    pub async fn targets(&self, args: &str, skip: usize) -> Result<Targets, RepoError> {
+        target::resolve(self.repo, self.roles, self.msg, args, skip).await
+    }
+
+    /// The first of [`Context::targets`].
+    pub async fn target(&self, args: &str, skip: usize) -> Result<Option<UserModel>, RepoError> {
+        Ok(self.targets(args, skip).await?.users.into_iter().next())
+    }
+
+    pub async fn reply(&self, text: String) -> Result<Message, Error> {
+        Ok(self
+            .bot
+            .send_message(self.msg.chat.id, text)
+            .reply_to(self.msg.id)
+            .await?)
+    }
+
+    /// Replies with HTML `text`, e.g. one containing [`nickname::mention`]s.
+    pub async fn reply_html(&self, text: String) -> Result<Message, Error> {
+        Ok(self
+            .bot
+            .send_message(self.msg.chat.id, text)
+            .parse_mode(ParseMode::Html)
+            .reply_to(self.msg.id)
+            .await?)
+    }
+}
+
+/// Parses a command sent with `/` or with the chat's (or else the configured) prefix.
+pub async fn parse_command(
+    msg: Message,
+    me: Me,
+    config: Arc<Config>,
+    repo: Arc<Repo>,
+) -> Option<Command> {
+    let text = msg.text()?;
+    let prefix = match repo.get_chat_settings(msg.chat.id.0).await {
+        Ok(settings) => settings.command_prefix,
+        Err(e) => {
+            tracing::error!("Failed to get settings of chat {}: {}", msg.chat.id, e);
+            None
+        }
+    }
+    .unwrap_or_else(|| config.command_prefix.clone());
+    let text = match text.strip_prefix(prefix.as_str()) {
+        Some(rest) if prefix != "/" => format!("/{rest}"),
+        _ => text.to_owned(),
+    };
+    Command::parse(&text, me.username()).ok()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_commands(
+    bot: Bot,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+    pending: Arc<anonymous::Pending>,
+    msg: Message,
+    cmd: Command,
+) -> Result<(), HandlerError> {
+    if anonymous::is_anonymous_admin(&msg) && !matches!(cmd, Command::Help | Command::Start) {
+        return anonymous::ask(&bot, &i18n, &repo, &pending, msg, cmd).await;
+    }
+    let sender = msg.from.as_ref().map(|u| u.id.0 as i64);
+    let ctx = Context::new(&bot, &i18n, &repo, &roles, &config, &msg, sender).await?;
+    execute(&ctx, cmd).await
+}
+
+/// Runs `cmd`, attaching the chat's locale to any error.
+pub async fn execute(ctx: &Context<'_>, cmd: Command) -> Result<(), HandlerError> {
+    run_command(ctx, cmd)
+        .await
+        .map_err(|err| HandlerError::new(ctx.msg, ctx.locale, err))
+}
+
+async fn run_command(ctx: &Context<'_>, cmd: Command) -> Result<(), Error> {
+    let (bot, msg) = (ctx.bot, ctx.msg);
     match cmd {
         Command::Help => {
-            bot.send_message(msg.chat.id, "🔰 Вот ссылка на статью по использованию бота > [тык](https://example.com)")
+            bot.send_message(msg.chat.id, tr!(ctx.i18n, ctx.locale, "help"))
                 .reply_to(msg.id)
                 .parse_mode(ParseMode::MarkdownV2)
                 .send().await?;
         }
         Command::Start => {
-           bot.send_message(msg.chat.id, "👋 Привет! Я — Помка, для подробного ознакомления с функционалом напиши /help")
+           bot.send_message(msg.chat.id, tr!(ctx.i18n, ctx.locale, "start"))
                .reply_to(msg.id)
                .send().await?;
 
         }
+        Command::Settings(args) => settings::command(ctx, args).await?,
+        Command::TransferOwnership(args) => ownership::command(ctx, args).await?,
+        Command::Role(args) => role::command(ctx, args).await?,
+        Command::Warn(args) => moderation::warn(ctx, args, false).await?,
+        Command::Dwarn(args) => moderation::warn(ctx, args, true).await?,
+        Command::Unwarn(args) => moderation::unwarn(ctx, args).await?,
+        Command::Ban(args) => moderation::ban(ctx, args, false).await?,
+        Command::Dban(args) => moderation::ban(ctx, args, true).await?,
+        Command::Unban(args) => moderation::unban(ctx, args).await?,
+        Command::Del => moderation::delete(ctx).await?,
+        Command::Purge => moderation::purge(ctx).await?,
+        Command::Profile(args) => profile::command(ctx, args).await?,
+        Command::Nick(args) => nickname::command(ctx, args).await?,
+        Command::Report(reason) => report::command(ctx, reason).await?,
+        Command::Filter(args) => filter::command(ctx, args).await?,
+        Command::Welcome(args) => greetings::command(ctx, args, false).await?,
+        Command::Goodbye(args) => greetings::command(ctx, args, true).await?,
+        Command::Gban(args) => gban::gban(ctx, args).await?,
+        Command::Ungban(args) => gban::ungban(ctx, args).await?,
+        Command::Gbans(args) => gban::gbans(ctx, args).await?,
+        Command::Schedule(args) => schedule::schedule(ctx, args).await?,
+        Command::Schedules => schedule::list(ctx).await?,
+        Command::Unschedule(args) => schedule::unschedule(ctx, args).await?,
+        Command::Remindme(args) => reminders::remind(ctx, args).await?,
+        Command::Reminders => reminders::list(ctx).await?,
+        Command::Unremind(args) => reminders::cancel(ctx, args).await?,
+        Command::Quota(args) => quota::command(ctx, args).await?,
     };
     Ok(())
 }
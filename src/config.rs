@@ -0,0 +1,841 @@
+use crate::i18n::{I18n, I18nError, Locale};
+use crate::models::chat_settings::{CaptchaKind, FloodAction, NightRestriction};
+use crate::models::users::Role;
+use crate::permission::{Permission, PermissionMap};
+use crate::repository::cache::{CacheOptions, CachedRepository};
+use crate::repository::db::{Repository, RepositoryOptions};
+use crate::repository::RepositoryTrait;
+use crate::role::RoleSelector;
+use sea_orm::{ConnectOptions, Database, DbErr};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::Level;
+use url::Url;
+
+const CONFIG: &str = "CONFIG";
+const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub creators: Vec<i64>,
+    pub command_prefix: String,
+    pub warns: WarnsConfig,
+    pub nicknames: NicknamesConfig,
+    pub locale: LocaleConfig,
+    pub log: LogConfig,
+    pub cache: CacheConfig,
+    pub updates: UpdatesConfig,
+    pub permissions: PermissionsConfig,
+    pub sync: SyncConfig,
+    pub reports: ReportsConfig,
+    pub flood: FloodConfig,
+    pub filters: FiltersConfig,
+    pub links: LinksConfig,
+    pub captcha: CaptchaConfig,
+    pub greetings: GreetingsConfig,
+    pub scheduler: SchedulerConfig,
+    pub night: NightConfig,
+    pub quotas: QuotasConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub pool_size: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarnsConfig {
+    /// Warns after which a user gets blocked.
+    pub max: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NicknamesConfig {
+    /// Length limits in characters.
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Words refused in nicknames on top of the built-in list.
+    pub banned_words: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocaleConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub default: Locale,
+    /// Directory with `<code>.lang` files overriding the built-in catalogs.
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub level: Level,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub capacity: usize,
+    /// Seconds a cached entry stays valid.
+    pub ttl: u64,
+    /// Seconds between two logs of the hit and miss counters, 0 to never log them.
+    pub stats_interval: u64,
+    /// Seconds between two saves of the message counters.
+    pub count_interval: u64,
+}
+
+/// Replaces the built-in permissions of a role; unset roles keep the defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionsConfig {
+    pub user: Option<Vec<Permission>>,
+    pub moderator: Option<Vec<Permission>>,
+    pub creator: Option<Vec<Permission>>,
+}
+
+/// How bot roles follow the Telegram administrators of a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    Off,
+    /// Telegram admins are moderators, and the owner a creator, in their synced chat only.
+    OneWay,
+    /// Like `one_way`, and bot moderators who are members of a synced chat are made admins there,
+    /// while admins blocked in the bot are removed.
+    TwoWay,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    pub mode: SyncMode,
+    /// Chats whose admins are synced, periodically and on admin changes; no other chat is.
+    pub chats: Vec<i64>,
+    /// Seconds between periodic syncs.
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReportsConfig {
+    /// Chat receiving reports; when unset every moderator gets them in private.
+    pub mod_chat: Option<i64>,
+    /// Seconds a user waits between two reports.
+    pub cooldown: u64,
+    /// Seconds a reported user stays muted.
+    pub mute_duration: u64,
+}
+
+/// Anti-flood thresholds used by chats that did not set their own.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FloodConfig {
+    /// Messages a user may send within `window` seconds.
+    pub messages: u32,
+    pub window: u64,
+    /// Identical messages a user may send within the window.
+    pub repeats: u32,
+    /// Stickers a user may send within the window.
+    pub stickers: u32,
+    pub action: FloodAction,
+    /// Seconds a flooding user stays muted.
+    pub mute_duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FiltersConfig {
+    /// Seconds a user stays muted by a blocklist entry with the `mute` action.
+    pub mute_duration: u64,
+}
+
+/// Link filter rules shared by every chat; chats add their own lists on top.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    pub allowlist: Vec<String>,
+    pub blocklist: Vec<String>,
+    /// Whether invites to other Telegram chats are deleted unless a chat says otherwise.
+    pub block_invites: bool,
+    /// Hours a new user has to wait before posting links, 0 to let them right away.
+    pub new_user_hours: u32,
+}
+
+/// Join captcha used by chats that did not choose their own.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptchaConfig {
+    pub kind: CaptchaKind,
+    /// Seconds a new member has to solve the challenge before being kicked.
+    pub timeout: u64,
+}
+
+/// Welcome and goodbye behaviour of chats that did not choose their own.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GreetingsConfig {
+    /// Seconds after which welcome and goodbye messages are deleted, 0 to keep them.
+    pub delete_after: u64,
+    /// Whether Telegram's join and leave notices are deleted.
+    pub clean_service: bool,
+}
+
+/// Night mode of chats that did not choose their own; the time zone is the scheduler's.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NightConfig {
+    /// Time of day the night starts at.
+    pub start: chrono::NaiveTime,
+    /// Time of day the night ends at.
+    pub end: chrono::NaiveTime,
+    pub restrict: NightRestriction,
+    /// Whether the start and end of the night are announced in the chat.
+    pub announce: bool,
+}
+
+/// Message quotas, whose rules every chat sets itself.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotasConfig {
+    /// Hours a member counts as a newcomer for, unless a chat says otherwise.
+    pub newcomer_hours: u32,
+    /// Seconds between two saves of the usage counters.
+    pub persist_interval: u64,
+}
+
+/// Scheduled posts and reminders.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Time zone schedules and reminders are written in, e.g. `Europe/Moscow`.
+    pub timezone: chrono_tz::Tz,
+    /// Most scheduled posts a chat may have.
+    pub max_per_chat: usize,
+    /// Most pending reminders a user may have.
+    pub max_reminders: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    Polling,
+    Webhook,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdatesConfig {
+    pub mode: UpdateMode,
+    #[serde(deserialize_with = "from_str_opt")]
+    pub webhook_url: Option<Url>,
+    pub listen: SocketAddr,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database: DatabaseConfig::default(),
+            creators: Vec::new(),
+            command_prefix: "/".to_owned(),
+            warns: WarnsConfig::default(),
+            nicknames: NicknamesConfig::default(),
+            locale: LocaleConfig::default(),
+            log: LogConfig::default(),
+            cache: CacheConfig::default(),
+            updates: UpdatesConfig::default(),
+            permissions: PermissionsConfig::default(),
+            sync: SyncConfig::default(),
+            reports: ReportsConfig::default(),
+            flood: FloodConfig::default(),
+            filters: FiltersConfig::default(),
+            links: LinksConfig::default(),
+            captcha: CaptchaConfig::default(),
+            greetings: GreetingsConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            night: NightConfig::default(),
+            quotas: QuotasConfig::default(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            pool_size: 10,
+        }
+    }
+}
+
+impl Default for WarnsConfig {
+    fn default() -> Self {
+        Self { max: 5 }
+    }
+}
+
+impl Default for NicknamesConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 2,
+            max_length: 32,
+            banned_words: Vec::new(),
+        }
+    }
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self {
+            default: Locale::Ru,
+            dir: None,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: Level::INFO }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            ttl: 60,
+            stats_interval: 3600,
+            count_interval: 30,
+        }
+    }
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        Self {
+            mode: UpdateMode::Polling,
+            webhook_url: None,
+            listen: SocketAddr::from(([0, 0, 0, 0], 8443)),
+        }
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            mode: SyncMode::Off,
+            chats: Vec::new(),
+            interval: 600,
+        }
+    }
+}
+
+impl Default for ReportsConfig {
+    fn default() -> Self {
+        Self {
+            mod_chat: None,
+            cooldown: 60,
+            mute_duration: 3600,
+        }
+    }
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        Self {
+            messages: 7,
+            window: 5,
+            repeats: 3,
+            stickers: 4,
+            action: FloodAction::Mute,
+            mute_duration: 600,
+        }
+    }
+}
+
+impl Default for FiltersConfig {
+    fn default() -> Self {
+        Self { mute_duration: 3600 }
+    }
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        Self {
+            allowlist: Vec::new(),
+            blocklist: ["bit.ly", "tinyurl.com", "cutt.ly", "clck.ru", "is.gd", "t.ly", "shorturl.at"]
+                .map(str::to_owned)
+                .to_vec(),
+            block_invites: true,
+            new_user_hours: 24,
+        }
+    }
+}
+
+impl Default for CaptchaConfig {
+    fn default() -> Self {
+        Self {
+            kind: CaptchaKind::Button,
+            timeout: 120,
+        }
+    }
+}
+
+impl Default for NightConfig {
+    fn default() -> Self {
+        Self {
+            start: chrono::NaiveTime::from_hms_opt(23, 0, 0).expect("valid time"),
+            end: chrono::NaiveTime::from_hms_opt(7, 0, 0).expect("valid time"),
+            restrict: NightRestriction::Media,
+            announce: true,
+        }
+    }
+}
+
+impl Default for QuotasConfig {
+    fn default() -> Self {
+        Self {
+            newcomer_hours: 24,
+            persist_interval: 60,
+        }
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            timezone: chrono_tz::UTC,
+            max_per_chat: 20,
+            max_reminders: 25,
+        }
+    }
+}
+
+impl Config {
+    /// Path given by `--config <path>`, the `CONFIG` variable or `config.toml`.
+    pub fn path(args: &[String]) -> (PathBuf, bool) {
+        if let Some(i) = args.iter().position(|a| a == "--config") {
+            if let Some(path) = args.get(i + 1) {
+                return (PathBuf::from(path), true);
+            }
+        }
+        match std::env::var(CONFIG) {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_PATH), false),
+        }
+    }
+
+    /// Reads the file, applies environment overrides and validates the result.
+    ///
+    /// A missing file is only an error if it was asked for explicitly.
+    pub fn load(path: &Path, required: bool) -> Result<Self, ConfigError> {
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(source) => toml::from_str(&source)
+                .map_err(|e| ConfigError::Parse(path.to_owned(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => Config::default(),
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+        let mut problems = Vec::new();
+        config.apply_env(&mut problems);
+        config.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    fn apply_env(&mut self, problems: &mut Vec<Problem>) {
+        env_override(problems, "DATABASE_URL", &mut self.database.url);
+        env_override(problems, "DATABASE_POOL_SIZE", &mut self.database.pool_size);
+        env_override(problems, "COMMAND_PREFIX", &mut self.command_prefix);
+        env_override(problems, "MAX_WARNS", &mut self.warns.max);
+        env_override(problems, "LOCALE", &mut self.locale.default);
+        env_override(problems, "LOG_LEVEL", &mut self.log.level);
+        env_override(problems, "CACHE_CAPACITY", &mut self.cache.capacity);
+        env_override(problems, "CACHE_TTL", &mut self.cache.ttl);
+        env_override(problems, "UPDATE_MODE", &mut self.updates.mode);
+        env_override(problems, "WEBHOOK_LISTEN", &mut self.updates.listen);
+        env_override(problems, "SYNC_MODE", &mut self.sync.mode);
+        if let Ok(dir) = std::env::var("LOCALES_DIR") {
+            self.locale.dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(url) = std::env::var("WEBHOOK_URL") {
+            match url.parse() {
+                Ok(url) => self.updates.webhook_url = Some(url),
+                Err(e) => problems.push(Problem::env("WEBHOOK_URL", e)),
+            }
+        }
+        if let Ok(creators) = std::env::var("CREATOR") {
+            match creators.split(',').map(|id| id.trim().parse()).collect() {
+                Ok(creators) => self.creators = creators,
+                Err(e) => problems.push(Problem::env("CREATOR", e)),
+            }
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<Problem>) {
+        if self.database.url.is_empty() {
+            problems.push(Problem::field(
+                "database.url",
+                "is required (set it in the file or with DATABASE_URL)",
+            ));
+        } else if !self.database.url.starts_with("postgres://")
+            && !self.database.url.starts_with("postgresql://")
+        {
+            problems.push(Problem::field("database.url", "must be a postgres:// URL"));
+        }
+        if self.database.pool_size == 0 {
+            problems.push(Problem::field("database.pool_size", "must be at least 1"));
+        }
+        if self.creators.is_empty() || self.creators.iter().any(|&id| id <= 0) {
+            problems.push(Problem::field(
+                "creators",
+                "must list at least one creator, by user id (set it in the file or with CREATOR)",
+            ));
+        }
+        if self.command_prefix.is_empty() || self.command_prefix.contains(char::is_whitespace) {
+            problems.push(Problem::field(
+                "command_prefix",
+                "must be non-empty and contain no whitespace",
+            ));
+        }
+        if self.warns.max < 1 {
+            problems.push(Problem::field("warns.max", "must be at least 1"));
+        }
+        if self.nicknames.min_length == 0 || self.nicknames.min_length > self.nicknames.max_length {
+            problems.push(Problem::field(
+                "nicknames.min_length",
+                "must be at least 1 and not above nicknames.max_length",
+            ));
+        }
+        if self.cache.ttl == 0 {
+            problems.push(Problem::field("cache.ttl", "must be at least 1 second"));
+        }
+        if self.cache.count_interval == 0 {
+            problems.push(Problem::field("cache.count_interval", "must be at least 1 second"));
+        }
+        if self.sync.interval == 0 {
+            problems.push(Problem::field("sync.interval", "must be at least 1 second"));
+        }
+        if self.sync.mode != SyncMode::Off && self.sync.chats.is_empty() {
+            problems.push(Problem::field(
+                "sync.chats",
+                "must list the synced chats when sync.mode is not off",
+            ));
+        }
+        if self.reports.mute_duration < 30 {
+            problems.push(Problem::field(
+                "reports.mute_duration",
+                "must be at least 30 seconds",
+            ));
+        }
+        if self.flood.messages == 0 || self.flood.repeats == 0 || self.flood.stickers == 0 {
+            problems.push(Problem::field(
+                "flood",
+                "messages, repeats and stickers must be at least 1",
+            ));
+        }
+        if self.flood.window == 0 {
+            problems.push(Problem::field("flood.window", "must be at least 1 second"));
+        }
+        if self.flood.mute_duration < 30 {
+            problems.push(Problem::field("flood.mute_duration", "must be at least 30 seconds"));
+        }
+        if self.filters.mute_duration < 30 {
+            problems.push(Problem::field("filters.mute_duration", "must be at least 30 seconds"));
+        }
+        if self.captcha.timeout < 10 {
+            problems.push(Problem::field("captcha.timeout", "must be at least 10 seconds"));
+        }
+        if self.scheduler.max_per_chat == 0 {
+            problems.push(Problem::field("scheduler.max_per_chat", "must be at least 1"));
+        }
+        if self.scheduler.max_reminders == 0 {
+            problems.push(Problem::field("scheduler.max_reminders", "must be at least 1"));
+        }
+        if self.quotas.persist_interval < 10 {
+            problems.push(Problem::field("quotas.persist_interval", "must be at least 10 seconds"));
+        }
+        if self.night.start == self.night.end {
+            problems.push(Problem::field("night.end", "must differ from night.start"));
+        }
+        if self.updates.mode == UpdateMode::Webhook && self.updates.webhook_url.is_none() {
+            problems.push(Problem::field(
+                "updates.webhook_url",
+                "is required when updates.mode is \"webhook\"",
+            ));
+        }
+    }
+
+    pub async fn repository(&self) -> Result<CachedRepository<Repository>, DbErr> {
+        let mut options = ConnectOptions::new(&self.database.url);
+        options.max_connections(self.database.pool_size);
+        Ok(CachedRepository::new(CacheOptions {
+            inner: RepositoryOptions {
+                database: Database::connect(options).await?,
+                max_warns: Some(self.warns.max),
+                permissions: self.permission_map(),
+            },
+            capacity: Some(self.cache.capacity),
+            ttl: Some(Duration::from_secs(self.cache.ttl)),
+        }))
+    }
+
+    pub fn permission_map(&self) -> PermissionMap {
+        let mut map = PermissionMap::default();
+        let roles = [
+            (Role::User, &self.permissions.user),
+            (Role::Moderator, &self.permissions.moderator),
+            (Role::Creator, &self.permissions.creator),
+        ];
+        for (role, permissions) in roles {
+            if let Some(permissions) = permissions {
+                map.set(role, permissions.iter().copied());
+            }
+        }
+        map
+    }
+
+    pub fn role_selector(&self) -> RoleSelector {
+        RoleSelector::new(self.creators.clone())
+    }
+
+    pub fn i18n(&self) -> Result<I18n, I18nError> {
+        I18n::load(self.locale.dir.as_deref(), self.locale.default)
+    }
+}
+
+fn env_override<T>(problems: &mut Vec<Problem>, var: &'static str, value: &mut T)
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Ok(raw) = std::env::var(var) {
+        match raw.parse() {
+            Ok(parsed) => *value = parsed,
+            Err(e) => problems.push(Problem::env(var, e)),
+        }
+    }
+}
+
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn from_str_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    from_str(deserializer).map(Some)
+}
+
+impl FromStr for UpdateMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "polling" => Ok(UpdateMode::Polling),
+            "webhook" => Ok(UpdateMode::Webhook),
+            _ => Err(format!("unknown mode '{s}', expected \"polling\" or \"webhook\"")),
+        }
+    }
+}
+
+impl FromStr for SyncMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(SyncMode::Off),
+            "one_way" => Ok(SyncMode::OneWay),
+            "two_way" => Ok(SyncMode::TwoWay),
+            _ => Err(format!(
+                "unknown mode '{s}', expected \"off\", \"one_way\" or \"two_way\""
+            )),
+        }
+    }
+}
+
+/// A single invalid setting.
+#[derive(Debug)]
+pub struct Problem {
+    pub source: String,
+    pub message: String,
+}
+
+impl Problem {
+    fn field(field: &str, message: &str) -> Self {
+        Self {
+            source: field.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    fn env(var: &str, err: impl fmt::Display) -> Self {
+        Self {
+            source: format!("${var}"),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(Vec<Problem>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "invalid {}: {}", path.display(), err),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  {}: {}", problem.source, problem.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, err) => Some(err),
+            ConfigError::Parse(_, err) => Some(err),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes the tests reading the environment, which is shared by the whole process.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const VALID: &str = r#"
+        creators = [1]
+        [database]
+        url = "postgres://localhost/pomka"
+    "#;
+
+    /// Sources of the problems `validate` finds in `source`.
+    fn problems(source: &str) -> Vec<String> {
+        let config: Config = toml::from_str(source).unwrap();
+        let mut problems = Vec::new();
+        config.validate(&mut problems);
+        problems.into_iter().map(|problem| problem.source).collect()
+    }
+
+    #[test]
+    fn accepts_a_minimal_config() {
+        assert!(problems(VALID).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let source = r#"
+            creators = []
+            command_prefix = "! "
+            [database]
+            url = "mysql://localhost/pomka"
+            [warns]
+            max = 0
+            [sync]
+            mode = "one_way"
+            [flood]
+            window = 0
+            [updates]
+            mode = "webhook"
+        "#;
+        assert_eq!(
+            problems(source),
+            [
+                "database.url",
+                "creators",
+                "command_prefix",
+                "warns.max",
+                "sync.chats",
+                "flood.window",
+                "updates.webhook_url",
+            ]
+        );
+        let night = format!("{VALID}\n[night]\nstart = \"07:00\"\nend = \"07:00\"");
+        assert_eq!(problems(&night), ["night.end"]);
+        let channel = VALID.replace("[1]", "[-100]");
+        assert_eq!(problems(&channel), ["creators"]);
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(toml::from_str::<Config>("[warns]\nmaximum = 3").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let _env = ENV.lock().unwrap();
+        let mut config: Config = toml::from_str(VALID).unwrap();
+        std::env::set_var("DATABASE_URL", "postgres://env/pomka");
+        std::env::set_var("CREATOR", "2, 3");
+        std::env::set_var("SYNC_MODE", "two_way");
+        std::env::set_var("MAX_WARNS", "many");
+        let mut problems = Vec::new();
+        config.apply_env(&mut problems);
+        for var in ["DATABASE_URL", "CREATOR", "SYNC_MODE", "MAX_WARNS"] {
+            std::env::remove_var(var);
+        }
+        assert_eq!(config.database.url, "postgres://env/pomka");
+        assert_eq!(config.creators, [2, 3]);
+        assert_eq!(config.sync.mode, SyncMode::TwoWay);
+        assert_eq!(config.warns.max, WarnsConfig::default().max);
+        let sources: Vec<_> = problems.iter().map(|problem| problem.source.as_str()).collect();
+        assert_eq!(sources, ["$MAX_WARNS"]);
+    }
+
+    /// What `--check-config` runs on the example shipped with the bot.
+    #[test]
+    fn example_config_passes_the_check_once_creators_are_set() {
+        let _env = ENV.lock().unwrap();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+        let Err(ConfigError::Invalid(problems)) = Config::load(&path, true) else {
+            panic!("the example lists no creators");
+        };
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].source, "creators");
+
+        std::env::set_var("CREATOR", "1");
+        let config = Config::load(&path, true);
+        std::env::remove_var("CREATOR");
+        let config = config.unwrap();
+        assert_eq!(config.creators, [1]);
+        assert!(config.i18n().is_ok());
+    }
+}
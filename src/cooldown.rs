@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Lets an event through at most once per `window` for every key.
+pub struct Cooldown<K> {
+    window: Duration,
+    last: Mutex<HashMap<K, Instant>>,
+}
+
+impl<K> Cooldown<K>
+where
+    K: Hash + Eq,
+{
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `true` and starts a new window if the key is not cooling down.
+    pub fn try_acquire(&self, key: K) -> bool {
+        let now = Instant::now();
+        let mut last = self.last.lock().unwrap();
+        last.retain(|_, at| now.duration_since(*at) < self.window);
+        if last.contains_key(&key) {
+            return false;
+        }
+        last.insert(key, now);
+        true
+    }
+}
@@ -1,12 +1,66 @@
+use crate::i18n::Locale;
+use std::fmt;
+use teloxide::types::{ChatId, Message, MessageId};
 use teloxide::RequestError;
 use crate::repository::db::RepoError;
 
+pub mod reply;
+
 #[derive(Debug)]
 pub enum Error {
     Repo(RepoError),
     Request(RequestError),
 }
 
+impl Error {
+    /// Whether the error is a normal outcome of a user's request rather than a bug or outage.
+    pub fn is_expected(&self) -> bool {
+        !matches!(
+            self,
+            Error::Request(_) | Error::Repo(RepoError::UnknownError | RepoError::InternalDbError(_))
+        )
+    }
+
+    /// Whether the user should get a reply about the error at all.
+    pub fn should_reply(&self) -> bool {
+        !matches!(self, Error::Request(_))
+    }
+
+    /// Stable machine-readable code, safe to log and match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Repo(err) => err.code(),
+            Error::Request(_) => "request",
+        }
+    }
+
+    /// Key of the user-facing message the chat reply layer should send.
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            Error::Repo(err) => err.message_key(),
+            Error::Request(_) => "error-internal",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Repo(err) => write!(f, "repository error: {err}"),
+            Error::Request(err) => write!(f, "telegram request error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Repo(err) => Some(err),
+            Error::Request(err) => Some(err),
+        }
+    }
+}
+
 impl From<RequestError> for Error {
     fn from(err: RequestError) -> Self {
         Error::Request(err)
@@ -17,4 +71,36 @@ impl From<RepoError> for Error {
     fn from(err: RepoError) -> Self {
         Error::Repo(err)
     }
-}
\ No newline at end of file
+}
+
+/// An [`Error`] together with the message whose handling caused it.
+#[derive(Debug)]
+pub struct HandlerError {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub locale: Locale,
+    pub error: Error,
+}
+
+impl HandlerError {
+    pub fn new(message: &Message, locale: Locale, error: impl Into<Error>) -> Self {
+        Self {
+            chat_id: message.chat.id,
+            message_id: message.id,
+            locale,
+            error: error.into(),
+        }
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in chat {} (message {}): {}", self.chat_id, self.message_id, self.error)
+    }
+}
+
+impl std::error::Error for HandlerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,103 @@
+use super::{Error, HandlerError};
+use crate::cooldown::Cooldown;
+use crate::i18n::{I18n, Locale};
+use crate::tr;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::error_handlers::ErrorHandler;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::ChatId;
+
+/// Dispatcher error handler that answers the message which caused the error.
+///
+/// Expected errors (missing permissions, unknown users, ...) get a friendly
+/// reply, unexpected ones are logged with their whole source chain. The same
+/// error is answered at most once per [`Self::COOLDOWN`] in every chat.
+pub struct ReplyErrorHandler {
+    bot: Bot,
+    i18n: Arc<I18n>,
+    cooldown: Cooldown<(ChatId, &'static str)>,
+}
+
+impl ReplyErrorHandler {
+    const COOLDOWN: Duration = Duration::from_secs(10);
+
+    pub fn new(bot: Bot, i18n: Arc<I18n>) -> Arc<Self> {
+        Arc::new(Self {
+            bot,
+            i18n,
+            cooldown: Cooldown::new(Self::COOLDOWN),
+        })
+    }
+
+    async fn handle(&self, err: HandlerError) {
+        if err.error.is_expected() {
+            tracing::debug!(
+                "Handler error in chat {} (message {}): [{}] {}",
+                err.chat_id,
+                err.message_id,
+                err.error.code(),
+                err.error
+            );
+        } else {
+            tracing::error!(
+                "Handler error in chat {} (message {}): [{}] {} ({:?})",
+                err.chat_id,
+                err.message_id,
+                err.error.code(),
+                err.error,
+                err.error
+            );
+        }
+        if !err.error.should_reply() {
+            return;
+        }
+        if !self.cooldown.try_acquire((err.chat_id, err.error.code())) {
+            return;
+        }
+        if let Err(e) = self
+            .bot
+            .send_message(
+                err.chat_id,
+                tr!(self.i18n, err.locale, err.error.message_key()),
+            )
+            .reply_to(err.message_id)
+            .await
+        {
+            tracing::warn!("Failed to reply with error to chat {}: {}", err.chat_id, e);
+        }
+    }
+}
+
+impl ErrorHandler<HandlerError> for ReplyErrorHandler {
+    fn handle_error(
+        self: Arc<Self>,
+        error: HandlerError,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move { self.handle(error).await })
+    }
+}
+
+/// Reports a failed button press to the user who pressed it.
+pub async fn alert(bot: &Bot, i18n: &I18n, locale: Locale, query: &CallbackQuery, err: Error) {
+    if !err.is_expected() {
+        tracing::error!(
+            "Callback query from {} failed: [{}] {} ({:?})",
+            query.from.id,
+            err.code(),
+            err,
+            err
+        );
+    }
+    if let Err(e) = bot
+        .answer_callback_query(query.id.clone())
+        .text(tr!(i18n, locale, err.message_key()))
+        .show_alert(true)
+        .await
+    {
+        tracing::warn!("Failed to answer callback query from {}: {}", query.from.id, e);
+    }
+}
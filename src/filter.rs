@@ -1,30 +1,408 @@
-use teloxide::Bot;
-use teloxide::prelude::*;
+use crate::command::Context;
+use crate::config::Config;
 use crate::error::Error;
-use crate::repository::RepositoryTrait;
-use crate::role::RoleSelector;
+use crate::i18n::I18n;
+use crate::models::filters::{Action, Kind};
+use crate::models::prelude::{FilterModel, UserModel};
+use crate::moderation::{penalize, Outcome, Penalty};
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::register::sender_id;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use regex::{Regex, RegexBuilder};
+use sea_orm::{ActiveEnum, Iterable};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+use teloxide::types::{Me, ParseMode};
+use teloxide::utils::html;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Upper bound on the compiled size of a single pattern.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Characters merged with the look-alike they are swapped for: Cyrillic letters with their Latin
+/// twins and digits with letters.
+const HOMOGLYPHS: &[(char, char)] = &[
+    ('а', 'a'),
+    ('в', 'b'),
+    ('е', 'e'),
+    ('ё', 'e'),
+    ('з', '3'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('к', 'k'),
+    ('м', 'm'),
+    ('н', 'h'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('т', 't'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('ѕ', 's'),
+    ('ԁ', 'd'),
+    ('ӏ', 'l'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('0', 'o'),
+];
+
+/// Characters that render as nothing and only serve to split words.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{061C}'
+            | '\u{115F}'
+            | '\u{1160}'
+            | '\u{17B4}'
+            | '\u{17B5}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{206A}'..='\u{206F}'
+            | '\u{3164}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FEFF}'
+            | '\u{FFA0}'
+    )
+}
+
+/// Compatibility-normalized, lowercased `text` without invisible characters and stray diacritics.
+fn fold(text: &str) -> String {
+    text.nfkc()
+        .filter(|c| !is_invisible(*c) && !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// [`fold`]ed `text` with look-alike letters of both alphabets merged.
+fn skeleton(text: &str) -> String {
+    fold(text)
+        .chars()
+        .map(|c| {
+            HOMOGLYPHS
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to)
+        })
+        .collect()
+}
 
-pub struct Handler<R>{
-    repo: R,
-    role_selector: RoleSelector,
+fn build(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
 }
 
-impl<R> Handler<R>
-where R: RepositoryTrait {
-    pub fn new(repo: R, role_selector: RoleSelector) -> Self {
-        Self { repo, role_selector }
+/// Compiles an entry; words and wildcards are matched on the [`skeleton`] of the text.
+fn compile(kind: Kind, pattern: &str) -> Result<Regex, regex::Error> {
+    match kind {
+        Kind::Word => build(&format!(r"\b{}\b", regex::escape(&skeleton(pattern)))),
+        Kind::Wildcard => {
+            let body: String = skeleton(pattern)
+                .split('*')
+                .map(|part| {
+                    part.split('?')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+                .join(".*");
+            build(&body)
+        }
+        Kind::Regex => build(pattern),
     }
+}
 
-    pub async fn filter(bot: Bot, message: Message) -> Result<(), Error> {
-        if let Some(text) = message.text() {
-            let (first, after) = text.split_once(char::is_whitespace)
-                    .unwrap_or((text, ""));
-            match first {
-                _ => ()
-            };
+/// The compiled blocklist of one chat and the entries it was built from.
+struct Compiled {
+    source: Vec<FilterModel>,
+    rules: Vec<(FilterModel, Regex)>,
+}
+
+impl Compiled {
+    fn new(source: Vec<FilterModel>) -> Self {
+        let rules = source
+            .iter()
+            .filter_map(|filter| match compile(filter.kind, &filter.pattern) {
+                Ok(regex) => Some((filter.clone(), regex)),
+                Err(e) => {
+                    tracing::warn!("Skipping filter {} of chat {}: {}", filter.id, filter.chat_id, e);
+                    None
+                }
+            })
+            .collect();
+        Self { source, rules }
+    }
+
+    /// The first entry matching `text`.
+    fn find(&self, text: &str) -> Option<&FilterModel> {
+        let (folded, skeleton) = (fold(text), skeleton(text));
+        self.rules
+            .iter()
+            .find(|(filter, regex)| match filter.kind {
+                Kind::Regex => regex.is_match(&folded) || regex.is_match(&skeleton),
+                Kind::Word | Kind::Wildcard => regex.is_match(&skeleton),
+            })
+            .map(|(filter, _)| filter)
+    }
+}
+
+/// Compiled blocklists of every chat, rebuilt whenever a chat's entries change.
+#[derive(Default)]
+pub struct Handler {
+    compiled: Mutex<HashMap<i64, Arc<Compiled>>>,
+}
+
+impl Handler {
+    fn compiled(&self, chat: i64, filters: Vec<FilterModel>) -> Arc<Compiled> {
+        let mut compiled = self.compiled.lock().unwrap();
+        match compiled.get(&chat) {
+            Some(cached) if cached.source == filters => cached.clone(),
+            _ => {
+                let fresh = Arc::new(Compiled::new(filters));
+                compiled.insert(chat, fresh.clone());
+                fresh
+            }
+        }
+    }
+
+    /// Checks every group message against the chat's blocklist, deleting matches and punishing
+    /// their authors on behalf of the bot. Members with the `bypass_filters` permission,
+    /// moderators by default, are exempt.
+    pub async fn filter(
+        bot: Bot,
+        msg: Message,
+        me: Me,
+        i18n: Arc<I18n>,
+        repo: Arc<Repo>,
+        config: Arc<Config>,
+        handler: Arc<Handler>,
+    ) -> bool {
+        if msg.chat.is_private() {
+            return true;
+        }
+        let Some(sender) = sender_id(&msg) else {
+            return true;
+        };
+        match handler.apply(&bot, &msg, &me, &i18n, &repo, &config, sender).await {
+            Ok(deleted) => !deleted,
+            Err(e) => {
+                tracing::error!("Filter failed for {} in chat {}: {}", sender, msg.chat.id, e);
+                true
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn apply(
+        &self,
+        bot: &Bot,
+        msg: &Message,
+        me: &Me,
+        i18n: &I18n,
+        repo: &Repo,
+        config: &Config,
+        sender: i64,
+    ) -> Result<bool, Error> {
+        let Some(text) = msg.text().or_else(|| msg.caption()) else {
+            return Ok(false);
+        };
+        let chat = msg.chat.id;
+        let filters = repo.get_filters(chat.0).await?;
+        if filters.is_empty() {
+            return Ok(false);
+        }
+        let compiled = self.compiled(chat.0, filters);
+        let Some(filter) = compiled.find(text) else {
+            return Ok(false);
+        };
+        if repo.get_permissions(sender, Some(chat.0)).await?.contains(&Permission::BypassFilters) {
+            return Ok(false);
+        }
+        let user = repo.get_user(sender).await?;
+        let by = me.id.0 as i64;
+        repo.filter_hit(by, sender, filter, text.to_owned()).await?;
+        bot.delete_message(chat, msg.id).await?;
+        if let Err(e) = Self::punish(bot, i18n, repo, config, chat, by, &user, filter).await {
+            tracing::error!("Failed to punish {} for a filtered message in {}: {}", sender, chat, e);
         }
+        Ok(true)
+    }
+
+    /// Applies the penalty of the `filter` that `user`'s message matched and sends its reply.
+    #[allow(clippy::too_many_arguments)]
+    async fn punish(
+        bot: &Bot,
+        i18n: &I18n,
+        repo: &Repo,
+        config: &Config,
+        chat: ChatId,
+        by: i64,
+        user: &UserModel,
+        filter: &FilterModel,
+    ) -> Result<(), Error> {
+        let settings = repo.get_chat_settings(chat.0).await?;
+        let locale = i18n.locale(settings.locale.as_deref(), None);
+        let penalty = match filter.action {
+            Action::Delete => None,
+            Action::Warn => Some(Penalty::Warn),
+            Action::Mute => Some(Penalty::Mute(config.filters.mute_duration)),
+            Action::Ban => Some(Penalty::Ban),
+        };
+        let announcement = match penalty {
+            Some(penalty) => Some(match penalize(bot, repo, by, chat, user, penalty).await? {
+                Outcome::Warned(warns) => tr!(
+                    i18n,
+                    locale,
+                    "moderation-warned",
+                    user = mention(user),
+                    warns = tr!(i18n, locale, "warns", count: warns),
+                    limit = settings.warn_limit.unwrap_or(config.warns.max)
+                ),
+                Outcome::WarnBanned => {
+                    tr!(i18n, locale, "moderation-warn-banned", user = mention(user))
+                }
+                Outcome::Muted(minutes) => {
+                    tr!(i18n, locale, "moderation-muted", count: minutes, user = mention(user))
+                }
+                Outcome::Banned => tr!(i18n, locale, "moderation-banned", user = mention(user)),
+            }),
+            None => None,
+        };
+        let text = match (&filter.reply, announcement) {
+            (Some(reply), Some(announcement)) => format!("{announcement}\n{}", html::escape(reply)),
+            (Some(reply), None) => html::escape(reply),
+            (None, Some(announcement)) => announcement,
+            (None, None) => return Ok(()),
+        };
+        bot.send_message(chat, text).parse_mode(ParseMode::Html).await?;
         Ok(())
     }
 }
 
+/// `/filter add <word|regex|wildcard> <delete|warn|mute|ban> <pattern>`, with an optional reply
+/// on the next line, `/filter del <pattern>` and `/filter list`.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let chat = ctx.msg.chat.id.0;
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let (line, reply) = args
+        .split_once('\n')
+        .map(|(line, reply)| (line, Some(reply.trim()).filter(|r| !r.is_empty())))
+        .unwrap_or((args.as_str(), None));
+    let mut words = line.trim().splitn(2, char::is_whitespace);
+    match (words.next().unwrap_or_default(), words.next().map(str::trim)) {
+        ("add", Some(rest)) => {
+            let mut words = rest.splitn(3, char::is_whitespace);
+            let parsed = (
+                words.next().and_then(|w| Kind::iter().find(|k| k.to_value() == w)),
+                words.next().and_then(|w| Action::iter().find(|a| a.to_value() == w)),
+                words.next().map(str::trim).filter(|p| !p.is_empty()),
+            );
+            let (Some(kind), Some(action), Some(pattern)) = parsed else {
+                ctx.reply(tr!(i18n, locale, "filter-usage")).await?;
+                return Ok(());
+            };
+            if let Err(e) = compile(kind, pattern) {
+                let error = html::escape(&e.to_string());
+                ctx.reply_html(tr!(i18n, locale, "filter-invalid", error = error))
+                    .await?;
+                return Ok(());
+            }
+            let reply = reply.map(str::to_owned);
+            ctx.repo
+                .add_filter(by, chat, kind, pattern.to_owned(), action, reply)
+                .await?;
+            let pattern = html::escape(pattern);
+            ctx.reply_html(tr!(i18n, locale, "filter-added", pattern = pattern))
+                .await?;
+        }
+        ("del", Some(pattern)) if !pattern.is_empty() => {
+            ctx.repo.delete_filter(by, chat, pattern.to_owned()).await?;
+            let pattern = html::escape(pattern);
+            ctx.reply_html(tr!(i18n, locale, "filter-deleted", pattern = pattern))
+                .await?;
+        }
+        ("list", None) => {
+            ctx.repo.authorize(by, Permission::EditSettings, None, Some(chat)).await?;
+            let filters = ctx.repo.get_filters(chat).await?;
+            if filters.is_empty() {
+                ctx.reply(tr!(i18n, locale, "filter-empty")).await?;
+                return Ok(());
+            }
+            let mut lines = vec![tr!(i18n, locale, "filter-list", count: filters.len() as i64)];
+            for filter in &filters {
+                lines.push(format!(
+                    "• {} <code>{}</code> → {}",
+                    filter.kind.to_value(),
+                    html::escape(&filter.pattern),
+                    filter.action.to_value()
+                ));
+            }
+            ctx.reply_html(lines.join("\n")).await?;
+        }
+        _ => {
+            ctx.reply(tr!(i18n, locale, "filter-usage")).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn matches(kind: Kind, pattern: &str, text: &str) -> bool {
+        let regex = compile(kind, pattern).unwrap();
+        match kind {
+            Kind::Regex => regex.is_match(&fold(text)) || regex.is_match(&skeleton(text)),
+            Kind::Word | Kind::Wildcard => regex.is_match(&skeleton(text)),
+        }
+    }
+
+    #[test]
+    fn fold_drops_invisible_characters_and_diacritics() {
+        assert_eq!(fold("Sp\u{200B}am"), "spam");
+        assert_eq!(fold("s\u{0336}p\u{0336}a\u{0336}m"), "spam");
+        assert_eq!(fold("ｓｐａｍ"), "spam");
+        assert_eq!(fold("Ёлка"), "ёлка");
+    }
+
+    #[test]
+    fn skeleton_merges_look_alikes() {
+        assert_eq!(skeleton("сасао"), "cacao");
+        assert_eq!(skeleton("Sp0m"), "spom");
+        assert_eq!(skeleton("spam"), skeleton("ѕраm"));
+    }
+
+    #[test]
+    fn words_match_whole_words_only() {
+        assert!(matches(Kind::Word, "spam", "buy ЅРАМ now"));
+        assert!(matches(Kind::Word, "spam", "s\u{200B}pam"));
+        assert!(!matches(Kind::Word, "spam", "spammer"));
+    }
+
+    #[test]
+    fn wildcards_match_any_run() {
+        assert!(matches(Kind::Wildcard, "free*money", "free easy money"));
+        assert!(matches(Kind::Wildcard, "c?t", "a cat"));
+        assert!(!matches(Kind::Wildcard, "c?t", "coat"));
+    }
+
+    #[test]
+    fn regexes_match_folded_text() {
+        assert!(matches(Kind::Regex, r"^t\.me/", "T.ME/joinchat"));
+        assert!(!matches(Kind::Regex, r"^t\.me/", "see t.me/x"));
+    }
+}
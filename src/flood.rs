@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::i18n::I18n;
+use crate::models::chat_settings::{FloodAction, Module};
+use crate::models::prelude::ChatSettingsModel;
+use crate::moderation::{penalize, Outcome, Penalty};
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::register::sender_id;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::{Me, ParseMode};
+
+/// Users silent for this long are forgotten whatever the chat's window.
+const FORGET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Thresholds of one chat, merged from its settings and the configuration.
+struct Limits {
+    messages: usize,
+    window: Duration,
+    repeats: usize,
+    stickers: usize,
+    action: FloodAction,
+}
+
+impl Limits {
+    fn new(config: &Config, settings: &ChatSettingsModel) -> Self {
+        let flood = settings.flood();
+        Self {
+            messages: flood.messages.unwrap_or(config.flood.messages) as usize,
+            window: Duration::from_secs(flood.window.unwrap_or(config.flood.window)),
+            repeats: flood.repeats.unwrap_or(config.flood.repeats) as usize,
+            stickers: flood.stickers.unwrap_or(config.flood.stickers) as usize,
+            action: flood.action.unwrap_or(config.flood.action),
+        }
+    }
+}
+
+/// A message as the anti-flood remembers it.
+struct Event {
+    at: Instant,
+    /// Hash of the text, caption or sticker, to spot repeats.
+    content: Option<u64>,
+    sticker: bool,
+}
+
+impl Event {
+    fn new(msg: &Message) -> Self {
+        let content = msg
+            .text()
+            .or_else(|| msg.caption())
+            .or_else(|| msg.sticker().map(|s| s.file.unique_id.as_str()))
+            .map(|content| {
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                hasher.finish()
+            });
+        Self {
+            at: Instant::now(),
+            content,
+            sticker: msg.sticker().is_some(),
+        }
+    }
+}
+
+/// Sliding windows of recent messages of every user in every chat.
+#[derive(Default)]
+pub struct Flood {
+    events: Mutex<HashMap<(ChatId, i64), VecDeque<Event>>>,
+}
+
+impl Flood {
+    /// Records a message and returns whether it crosses a threshold, starting the sender's
+    /// window over if it does.
+    fn record(&self, chat: ChatId, user: i64, event: Event, limits: &Limits) -> bool {
+        let mut events = self.events.lock().unwrap();
+        events.retain(|_, window| window.back().is_some_and(|e| e.at.elapsed() < FORGET_AFTER));
+        let window = events.entry((chat, user)).or_default();
+        while window.front().is_some_and(|e| e.at.elapsed() >= limits.window) {
+            window.pop_front();
+        }
+        let content = event.content;
+        window.push_back(event);
+        let repeats = content.map_or(0, |c| window.iter().filter(|e| e.content == Some(c)).count());
+        let stickers = window.iter().filter(|e| e.sticker).count();
+        let flooding = window.len() > limits.messages
+            || repeats > limits.repeats
+            || stickers > limits.stickers;
+        if flooding {
+            window.clear();
+        }
+        flooding
+    }
+}
+
+/// Watches every group message and mutes or warns, on behalf of the bot, whoever floods.
+///
+/// Members with the `bypass_flood` permission, moderators by default, and anonymous admins are
+/// never punished.
+pub async fn check(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+    flood: Arc<Flood>,
+) -> bool {
+    if msg.chat.is_private() {
+        return true;
+    }
+    let Some(sender) = sender_id(&msg) else {
+        return true;
+    };
+    match punish(&bot, &msg, &me, &i18n, &repo, &config, &flood, sender).await {
+        Ok(punished) => !punished,
+        Err(e) => {
+            tracing::error!("Anti-flood failed for {} in chat {}: {}", sender, msg.chat.id, e);
+            true
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn punish(
+    bot: &Bot,
+    msg: &Message,
+    me: &Me,
+    i18n: &I18n,
+    repo: &Repo,
+    config: &Config,
+    flood: &Flood,
+    sender: i64,
+) -> Result<bool, Error> {
+    let chat = msg.chat.id;
+    let settings = repo.get_chat_settings(chat.0).await?;
+    if !settings.module_enabled(Module::AntiFlood) {
+        return Ok(false);
+    }
+    let limits = Limits::new(config, &settings);
+    if !flood.record(chat, sender, Event::new(msg), &limits) {
+        return Ok(false);
+    }
+    if repo.get_permissions(sender, Some(chat.0)).await?.contains(&Permission::BypassFlood) {
+        return Ok(false);
+    }
+    let user = repo.get_user(sender).await?;
+    let by = me.id.0 as i64;
+    let locale = i18n.locale(settings.locale.as_deref(), None);
+    let penalty = match limits.action {
+        FloodAction::Mute => Penalty::Mute(config.flood.mute_duration),
+        FloodAction::Warn => Penalty::Warn,
+    };
+    let text = match penalize(bot, repo, by, chat, &user, penalty).await? {
+        Outcome::Muted(minutes) => {
+            tr!(i18n, locale, "flood-muted", count: minutes, user = mention(&user))
+        }
+        Outcome::Warned(warns) => tr!(
+            i18n,
+            locale,
+            "flood-warned",
+            user = mention(&user),
+            warns = tr!(i18n, locale, "warns", count: warns),
+            limit = settings.warn_limit.unwrap_or(config.warns.max)
+        ),
+        Outcome::WarnBanned | Outcome::Banned => {
+            tr!(i18n, locale, "moderation-warn-banned", user = mention(&user))
+        }
+    };
+    if let Err(e) = bot.send_message(chat, text).parse_mode(ParseMode::Html).await {
+        tracing::warn!("Failed to announce flood penalty in chat {}: {}", chat, e);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAT: ChatId = ChatId(-100);
+
+    fn limits() -> Limits {
+        Limits {
+            messages: 3,
+            window: Duration::from_secs(10),
+            repeats: 2,
+            stickers: 1,
+            action: FloodAction::Mute,
+        }
+    }
+
+    fn text(content: u64) -> Event {
+        Event {
+            at: Instant::now(),
+            content: Some(content),
+            sticker: false,
+        }
+    }
+
+    fn sticker(content: u64) -> Event {
+        Event {
+            sticker: true,
+            ..text(content)
+        }
+    }
+
+    #[test]
+    fn too_many_messages_flood_and_start_over() {
+        let flood = Flood::default();
+        for content in 1..=3 {
+            assert!(!flood.record(CHAT, 1, text(content), &limits()));
+        }
+        assert!(flood.record(CHAT, 1, text(4), &limits()));
+        assert!(!flood.record(CHAT, 1, text(5), &limits()));
+    }
+
+    #[test]
+    fn old_messages_leave_the_window() {
+        let flood = Flood::default();
+        for content in 1..=3 {
+            let old = Event {
+                at: Instant::now() - Duration::from_secs(11),
+                ..text(content)
+            };
+            assert!(!flood.record(CHAT, 1, old, &limits()));
+        }
+        assert!(!flood.record(CHAT, 1, text(4), &limits()));
+    }
+
+    #[test]
+    fn repeats_flood_sooner() {
+        let flood = Flood::default();
+        assert!(!flood.record(CHAT, 1, text(7), &limits()));
+        assert!(!flood.record(CHAT, 1, text(7), &limits()));
+        assert!(flood.record(CHAT, 1, text(7), &limits()));
+    }
+
+    #[test]
+    fn stickers_flood_sooner() {
+        let flood = Flood::default();
+        assert!(!flood.record(CHAT, 1, sticker(1), &limits()));
+        assert!(flood.record(CHAT, 1, sticker(2), &limits()));
+    }
+
+    #[test]
+    fn windows_are_per_user_and_chat() {
+        let flood = Flood::default();
+        assert!(!flood.record(CHAT, 1, sticker(1), &limits()));
+        assert!(!flood.record(CHAT, 2, sticker(1), &limits()));
+        assert!(!flood.record(ChatId(-200), 1, sticker(1), &limits()));
+    }
+}
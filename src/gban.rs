@@ -0,0 +1,232 @@
+use crate::command::Context;
+use crate::error::{Error, HandlerError};
+use crate::models::chat_settings::Module;
+use crate::models::prelude::GlobalBanModel;
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::{ChatMemberKind, InputFile, User, UserId};
+use teloxide::utils::html;
+
+/// Bot state key of the chats the bot is an administrator in.
+const ADMIN_CHATS_STATE: &str = "admin_chats";
+/// Largest ban list `/gbans import` downloads.
+const MAX_IMPORT_SIZE: u32 = 1 << 20;
+
+/// An entry of an exported ban list.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    user_id: i64,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+async fn admin_chats(repo: &Repo) -> Result<BTreeSet<i64>, Error> {
+    Ok(repo
+        .get_state(ADMIN_CHATS_STATE.to_owned())
+        .await?
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default())
+}
+
+/// Keeps track of the chats the bot administers as it is promoted, demoted or removed.
+pub async fn my_chat_member(update: ChatMemberUpdated, repo: Arc<Repo>) -> Result<(), HandlerError> {
+    let chat = update.chat.id.0;
+    let admin = matches!(
+        update.new_chat_member.kind,
+        ChatMemberKind::Administrator(_) | ChatMemberKind::Owner(_)
+    );
+    let result = async {
+        let mut chats = admin_chats(&repo).await?;
+        let changed = if admin { chats.insert(chat) } else { chats.remove(&chat) };
+        if changed {
+            repo.set_state(ADMIN_CHATS_STATE.to_owned(), serde_json::json!(chats))
+                .await?;
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!("Failed to update the administered chats with {}: {}", chat, e);
+    }
+    Ok(())
+}
+
+/// Chats that take part in global bans: the administered ones that did not opt out, and `current`.
+async fn federated_chats(repo: &Repo, current: ChatId) -> Result<Vec<ChatId>, Error> {
+    let mut chats = admin_chats(repo).await?;
+    if !current.is_user() {
+        chats.insert(current.0);
+    }
+    let mut federated = Vec::new();
+    for chat in chats {
+        if repo.get_chat_settings(chat).await?.module_enabled(Module::GlobalBans) {
+            federated.push(ChatId(chat));
+        }
+    }
+    Ok(federated)
+}
+
+/// Bans a new member who is on the global ban list, unless the chat opted out.
+///
+/// Returns the ban if the member was banned.
+pub async fn enforce(
+    bot: &Bot,
+    repo: &Repo,
+    chat: ChatId,
+    user: &User,
+) -> Result<Option<GlobalBanModel>, Error> {
+    let Some(ban) = repo.get_global_ban(user.id.0 as i64).await? else {
+        return Ok(None);
+    };
+    if !repo.get_chat_settings(chat.0).await?.module_enabled(Module::GlobalBans) {
+        return Ok(None);
+    }
+    bot.ban_chat_member(chat, user.id).await?;
+    Ok(Some(ban))
+}
+
+/// `/gban <targets> [reason]` puts the targets on the global ban list and bans them in every
+/// chat the bot administers.
+pub async fn gban(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let targets = ctx.targets(&args, 0).await?;
+    if targets.users.is_empty() {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "gban-usage")).await?;
+        return Ok(());
+    }
+    let reason = Some(targets.rest.trim().to_owned()).filter(|r| !r.is_empty());
+    let chats = federated_chats(ctx.repo, ctx.msg.chat.id).await?;
+    let mut lines = Vec::new();
+    for target in &targets.users {
+        ctx.repo.global_ban(by, target.id, reason.clone()).await?;
+        let mut banned = 0;
+        for &chat in &chats {
+            let result = if target.is_channel() {
+                ctx.bot.ban_chat_sender_chat(chat, ChatId(target.id)).await
+            } else {
+                ctx.bot.ban_chat_member(chat, UserId(target.id as u64)).await
+            };
+            match result {
+                Ok(_) => banned += 1,
+                Err(e) => tracing::warn!("Failed to ban {} in chat {}: {}", target.id, chat, e),
+            }
+        }
+        lines.push(tr!(
+            ctx.i18n,
+            ctx.locale,
+            "gban-done",
+            count: banned,
+            user = mention(target)
+        ));
+    }
+    if let Some(reason) = &reason {
+        let reason = html::escape(reason);
+        lines.push(tr!(ctx.i18n, ctx.locale, "moderation-reason", reason = reason));
+    }
+    ctx.reply_html(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// `/ungban <targets>` takes the targets off the global ban list and lifts their bans.
+pub async fn ungban(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let targets = ctx.targets(&args, 0).await?;
+    if targets.users.is_empty() {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "gban-usage")).await?;
+        return Ok(());
+    }
+    let chats = federated_chats(ctx.repo, ctx.msg.chat.id).await?;
+    let mut lines = Vec::new();
+    for target in &targets.users {
+        ctx.repo.global_unban(by, target.id).await?;
+        for &chat in &chats {
+            let result = if target.is_channel() {
+                ctx.bot.unban_chat_sender_chat(chat, ChatId(target.id)).await
+            } else {
+                ctx.bot
+                    .unban_chat_member(chat, UserId(target.id as u64))
+                    .only_if_banned(true)
+                    .await
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to unban {} in chat {}: {}", target.id, chat, e);
+            }
+        }
+        lines.push(tr!(ctx.i18n, ctx.locale, "ungban-done", user = mention(target)));
+    }
+    ctx.reply_html(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// `/gbans export` sends the global ban list as a JSON file, `/gbans import` in reply to such a
+/// file adds its entries.
+pub async fn gbans(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    match args.trim() {
+        "export" => {
+            ctx.repo.authorize(by, Permission::GlobalBan, None, None).await?;
+            let entries: Vec<Entry> = ctx
+                .repo
+                .get_global_bans()
+                .await?
+                .into_iter()
+                .map(|ban| Entry {
+                    user_id: ban.user_id,
+                    reason: ban.reason,
+                })
+                .collect();
+            let json = serde_json::to_vec_pretty(&entries).unwrap_or_default();
+            ctx.bot
+                .send_document(ctx.msg.chat.id, InputFile::memory(json).file_name("gbans.json"))
+                .caption(tr!(i18n, locale, "gbans-exported", count: entries.len() as i64))
+                .reply_to(ctx.msg.id)
+                .await?;
+        }
+        "import" => {
+            ctx.repo.authorize(by, Permission::GlobalBan, None, None).await?;
+            let Some(document) = ctx.msg.reply_to_message().and_then(|r| r.document()) else {
+                ctx.reply(tr!(i18n, locale, "gbans-usage")).await?;
+                return Ok(());
+            };
+            if document.file.size > MAX_IMPORT_SIZE {
+                ctx.reply(tr!(i18n, locale, "gbans-invalid")).await?;
+                return Ok(());
+            }
+            let file = ctx.bot.get_file(document.file.id.clone()).await?;
+            let mut data = Vec::new();
+            if let Err(e) = ctx.bot.download_file(&file.path, &mut data).await {
+                tracing::warn!("Failed to download ban list {}: {}", file.id, e);
+                ctx.reply(tr!(i18n, locale, "gbans-invalid")).await?;
+                return Ok(());
+            }
+            let Ok(entries) = serde_json::from_slice::<Vec<Entry>>(&data) else {
+                ctx.reply(tr!(i18n, locale, "gbans-invalid")).await?;
+                return Ok(());
+            };
+            let total = entries.len() as i64;
+            let bans = entries.into_iter().map(|e| (e.user_id, e.reason)).collect();
+            let added = ctx.repo.import_global_bans(by, bans).await?;
+            ctx.reply(tr!(i18n, locale, "gbans-imported", count: added as i64, total = total))
+                .await?;
+        }
+        _ => {
+            ctx.reply(tr!(i18n, locale, "gbans-usage")).await?;
+        }
+    }
+    Ok(())
+}
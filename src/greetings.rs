@@ -0,0 +1,275 @@
+use crate::command::Context;
+use crate::config::Config;
+use crate::error::{Error, HandlerError};
+use crate::models::chat_settings::{
+    Change, Greeting, GreetingButton, GreetingMedia, GreetingsChange, MediaKind, Module,
+};
+use crate::models::jobs::{DeleteMessage, Kind};
+use crate::models::prelude::ChatSettingsModel;
+use crate::permission::Permission;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::template::{render, Placeholders};
+use crate::tr;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{
+    Chat, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, Me, ParseMode, User,
+};
+use url::Url;
+
+/// Sends the chat's welcome message to a new member.
+///
+/// Called on join, or once the captcha is solved when the chat has one.
+pub async fn welcome(
+    bot: &Bot,
+    repo: &Repo,
+    config: &Config,
+    chat: &Chat,
+    user: &User,
+) -> Result<(), Error> {
+    let settings = repo.get_chat_settings(chat.id.0).await?;
+    if !settings.module_enabled(Module::Welcome) {
+        return Ok(());
+    }
+    let greeting = settings.greetings().welcome;
+    let text = settings.welcome_text.as_deref();
+    greet(bot, repo, config, &settings, chat, user, text, &greeting, true).await
+}
+
+/// Sends the chat's goodbye message for a member who left.
+pub async fn goodbye(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let Some(user) = msg.left_chat_member().filter(|u| !u.is_bot && u.id != me.id) else {
+        return Ok(());
+    };
+    let result = async {
+        let settings = repo.get_chat_settings(msg.chat.id.0).await?;
+        if !settings.module_enabled(Module::Welcome) {
+            return Ok(());
+        }
+        let greetings = settings.greetings();
+        let text = greetings.goodbye_text.as_deref();
+        greet(&bot, &repo, &config, &settings, &msg.chat, user, text, &greetings.goodbye, true).await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!("Failed to say goodbye to {} in chat {}: {}", user.id, msg.chat.id, e);
+    }
+    Ok(())
+}
+
+/// Deletes Telegram's join and leave notices in chats that asked for it.
+pub async fn clean(bot: Bot, msg: Message, repo: Arc<Repo>, config: Arc<Config>) {
+    let result = async {
+        let settings = repo.get_chat_settings(msg.chat.id.0).await?;
+        let greetings = settings.greetings();
+        if greetings.clean_service.unwrap_or(config.greetings.clean_service) {
+            bot.delete_message(msg.chat.id, msg.id).await?;
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to delete service message in chat {}: {}", msg.chat.id, e);
+    }
+}
+
+/// Sends a greeting with its media and buttons, scheduling its deletion on the job runner if
+/// `expire` is set and the chat deletes greetings.
+#[allow(clippy::too_many_arguments)]
+async fn greet(
+    bot: &Bot,
+    repo: &Repo,
+    config: &Config,
+    settings: &ChatSettingsModel,
+    chat: &Chat,
+    user: &User,
+    text: Option<&str>,
+    greeting: &Greeting,
+    expire: bool,
+) -> Result<(), Error> {
+    if text.is_none() && greeting.media.is_none() {
+        return Ok(());
+    }
+    let count = bot.get_chat_member_count(chat.id).await.ok();
+    let values = Placeholders { user, chat, count };
+    let text = text.map(|t| render(t, &values)).unwrap_or_default();
+    let keyboard = InlineKeyboardMarkup::new(greeting.buttons.iter().filter_map(|b| {
+        Some([InlineKeyboardButton::url(b.text.clone(), Url::parse(&b.url).ok()?)])
+    }));
+    let sent = match &greeting.media {
+        None => {
+            bot.send_message(chat.id, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(keyboard)
+                .await?
+        }
+        Some(media) => {
+            let file = InputFile::file_id(media.file_id.clone());
+            match media.kind {
+                MediaKind::Photo => {
+                    bot.send_photo(chat.id, file)
+                        .caption(text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?
+                }
+                MediaKind::Video => {
+                    bot.send_video(chat.id, file)
+                        .caption(text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?
+                }
+                MediaKind::Animation => {
+                    bot.send_animation(chat.id, file)
+                        .caption(text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?
+                }
+                MediaKind::Document => {
+                    bot.send_document(chat.id, file)
+                        .caption(text)
+                        .parse_mode(ParseMode::Html)
+                        .reply_markup(keyboard)
+                        .await?
+                }
+            }
+        }
+    };
+    let delete_after = settings
+        .greetings()
+        .delete_after
+        .unwrap_or(config.greetings.delete_after);
+    if let Some(me) = sent.from.as_ref().filter(|_| expire && delete_after > 0) {
+        let payload = DeleteMessage {
+            message: sent.id.0,
+        };
+        let at = Utc::now() + Duration::from_secs(delete_after);
+        repo.schedule_job(
+            Kind::DeleteMessage,
+            chat.id.0,
+            me.id.0 as i64,
+            serde_json::to_value(payload).expect("payload serializes"),
+            at.fixed_offset(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Media of a photo, video, animation or document message.
+fn media(msg: &Message) -> Option<GreetingMedia> {
+    let (kind, file_id) = if let Some(photo) = msg.photo().and_then(|sizes| sizes.last()) {
+        (MediaKind::Photo, &photo.file.id)
+    } else if let Some(video) = msg.video() {
+        (MediaKind::Video, &video.file.id)
+    } else if let Some(animation) = msg.animation() {
+        (MediaKind::Animation, &animation.file.id)
+    } else if let Some(document) = msg.document() {
+        (MediaKind::Document, &document.file.id)
+    } else {
+        return None;
+    };
+    Some(GreetingMedia {
+        kind,
+        file_id: file_id.clone(),
+    })
+}
+
+/// A `[label](url)` line.
+fn button(line: &str) -> Option<GreetingButton> {
+    let (text, url) = line
+        .trim()
+        .strip_prefix('[')?
+        .strip_suffix(')')?
+        .split_once("](")?;
+    let url = Url::parse(url.trim()).ok()?;
+    let text = text.trim();
+    (!text.is_empty() && matches!(url.scheme(), "http" | "https" | "tg")).then(|| GreetingButton {
+        text: text.to_owned(),
+        url: url.to_string(),
+    })
+}
+
+/// Splits the `[label](url)` lines off the end of `text`.
+fn split_buttons(text: &str) -> (String, Vec<GreetingButton>) {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let mut buttons = Vec::new();
+    while let Some(button) = lines.last().and_then(|line| button(line)) {
+        buttons.insert(0, button);
+        lines.pop();
+    }
+    (lines.join("\n").trim().to_owned(), buttons)
+}
+
+/// `/welcome <text>` and `/goodbye <text>` set the chat's greeting, `[label](url)` lines at the
+/// end becoming buttons. In reply to a photo, video, animation or document the file is sent
+/// with it, its caption serving as the text if none is given. Without arguments the greeting is
+/// previewed, `off` removes it.
+pub async fn command(ctx: &Context<'_>, args: String, goodbye: bool) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let chat = ctx.msg.chat.id.0;
+    let args = args.trim();
+    let reply = ctx.msg.reply_to_message();
+    let change = |text: Option<String>, greeting: Greeting| match goodbye {
+        false => (Change::WelcomeText(text), Change::Greetings(GreetingsChange::Welcome(greeting))),
+        true => (
+            Change::Greetings(GreetingsChange::GoodbyeText(text)),
+            Change::Greetings(GreetingsChange::Goodbye(greeting)),
+        ),
+    };
+    if args.is_empty() && reply.is_none() {
+        ctx.repo.authorize(by, Permission::EditSettings, None, Some(ctx.msg.chat.id.0)).await?;
+        let greetings = ctx.settings.greetings();
+        let (text, greeting) = match goodbye {
+            false => (ctx.settings.welcome_text.as_deref(), &greetings.welcome),
+            true => (greetings.goodbye_text.as_deref(), &greetings.goodbye),
+        };
+        let Some(user) = ctx.msg.from.as_ref() else {
+            return Ok(());
+        };
+        if text.is_none() && greeting.media.is_none() {
+            ctx.reply(tr!(i18n, locale, "greetings-not-set")).await?;
+            return Ok(());
+        }
+        let settings = &ctx.settings;
+        greet(ctx.bot, ctx.repo, ctx.config, settings, &ctx.msg.chat, user, text, greeting, false).await?;
+        return Ok(());
+    }
+    let key = if args == "off" {
+        let (text, extras) = change(None, Greeting::default());
+        ctx.repo.update_chat_settings(by, chat, text).await?;
+        ctx.repo.update_chat_settings(by, chat, extras).await?;
+        "greetings-removed"
+    } else {
+        let source = match args {
+            "" => reply.and_then(|r| r.text().or_else(|| r.caption())).unwrap_or_default(),
+            args => args,
+        };
+        let (text, buttons) = split_buttons(source);
+        let media = reply.and_then(media);
+        if text.is_empty() && media.is_none() {
+            ctx.reply(tr!(i18n, locale, "greetings-usage")).await?;
+            return Ok(());
+        }
+        let text = Some(text).filter(|t| !t.is_empty());
+        let (text, extras) = change(text, Greeting { media, buttons });
+        ctx.repo.update_chat_settings(by, chat, text).await?;
+        ctx.repo.update_chat_settings(by, chat, extras).await?;
+        "greetings-saved"
+    };
+    ctx.reply(tr!(i18n, locale, key)).await?;
+    Ok(())
+}
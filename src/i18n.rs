@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::Ru, Locale::En];
+
+    /// Parses an IETF language tag such as Telegram's `language_code` (`ru`, `en-US`).
+    pub fn from_code(code: &str) -> Option<Self> {
+        let lang = code.split(['-', '_']).next()?.to_ascii_lowercase();
+        match lang.as_str() {
+            "ru" => Some(Locale::Ru),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::Ru => "ru",
+            Locale::En => "en",
+        }
+    }
+
+    fn builtin(self) -> &'static str {
+        match self {
+            Locale::Ru => include_str!("../locales/ru.lang"),
+            Locale::En => include_str!("../locales/en.lang"),
+        }
+    }
+
+    /// CLDR plural category of `n` for cardinal numbers.
+    fn plural(self, n: i64) -> Plural {
+        let n = n.unsigned_abs();
+        match self {
+            Locale::Ru => match (n % 10, n % 100) {
+                (1, r) if r != 11 => Plural::One,
+                (2..=4, r) if !(12..=14).contains(&r) => Plural::Few,
+                _ => Plural::Many,
+            },
+            Locale::En => match n {
+                1 => Plural::One,
+                _ => Plural::Other,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Locale::from_code(s).ok_or_else(|| format!("unsupported locale '{s}'"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Plural {
+    One,
+    Few,
+    Many,
+    Other,
+}
+
+impl Plural {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "one" => Some(Plural::One),
+            "few" => Some(Plural::Few),
+            "many" => Some(Plural::Many),
+            "other" => Some(Plural::Other),
+            _ => None,
+        }
+    }
+}
+
+enum Entry {
+    Plain(String),
+    Plural(HashMap<Plural, String>),
+}
+
+#[derive(Default)]
+struct Catalog {
+    entries: HashMap<String, Entry>,
+}
+
+impl Catalog {
+    fn parse(source: &str, origin: &Path) -> Result<Self, I18nError> {
+        let mut catalog = Catalog::default();
+        catalog.extend(source, origin)?;
+        Ok(catalog)
+    }
+
+    fn extend(&mut self, source: &str, origin: &Path) -> Result<(), I18nError> {
+        for (n, line) in source.lines().enumerate() {
+            let error = |message: &str| I18nError::Parse {
+                file: origin.to_owned(),
+                line: n + 1,
+                message: message.to_owned(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, text) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `key = text`"))?;
+            let (key, text) = (key.trim(), text.trim().replace("\\n", "\n"));
+            match key.split_once('[') {
+                Some((key, category)) => {
+                    let category = category
+                        .strip_suffix(']')
+                        .and_then(Plural::from_name)
+                        .ok_or_else(|| error("unknown plural category"))?;
+                    let entry = self
+                        .entries
+                        .entry(key.trim().to_owned())
+                        .or_insert_with(|| Entry::Plural(HashMap::new()));
+                    match entry {
+                        Entry::Plural(forms) => {
+                            forms.insert(category, text);
+                        }
+                        Entry::Plain(_) => *entry = Entry::Plural(HashMap::from([(category, text)])),
+                    }
+                }
+                None => {
+                    self.entries.insert(key.to_owned(), Entry::Plain(text));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Message catalogs for every supported [`Locale`].
+pub struct I18n {
+    catalogs: HashMap<Locale, Catalog>,
+    default: Locale,
+}
+
+impl I18n {
+    /// Loads the built-in catalogs, then overrides them with `<dir>/<code>.lang` files if present.
+    pub fn load(dir: Option<&Path>, default: Locale) -> Result<Self, I18nError> {
+        let mut catalogs = HashMap::new();
+        for locale in Locale::ALL {
+            let origin = PathBuf::from(format!("locales/{}.lang", locale.code()));
+            let mut catalog = Catalog::parse(locale.builtin(), &origin)?;
+            if let Some(dir) = dir {
+                let path = dir.join(format!("{}.lang", locale.code()));
+                match std::fs::read_to_string(&path) {
+                    Ok(source) => catalog.extend(&source, &path)?,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(I18nError::Io(path, e)),
+                }
+            }
+            catalogs.insert(locale, catalog);
+        }
+        Ok(Self { catalogs, default })
+    }
+
+    /// Picks the chat's configured locale, falling back to the user's Telegram language.
+    pub fn locale(&self, chat: Option<&str>, user: Option<&str>) -> Locale {
+        chat.and_then(Locale::from_code)
+            .or_else(|| user.and_then(Locale::from_code))
+            .unwrap_or(self.default)
+    }
+
+    /// Renders `key`, choosing the plural form by `count` when the message has one.
+    ///
+    /// Falls back to the default locale and then to the key itself.
+    pub fn get(
+        &self,
+        locale: Locale,
+        key: &str,
+        count: Option<i64>,
+        args: &[(&str, &dyn fmt::Display)],
+    ) -> String {
+        let found = [locale, self.default].into_iter().find_map(|locale| {
+            let entry = self.catalogs.get(&locale)?.entries.get(key)?;
+            match entry {
+                Entry::Plain(text) => Some(text),
+                Entry::Plural(forms) => {
+                    let category = locale.plural(count.unwrap_or_default());
+                    forms.get(&category).or_else(|| forms.get(&Plural::Other))
+                }
+            }
+        });
+        let Some(text) = found else {
+            tracing::warn!("Missing message '{}' for locale {}", key, locale);
+            return key.to_owned();
+        };
+        substitute(text, args)
+    }
+}
+
+/// Replaces every `{name}` in `text` with its value from `args` in one pass, so braces inside
+/// the values are left alone. Unknown placeholders are kept as written.
+fn substitute(text: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = tail.find('}').and_then(|end| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &tail[1..end])?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(&value.to_string());
+                rest = &tail[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug)]
+pub enum I18nError {
+    Io(PathBuf, std::io::Error),
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for I18nError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            I18nError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            I18nError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for I18nError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            I18nError::Io(_, err) => Some(err),
+            I18nError::Parse { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn i18n() -> I18n {
+        I18n::load(None, Locale::Ru).unwrap()
+    }
+
+    #[test]
+    fn substitutes_values_once() {
+        let name = "{limit}";
+        let text = substitute("{user} ({warns}/{limit}) {other}", &[
+            ("user", &name),
+            ("warns", &1),
+            ("limit", &3),
+        ]);
+        assert_eq!(text, "{limit} (1/3) {other}");
+    }
+
+    #[test]
+    fn picks_plural_forms() {
+        let i18n = i18n();
+        let warns = |locale, n: i64| i18n.get(locale, "warns", Some(n), &[("count", &n)]);
+        assert_eq!(warns(Locale::Ru, 1), "1 предупреждение");
+        assert_eq!(warns(Locale::Ru, 3), "3 предупреждения");
+        assert_eq!(warns(Locale::Ru, 11), "11 предупреждений");
+        assert_eq!(warns(Locale::Ru, 21), "21 предупреждение");
+        assert_eq!(warns(Locale::En, 1), "1 warning");
+        assert_eq!(warns(Locale::En, 3), "3 warnings");
+    }
+
+    #[test]
+    fn falls_back_to_the_key() {
+        assert_eq!(i18n().get(Locale::En, "no-such-key", None, &[]), "no-such-key");
+    }
+}
@@ -0,0 +1,222 @@
+use crate::captcha;
+use crate::config::Config;
+use crate::error::Error;
+use crate::i18n::I18n;
+use crate::models::jobs::Kind;
+use crate::models::prelude::JobModel;
+use crate::nickname::mention;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, ParseMode, ReplyParameters};
+use teloxide::utils::html;
+
+/// Longest the runner sleeps, so that jobs added meanwhile are picked up in time.
+const POLL_INTERVAL: Duration = Duration::from_secs(20);
+/// Shortest pause between two rounds, should a due job fail to move on.
+const MIN_WAIT: Duration = Duration::from_secs(1);
+
+/// Parses a five-field cron expression.
+///
+/// Weekdays are numbered the standard way, 0 or 7 being Sunday, and translated to the numbering
+/// of the `cron` crate, which counts from Sunday being 1.
+pub fn parse_cron(expression: &str) -> Option<cron::Schedule> {
+    let fields: Vec<_> = expression.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return None;
+    };
+    let weekday = weekdays(weekday)?;
+    cron::Schedule::from_str(&format!("0 {minute} {hour} {day} {month} {weekday}")).ok()
+}
+
+/// Translates a standard weekday field into the numbering of the `cron` crate, spelling out
+/// the days of numeric ranges and steps; names are left as they are.
+fn weekdays(field: &str) -> Option<String> {
+    let mut translated = Vec::new();
+    for part in field.split(',') {
+        if part == "*" || part == "?" || part.contains(|c: char| c.is_ascii_alphabetic()) {
+            translated.push(part.to_owned());
+            continue;
+        }
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<usize>().ok().filter(|&s| s > 0)?)),
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+            None => {
+                let first = range.parse().ok()?;
+                (first, if step.is_some() { 6 } else { first })
+            }
+        };
+        if first > last || last > 7 {
+            return None;
+        }
+        let mut days: Vec<u32> = (first..=last)
+            .step_by(step.unwrap_or(1))
+            .map(|day| day % 7 + 1)
+            .collect();
+        days.sort_unstable();
+        days.dedup();
+        translated.extend(days.iter().map(u32::to_string));
+    }
+    Some(translated.join(","))
+}
+
+/// Next run of a five-field cron `expression`, read in `tz`, after `after`.
+pub fn next_run(expression: &str, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_cron(expression)?
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Runs jobs as they fall due, for as long as the bot runs.
+///
+/// Jobs live in the repository, so those due while the bot was down run as soon as it is back;
+/// recurring ones then run once and carry on from their next time.
+pub async fn run(bot: Bot, i18n: Arc<I18n>, repo: Arc<Repo>, config: Arc<Config>) {
+    loop {
+        if let Err(e) = run_due(&bot, &i18n, &repo, &config).await {
+            tracing::error!("Failed to run due jobs: {}", e);
+        }
+        let wait = match repo.get_next_job_at().await {
+            Ok(Some(at)) => (at.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default()
+                .clamp(MIN_WAIT, POLL_INTERVAL),
+            Ok(None) => POLL_INTERVAL,
+            Err(e) => {
+                tracing::error!("Failed to get the next job: {}", e);
+                POLL_INTERVAL
+            }
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+async fn run_due(bot: &Bot, i18n: &I18n, repo: &Repo, config: &Config) -> Result<(), Error> {
+    let now = Utc::now();
+    for job in repo.get_due_jobs(now.fixed_offset()).await? {
+        // Moved on before running, so that a failing job is not retried over and over.
+        let next = job
+            .cron
+            .as_deref()
+            .and_then(|cron| next_run(cron, config.scheduler.timezone, now));
+        match next {
+            Some(next) => repo.reschedule_job(job.id, next.fixed_offset()).await?,
+            // Claimed by whoever deletes it first, e.g. a member answering their captcha.
+            None if !repo.finish_job(job.id).await? => continue,
+            None => {}
+        }
+        if let Err(e) = execute(bot, i18n, repo, &job).await {
+            tracing::warn!("Job {} in chat {} failed: {}", job.id, job.chat_id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn execute(bot: &Bot, i18n: &I18n, repo: &Repo, job: &JobModel) -> Result<(), Error> {
+    let chat = ChatId(job.chat_id);
+    match job.kind {
+        Kind::Post => {
+            let post = job.post();
+            let sent = bot.send_message(chat, post.text).await?;
+            if post.pin {
+                bot.pin_chat_message(chat, sent.id)
+                    .disable_notification(true)
+                    .await?;
+            }
+        }
+        Kind::Reminder => {
+            let reminder = job.reminder();
+            let locale = i18n.locale(Some(&reminder.locale), None);
+            let user = mention(&repo.get_user(job.created_by).await?);
+            let text = match reminder.text.as_str() {
+                "" => tr!(i18n, locale, "reminder-this", user = user),
+                text => tr!(i18n, locale, "reminder", user = user, text = html::escape(text)),
+            };
+            let private = ChatId(job.created_by);
+            if reminder.dm && chat != private {
+                // Users who never started the bot cannot be written to, so they get it in the chat.
+                match bot.send_message(private, text.clone()).parse_mode(ParseMode::Html).await {
+                    Ok(_) => return Ok(()),
+                    Err(e) => tracing::warn!("Failed to remind {} privately: {}", job.created_by, e),
+                }
+            }
+            let mut request = bot.send_message(chat, text).parse_mode(ParseMode::Html);
+            if let Some(message) = reminder.reply_to {
+                let reply = ReplyParameters::new(MessageId(message)).allow_sending_without_reply();
+                request = request.reply_parameters(reply);
+            }
+            request.await?;
+        }
+        Kind::Captcha => {
+            let captcha = job.captcha();
+            let user = UserId(job.created_by as u64);
+            captcha::kick(bot, repo, chat, user, MessageId(captcha.message)).await?;
+        }
+        Kind::DeleteMessage => {
+            bot.delete_message(chat, MessageId(job.delete_message().message))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        next_run(expression, Tz::UTC, after)
+    }
+
+    #[test]
+    fn weekday_ranges_count_from_monday_being_one() {
+        // 2026-10-17 is a Saturday.
+        let saturday = at(2026, 10, 17, 12, 0);
+        assert_eq!(next("0 9 * * 1-5", saturday), Some(at(2026, 10, 19, 9, 0)));
+        assert_eq!(next("0 9 * * 1-5", at(2026, 10, 19, 9, 0)), Some(at(2026, 10, 20, 9, 0)));
+        assert_eq!(next("0 9 * * 1-5", at(2026, 10, 23, 10, 0)), Some(at(2026, 10, 26, 9, 0)));
+    }
+
+    #[test]
+    fn zero_and_seven_are_sunday() {
+        let monday = at(2026, 10, 19, 12, 0);
+        assert_eq!(next("0 9 * * 0", monday), Some(at(2026, 10, 25, 9, 0)));
+        assert_eq!(next("0 9 * * 7", monday), Some(at(2026, 10, 25, 9, 0)));
+        assert_eq!(next("0 9 * * 5-7", monday), Some(at(2026, 10, 23, 9, 0)));
+        assert_eq!(next("0 9 * * 6,0", at(2026, 10, 24, 10, 0)), Some(at(2026, 10, 25, 9, 0)));
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(next("*/15 * * * *", at(2026, 10, 19, 10, 7)), Some(at(2026, 10, 19, 10, 15)));
+        assert_eq!(next("*/15 * * * *", at(2026, 10, 19, 10, 45)), Some(at(2026, 10, 19, 11, 0)));
+        // Sunday, Tuesday, Thursday and Saturday.
+        assert_eq!(weekdays("*/2").as_deref(), Some("1,3,5,7"));
+        assert_eq!(weekdays("1-5/2").as_deref(), Some("2,4,6"));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(parse_cron("0 9 * *").is_none());
+        assert!(parse_cron("0 0 9 * * 1").is_none());
+        assert!(parse_cron("0 9 * * 8").is_none());
+        assert!(parse_cron("0 9 * * 5-1").is_none());
+        assert!(parse_cron("0 9 * * */0").is_none());
+        assert!(parse_cron("0 9 * * MON-FRI").is_some());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::i18n::I18n;
+use crate::models::chat_settings::Module;
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::register::sender_id;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{Me, MessageEntityKind, ParseMode, Recipient};
+use url::Url;
+
+/// Hosts of `t.me`-style links.
+const TELEGRAM_HOSTS: &[&str] = &["t.me", "telegram.me", "telegram.dog"];
+
+/// Why a link was refused; each has a message `links-<reason>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Refusal {
+    Blocked,
+    Invite,
+    Newcomer,
+}
+
+impl Refusal {
+    fn message_key(self) -> &'static str {
+        match self {
+            Refusal::Blocked => "links-blocked",
+            Refusal::Invite => "links-invite",
+            Refusal::Newcomer => "links-newcomer",
+        }
+    }
+}
+
+/// Links of `Url` and `TextLink` entities of the text or the caption.
+fn links(msg: &Message) -> Vec<Url> {
+    let entities = msg
+        .parse_entities()
+        .or_else(|| msg.parse_caption_entities())
+        .unwrap_or_default();
+    entities
+        .iter()
+        .filter_map(|entity| match entity.kind() {
+            MessageEntityKind::Url => Url::parse(entity.text())
+                .or_else(|_| Url::parse(&format!("http://{}", entity.text())))
+                .ok(),
+            MessageEntityKind::TextLink { url } => Some(url.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn host(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    host.strip_prefix("www.").map(str::to_owned).unwrap_or(host)
+}
+
+/// Whether `host` is one of `domains` or a subdomain of one.
+fn listed<'a>(host: &str, mut domains: impl Iterator<Item = &'a String>) -> bool {
+    domains.any(|domain| host == domain || host.ends_with(&format!(".{domain}")))
+}
+
+/// Whether `url` invites to a Telegram chat other than `chat`.
+///
+/// Private invites always count; public `t.me/<name>` links count when they lead to a group or
+/// a channel.
+async fn is_foreign_invite(bot: &Bot, chat: ChatId, url: &Url) -> bool {
+    if url.scheme() == "tg" {
+        return url.host_str() == Some("join");
+    }
+    if !TELEGRAM_HOSTS.contains(&host(url).as_str()) {
+        return false;
+    }
+    let path = url.path().trim_start_matches('/');
+    if path.starts_with("joinchat/") || path.starts_with('+') {
+        return true;
+    }
+    let name = path.split('/').next().unwrap_or_default();
+    if name.is_empty() || name == "c" || name == "s" {
+        return false;
+    }
+    match bot.get_chat(Recipient::ChannelUsername(format!("@{name}"))).await {
+        Ok(target) => !target.is_private() && target.id != chat,
+        Err(_) => false,
+    }
+}
+
+/// Deletes group messages with links the chat does not allow, on behalf of the bot.
+///
+/// Blocklisted domains are always refused and allowlisted ones always pass. Otherwise invites
+/// to other Telegram chats are refused if the chat blocks them, and any link is refused while
+/// its author is newer than the chat's waiting period. Members with the `bypass_links`
+/// permission, moderators by default, are exempt.
+pub async fn check(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+) -> bool {
+    if msg.chat.is_private() {
+        return true;
+    }
+    let Some(sender) = sender_id(&msg) else {
+        return true;
+    };
+    match apply(&bot, &msg, &me, &i18n, &repo, &config, sender).await {
+        Ok(deleted) => !deleted,
+        Err(e) => {
+            tracing::error!("Link filter failed for {} in chat {}: {}", sender, msg.chat.id, e);
+            true
+        }
+    }
+}
+
+async fn apply(
+    bot: &Bot,
+    msg: &Message,
+    me: &Me,
+    i18n: &I18n,
+    repo: &Repo,
+    config: &Config,
+    sender: i64,
+) -> Result<bool, Error> {
+    let urls = links(msg);
+    if urls.is_empty() {
+        return Ok(false);
+    }
+    let chat = msg.chat.id;
+    let settings = repo.get_chat_settings(chat.0).await?;
+    if !settings.module_enabled(Module::LinkFilter) {
+        return Ok(false);
+    }
+    if repo.get_permissions(sender, Some(chat.0)).await?.contains(&Permission::BypassLinks) {
+        return Ok(false);
+    }
+    let user = repo.get_user(sender).await?;
+    let rules = settings.links();
+    let block_invites = rules.block_invites.unwrap_or(config.links.block_invites);
+    let hours = rules.new_user_hours.unwrap_or(config.links.new_user_hours);
+    let newcomer = chrono::Utc::now() < user.created_at + chrono::Duration::hours(hours.into());
+    let mut refusal = None;
+    for url in &urls {
+        let host = host(url);
+        if listed(&host, config.links.blocklist.iter().chain(&rules.blocklist)) {
+            refusal = Some(Refusal::Blocked);
+        } else if listed(&host, config.links.allowlist.iter().chain(&rules.allowlist)) {
+            continue;
+        } else if block_invites && is_foreign_invite(bot, chat, url).await {
+            refusal = Some(Refusal::Invite);
+        } else if newcomer {
+            refusal = Some(Refusal::Newcomer);
+        }
+        if refusal.is_some() {
+            break;
+        }
+    }
+    let Some(refusal) = refusal else {
+        return Ok(false);
+    };
+
+    let text = msg.text().or_else(|| msg.caption()).map(str::to_owned);
+    repo.delete_messages(me.id.0 as i64, Some(sender), chat.0, vec![msg.id.0], text)
+        .await?;
+    bot.delete_message(chat, msg.id).await?;
+    let locale = i18n.locale(settings.locale.as_deref(), None);
+    let text = tr!(
+        i18n,
+        locale,
+        refusal.message_key(),
+        user = mention(&user),
+        hours = hours
+    );
+    if let Err(e) = bot.send_message(chat, text).parse_mode(ParseMode::Html).await {
+        tracing::warn!("Failed to explain a refused link in chat {}: {}", chat, e);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    fn domains(list: &[&str]) -> Vec<String> {
+        list.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn hosts_drop_www_and_case() {
+        assert_eq!(host(&url("https://WWW.Example.com/x")), "example.com");
+        assert_eq!(host(&url("http://t.me/joinchat/abc")), "t.me");
+    }
+
+    #[test]
+    fn subdomains_are_listed_with_their_domain() {
+        let list = domains(&["example.com"]);
+        assert!(listed("example.com", list.iter()));
+        assert!(listed("cdn.example.com", list.iter()));
+        assert!(!listed("badexample.com", list.iter()));
+        assert!(!listed("example.com.evil.org", list.iter()));
+    }
+
+    #[test]
+    fn links_come_from_url_and_text_link_entities() {
+        let msg: Message = serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup", "title": "chat"},
+            "from": {"id": 1, "is_bot": false, "first_name": "a"},
+            "text": "see example.com and here",
+            "entities": [
+                {"type": "url", "offset": 4, "length": 11},
+                {"type": "text_link", "offset": 20, "length": 4, "url": "https://t.me/+abc"}
+            ]
+        }))
+        .unwrap();
+        let hosts: Vec<String> = links(&msg).iter().map(host).collect();
+        assert_eq!(hosts, ["example.com", "t.me"]);
+    }
+}
@@ -43,3 +43,26 @@ macro_rules! action {
             .await?;
     };
 }
+
+#[macro_export]
+macro_rules! tr {
+    ($i18n:expr, $locale:expr, $key:expr, count: $count:expr $(, $name:ident = $val:expr)* $(,)?) => {{
+        let count: i64 = $count;
+        let text = $i18n.get(
+            $locale,
+            $key,
+            Some(count),
+            &[("count", &count as &dyn std::fmt::Display) $(, (stringify!($name), &$val as &dyn std::fmt::Display))*],
+        );
+        text
+    }};
+    ($i18n:expr, $locale:expr, $key:expr $(, $name:ident = $val:expr)* $(,)?) => {{
+        let text = $i18n.get(
+            $locale,
+            $key,
+            None,
+            &[$((stringify!($name), &$val as &dyn std::fmt::Display)),*],
+        );
+        text
+    }};
+}
@@ -1,26 +1,87 @@
-use repository::RepositoryTrait;
 use std::sync::Arc;
 use teloxide::dispatching::DefaultKey;
+use teloxide::error_handlers::LoggingErrorHandler;
 use teloxide::prelude::*;
-use crate::command::{handle_commands, Command};
-use crate::error::Error;
+use teloxide::update_listeners::webhooks;
+use crate::command::{handle_commands, parse_command};
+use crate::config::{Config, SyncMode, UpdateMode};
+use crate::error::reply::ReplyErrorHandler;
+use crate::error::HandlerError;
+use crate::register::{register_bot, register_sender, MessageCounter};
+use crate::repository::RepositoryTrait;
 
-mod from_env;
+mod anonymous;
+mod captcha;
+mod config;
+mod i18n;
+mod jobs;
+mod links;
 mod macros;
 mod models;
+mod moderation;
+mod nickname;
+mod night;
+mod ownership;
+mod profile;
+mod quota;
+mod reminders;
 mod repository;
+mod register;
+mod report;
 mod role;
+mod schedule;
 mod command;
+mod cooldown;
 mod filter;
+mod flood;
+mod gban;
+mod greetings;
 mod error;
+mod settings;
+mod sync;
+mod target;
+mod template;
+mod when;
+mod permission;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let args: Vec<String> = std::env::args().collect();
+    let (path, required) = Config::path(&args);
+    let config = match Config::load(&path, required) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let i18n = match config.i18n() {
+        Ok(i18n) => Arc::new(i18n),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    if args.iter().any(|a| a == "--check-config") {
+        println!("Configuration is valid");
+        return;
+    }
+
+    tracing_subscriber::fmt().with_max_level(config.log.level).init();
 
+    let repo = Arc::new(config.repository().await.expect("cannot connect to database"));
+    let roles = Arc::new(config.role_selector());
+    match repo.reconcile_creators(config.creators.clone()).await {
+        Ok(changed) => {
+            for (user, role) in changed {
+                tracing::info!("Creator list changed, user {} is now {}", user, role);
+            }
+        }
+        Err(e) => tracing::error!("Failed to reconcile creators: {}", e),
+    }
     let bot = Bot::from_env();
 
-    let handler = Update::filter_message()
+    let messages = Update::filter_message()
         .inspect(|m: Message| {
             let text = m.text().unwrap_or("null").to_string();
             let sender = if let Some(sender) = m.sender_chat {
@@ -30,46 +91,93 @@ async fn main() {
                 "".to_owned()
             };
             tracing::debug!("Got message '{}' from chat {}, {}", text, m.chat.id, sender);
-        }).branch(
-        dptree::entry()
-            .filter_command::<Command>()
+        })
+        .inspect_async(register_sender)
+        // Each check returns whether the message goes on to the handlers below, which it does
+        // not once it is deleted or its author punished for it; a failing check lets it through.
+        .filter_async(flood::check)
+        .filter_async(filter::Handler::filter)
+        .filter_async(links::check)
+        .filter_async(quota::check)
+        .branch(
+            dptree::filter(|m: Message| m.new_chat_members().is_some())
+                .inspect_async(greetings::clean)
+                .endpoint(captcha::join),
+        )
+        .branch(
+            dptree::filter(|m: Message| m.left_chat_member().is_some())
+                .inspect_async(greetings::clean)
+                .endpoint(greetings::goodbye),
+        )
+        .branch(
+        dptree::filter_map_async(parse_command)
             .endpoint(handle_commands)
     );
-        /* .branch(dptree::entry()
-            .endpoint(filter)
-        );*/
+    let callbacks = Update::filter_callback_query()
+        .branch(dptree::filter(settings::is_callback).endpoint(settings::callback))
+        .branch(dptree::filter(ownership::is_callback).endpoint(ownership::callback))
+        .branch(dptree::filter(anonymous::is_callback).endpoint(anonymous::callback))
+        .branch(dptree::filter(report::is_callback).endpoint(report::callback))
+        .branch(dptree::filter(captcha::is_callback).endpoint(captcha::callback));
+    let members = Update::filter_chat_member().endpoint(sync::chat_member);
+    let my_member = Update::filter_my_chat_member().endpoint(gban::my_chat_member);
+    let handler = dptree::entry()
+        .branch(messages)
+        .branch(callbacks)
+        .branch(members)
+        .branch(my_member);
 
+    let me = bot.get_me().await.expect("cannot get me");
+    if let Err(e) = register_bot(&repo, &me.user).await {
+        tracing::error!("Failed to register the bot itself: {}", e);
+    }
+    tracing::info!("Starting bot {}...", me.username().to_string());
 
-        /*.inspect_async(|m: Message| async {
-            if let Some(sender) = m.sender_chat {
-                let nickname = if let Some(first_name) = sender.first_name() {
-                    first_name.to_owned()
-                } else if let Some(title) = sender.title() {
-                    title.to_owned()
-                } else {
-                    "_".to_owned()
-                };
-                if let Err(e) = repo
-                    .new_user(
-                        sender.id.0,
-                        role_selector.select(sender.id.0),
-                        sender.username().map(|u: &str| u.to_owned()),
-                        nickname,
-                    )
-                    .await
-                {
-                    tracing::error!("Failed to create/update user: {:?}", e);
-                }
-            }
-        });*/
-    {
-        let me = bot.get_me().await.expect("cannot get me");
-        tracing::info!("Starting bot {}...", me.username().to_string());
+    if config.cache.stats_interval > 0 {
+        let every = std::time::Duration::from_secs(config.cache.stats_interval);
+        tokio::spawn(repository::cache::log_stats(repo.clone(), every));
+    }
+    if config.sync.mode != SyncMode::Off {
+        tokio::spawn(sync::run(bot.clone(), repo.clone(), roles.clone(), config.clone(), me.id));
     }
+    tokio::spawn(jobs::run(bot.clone(), i18n.clone(), repo.clone(), config.clone()));
+    tokio::spawn(night::run(bot.clone(), i18n.clone(), repo.clone(), config.clone(), me.id));
+    let counter = Arc::new(MessageCounter::default());
+    tokio::spawn(register::flush_counts(repo.clone(), config.clone(), counter.clone()));
+    let quotas = Arc::new(quota::Quotas::load(&repo).await);
+    tokio::spawn(quota::persist(repo.clone(), config.clone(), quotas.clone()));
 
-    Dispatcher::<Bot, Error, DefaultKey>::builder(bot, handler)
+    let mut dispatcher = Dispatcher::<Bot, HandlerError, DefaultKey>::builder(bot.clone(), handler)
+        .dependencies(dptree::deps![
+            config.clone(),
+            i18n.clone(),
+            repo.clone(),
+            roles,
+            Arc::new(anonymous::Pending::default()),
+            Arc::new(flood::Flood::default()),
+            Arc::new(filter::Handler::default()),
+            quotas.clone(),
+            counter.clone()
+        ])
+        .error_handler(ReplyErrorHandler::new(bot.clone(), i18n))
         .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    match config.updates.mode {
+        UpdateMode::Polling => dispatcher.dispatch().await,
+        UpdateMode::Webhook => {
+            let url = config.updates.webhook_url.clone().expect("validated by config");
+            let listener = webhooks::axum(bot, webhooks::Options::new(config.updates.listen, url))
+                .await
+                .expect("cannot set up webhook");
+            dispatcher
+                .dispatch_with_listener(
+                    listener,
+                    LoggingErrorHandler::with_custom_text("Error from the webhook listener"),
+                )
+                .await
+        }
+    }
+    counter.flush(&repo).await;
+    quotas.flush(&repo).await;
 }
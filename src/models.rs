@@ -1,4 +1,12 @@
 pub mod actions;
+pub mod bot_state;
+pub mod chat_settings;
 pub mod commands;
+pub mod filters;
+pub mod global_bans;
+pub mod jobs;
+pub mod permission_overrides;
 pub mod prelude;
+pub mod reports;
+pub mod roles;
 pub mod users;
@@ -28,10 +28,33 @@ pub enum Type {
     DemoteUser,
     WarnUser,
     UnWarnUser,
+    MuteUser,
+    DeleteMessages,
+    TransferOwnership,
+    ChangeNickname,
 
     CreateCommand,
     DeleteCommand,
     EditCommand,
+
+    ChangeSettings,
+    AddFilter,
+    DeleteFilter,
+    FilterHit,
+    CaptchaPassed,
+    CaptchaFailed,
+    GlobalBan,
+    GlobalUnban,
+    ImportGlobalBans,
+    CreateJob,
+    DeleteJob,
+    NightModeStart,
+    NightModeEnd,
+
+    CreateRole,
+    DeleteRole,
+    AssignRole,
+    OverridePermission,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,575 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, Default)]
+#[sea_orm(table_name = "chat_settings")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub chat_id: i64,
+    pub warn_limit: Option<i64>,
+    pub command_prefix: Option<String>,
+    pub locale: Option<String>,
+    pub welcome_text: Option<String>,
+    /// Map of [`Module`] name to whether it is enabled.
+    pub modules: Json,
+    /// Serialized [`FloodSettings`].
+    pub flood: Json,
+    /// Serialized [`LinkSettings`].
+    pub links: Json,
+    /// Serialized [`CaptchaSettings`].
+    pub captcha: Json,
+    /// Serialized [`GreetingSettings`].
+    pub greetings: Json,
+    /// Serialized [`NightSettings`].
+    pub night: Json,
+    /// Serialized [`QuotaSettings`].
+    pub quotas: Json,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+impl Model {
+    /// Settings of a chat that has never changed anything.
+    pub fn new(chat_id: i64) -> Self {
+        Self {
+            chat_id,
+            modules: Json::Object(Default::default()),
+            flood: Json::Object(Default::default()),
+            links: Json::Object(Default::default()),
+            captcha: Json::Object(Default::default()),
+            greetings: Json::Object(Default::default()),
+            night: Json::Object(Default::default()),
+            quotas: Json::Object(Default::default()),
+            ..Default::default()
+        }
+    }
+
+    pub fn module_enabled(&self, module: Module) -> bool {
+        self.modules
+            .get(module.name())
+            .and_then(Json::as_bool)
+            .unwrap_or(module.enabled_by_default())
+    }
+
+    pub fn flood(&self) -> FloodSettings {
+        serde_json::from_value(self.flood.clone()).unwrap_or_default()
+    }
+
+    pub fn links(&self) -> LinkSettings {
+        serde_json::from_value(self.links.clone()).unwrap_or_default()
+    }
+
+    pub fn captcha(&self) -> CaptchaSettings {
+        serde_json::from_value(self.captcha.clone()).unwrap_or_default()
+    }
+
+    pub fn greetings(&self) -> GreetingSettings {
+        serde_json::from_value(self.greetings.clone()).unwrap_or_default()
+    }
+
+    pub fn night(&self) -> NightSettings {
+        serde_json::from_value(self.night.clone()).unwrap_or_default()
+    }
+
+    pub fn quotas(&self) -> QuotaSettings {
+        serde_json::from_value(self.quotas.clone()).unwrap_or_default()
+    }
+}
+
+/// Optional bot features that can be switched on and off per chat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Module {
+    CustomCommands,
+    Welcome,
+    AntiFlood,
+    LinkFilter,
+    Captcha,
+    GlobalBans,
+    NightMode,
+    Quotas,
+}
+
+impl Module {
+    pub const ALL: [Module; 8] = [
+        Module::CustomCommands,
+        Module::Welcome,
+        Module::AntiFlood,
+        Module::LinkFilter,
+        Module::Captcha,
+        Module::GlobalBans,
+        Module::NightMode,
+        Module::Quotas,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Module::CustomCommands => "custom_commands",
+            Module::Welcome => "welcome",
+            Module::AntiFlood => "anti_flood",
+            Module::LinkFilter => "link_filter",
+            Module::Captcha => "captcha",
+            Module::GlobalBans => "global_bans",
+            Module::NightMode => "night_mode",
+            Module::Quotas => "quotas",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    pub fn enabled_by_default(self) -> bool {
+        match self {
+            Module::CustomCommands => true,
+            Module::Welcome => false,
+            Module::AntiFlood => true,
+            Module::LinkFilter => false,
+            Module::Captcha => false,
+            Module::GlobalBans => true,
+            Module::NightMode => false,
+            Module::Quotas => false,
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What the anti-flood does to whoever crosses a threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloodAction {
+    Mute,
+    Warn,
+}
+
+impl FloodAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mute" => Some(FloodAction::Mute),
+            "warn" => Some(FloodAction::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// Anti-flood thresholds of a chat; unset ones fall back to the configuration.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FloodSettings {
+    /// Messages allowed within the window.
+    pub messages: Option<u32>,
+    /// Length of the window in seconds.
+    pub window: Option<u64>,
+    /// Identical messages allowed within the window.
+    pub repeats: Option<u32>,
+    /// Stickers allowed within the window.
+    pub stickers: Option<u32>,
+    pub action: Option<FloodAction>,
+}
+
+/// A change to one of the [`FloodSettings`], `None` restoring the configured value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FloodChange {
+    Messages(Option<u32>),
+    Window(Option<u64>),
+    Repeats(Option<u32>),
+    Stickers(Option<u32>),
+    Action(Option<FloodAction>),
+}
+
+/// Link filter rules of a chat, applied on top of the configured ones.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkSettings {
+    /// Domains, with their subdomains, links to which are always allowed.
+    pub allowlist: Vec<String>,
+    /// Domains, with their subdomains, links to which are always deleted.
+    pub blocklist: Vec<String>,
+    /// Whether invites to other Telegram chats are deleted.
+    pub block_invites: Option<bool>,
+    /// Hours a new user has to wait before posting links.
+    pub new_user_hours: Option<u32>,
+}
+
+/// A change to the [`LinkSettings`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinksChange {
+    Allow(String),
+    Block(String),
+    /// Takes a domain off both lists.
+    Remove(String),
+    BlockInvites(Option<bool>),
+    NewUserHours(Option<u32>),
+}
+
+/// The challenge new members solve.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaKind {
+    /// Press a button.
+    Button,
+    /// Pick the sum of two numbers.
+    Math,
+    /// Pick the named emoji.
+    Emoji,
+}
+
+impl CaptchaKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CaptchaKind::Button => "button",
+            CaptchaKind::Math => "math",
+            CaptchaKind::Emoji => "emoji",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [CaptchaKind::Button, CaptchaKind::Math, CaptchaKind::Emoji]
+            .into_iter()
+            .find(|k| k.name() == name)
+    }
+}
+
+/// Join captcha of a chat; unset values fall back to the configuration.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptchaSettings {
+    pub kind: Option<CaptchaKind>,
+    /// Seconds a new member has to solve the challenge.
+    pub timeout: Option<u64>,
+}
+
+/// A change to the [`CaptchaSettings`], `None` restoring the configured value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaptchaChange {
+    Kind(Option<CaptchaKind>),
+    Timeout(Option<u64>),
+}
+
+/// Kind of media attached to a greeting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Photo,
+    Video,
+    Animation,
+    Document,
+}
+
+/// A file sent with a greeting, its text becoming the caption.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GreetingMedia {
+    pub kind: MediaKind,
+    pub file_id: String,
+}
+
+/// A link button under a greeting.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GreetingButton {
+    pub text: String,
+    pub url: String,
+}
+
+/// Media and buttons of a welcome or goodbye message.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Greeting {
+    pub media: Option<GreetingMedia>,
+    pub buttons: Vec<GreetingButton>,
+}
+
+/// Welcome and goodbye messages of a chat; the welcome text is [`Model::welcome_text`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GreetingSettings {
+    pub welcome: Greeting,
+    pub goodbye_text: Option<String>,
+    pub goodbye: Greeting,
+    /// Seconds after which greetings are deleted, 0 keeping them.
+    pub delete_after: Option<u64>,
+    /// Whether Telegram's join and leave notices are deleted.
+    pub clean_service: Option<bool>,
+}
+
+/// A change to the [`GreetingSettings`], `None` restoring the configured value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GreetingsChange {
+    Welcome(Greeting),
+    GoodbyeText(Option<String>),
+    Goodbye(Greeting),
+    DeleteAfter(Option<u64>),
+    CleanService(Option<bool>),
+}
+
+/// What night mode takes away from members.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NightRestriction {
+    /// Media, stickers, polls and link previews.
+    Media,
+    /// Every kind of message.
+    All,
+}
+
+impl NightRestriction {
+    pub fn name(self) -> &'static str {
+        match self {
+            NightRestriction::Media => "media",
+            NightRestriction::All => "all",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [NightRestriction::Media, NightRestriction::All]
+            .into_iter()
+            .find(|r| r.name() == name)
+    }
+}
+
+/// Night mode of a chat; unset values fall back to the configuration.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightSettings {
+    /// Time of day the night starts at, in the chat's time zone.
+    pub start: Option<chrono::NaiveTime>,
+    /// Time of day the night ends at, in the chat's time zone.
+    pub end: Option<chrono::NaiveTime>,
+    pub restrict: Option<NightRestriction>,
+    /// Time zone of the chat, the scheduler's if unset.
+    pub timezone: Option<chrono_tz::Tz>,
+    /// Whether the start and end of the night are announced in the chat.
+    pub announce: Option<bool>,
+}
+
+/// A change to the [`NightSettings`], `None` restoring the configured value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NightChange {
+    Start(Option<chrono::NaiveTime>),
+    End(Option<chrono::NaiveTime>),
+    Restrict(Option<NightRestriction>),
+    Timezone(Option<chrono_tz::Tz>),
+    Announce(Option<bool>),
+}
+
+/// Messages a quota counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    /// Every message.
+    Messages,
+    /// Photos, videos, animations, files, audio and voice or video notes.
+    Media,
+    Stickers,
+}
+
+impl QuotaKind {
+    pub const ALL: [QuotaKind; 3] = [QuotaKind::Messages, QuotaKind::Media, QuotaKind::Stickers];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QuotaKind::Messages => "messages",
+            QuotaKind::Media => "media",
+            QuotaKind::Stickers => "stickers",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+/// At most `limit` messages of a kind per `window` seconds from each member.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quota {
+    pub kind: QuotaKind,
+    pub limit: u32,
+    pub window: u64,
+    /// Whether only newcomers are held to it.
+    #[serde(default)]
+    pub newcomers: bool,
+}
+
+/// Message quotas of a chat.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaSettings {
+    pub rules: Vec<Quota>,
+    /// Hours a member counts as a newcomer for, the configured ones if unset.
+    pub newcomer_hours: Option<u32>,
+}
+
+/// A change to the [`QuotaSettings`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuotasChange {
+    Add(Quota),
+    /// Removes the rule at the index.
+    Remove(usize),
+    NewcomerHours(Option<u32>),
+}
+
+/// A single change to a chat's settings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    WarnLimit(Option<i64>),
+    CommandPrefix(Option<String>),
+    Locale(Option<String>),
+    WelcomeText(Option<String>),
+    Module(Module, bool),
+    Flood(FloodChange),
+    Links(LinksChange),
+    Captcha(CaptchaChange),
+    Greetings(GreetingsChange),
+    Night(NightChange),
+    Quotas(QuotasChange),
+}
+
+impl Change {
+    /// The only column the change touches.
+    pub fn column(&self) -> Column {
+        match self {
+            Change::WarnLimit(_) => Column::WarnLimit,
+            Change::CommandPrefix(_) => Column::CommandPrefix,
+            Change::Locale(_) => Column::Locale,
+            Change::WelcomeText(_) => Column::WelcomeText,
+            Change::Module(..) => Column::Modules,
+            Change::Flood(_) => Column::Flood,
+            Change::Links(_) => Column::Links,
+            Change::Captcha(_) => Column::Captcha,
+            Change::Greetings(_) => Column::Greetings,
+            Change::Night(_) => Column::Night,
+            Change::Quotas(_) => Column::Quotas,
+        }
+    }
+
+    /// Applies the change, returning the setting's name with its old and new values.
+    pub fn apply(self, settings: &mut Model) -> (String, Json, Json) {
+        fn swap<T: Serialize>(field: &mut T, value: T) -> (Json, Json) {
+            let old = serde_json::to_value(&*field).unwrap_or_default();
+            let new = serde_json::to_value(&value).unwrap_or_default();
+            *field = value;
+            (old, new)
+        }
+        let (key, (old, new)) = match self {
+            Change::WarnLimit(v) => ("warn_limit".into(), swap(&mut settings.warn_limit, v)),
+            Change::CommandPrefix(v) => ("command_prefix".into(), swap(&mut settings.command_prefix, v)),
+            Change::Locale(v) => ("locale".into(), swap(&mut settings.locale, v)),
+            Change::WelcomeText(v) => ("welcome_text".into(), swap(&mut settings.welcome_text, v)),
+            Change::Module(module, enabled) => {
+                let old = Json::Bool(settings.module_enabled(module));
+                if !settings.modules.is_object() {
+                    settings.modules = Json::Object(Default::default());
+                }
+                settings.modules[module.name()] = Json::Bool(enabled);
+                (format!("modules.{module}"), (old, Json::Bool(enabled)))
+            }
+            Change::Flood(change) => {
+                let mut flood = settings.flood();
+                let (name, values) = match change {
+                    FloodChange::Messages(v) => ("messages", swap(&mut flood.messages, v)),
+                    FloodChange::Window(v) => ("window", swap(&mut flood.window, v)),
+                    FloodChange::Repeats(v) => ("repeats", swap(&mut flood.repeats, v)),
+                    FloodChange::Stickers(v) => ("stickers", swap(&mut flood.stickers, v)),
+                    FloodChange::Action(v) => ("action", swap(&mut flood.action, v)),
+                };
+                settings.flood = serde_json::to_value(&flood).unwrap_or_default();
+                (format!("flood.{name}"), values)
+            }
+            Change::Links(change) => {
+                let mut links = settings.links();
+                let (name, values) = match change {
+                    LinksChange::Allow(domain) => {
+                        let old = serde_json::json!(links.allowlist);
+                        links.blocklist.retain(|d| *d != domain);
+                        if !links.allowlist.contains(&domain) {
+                            links.allowlist.push(domain);
+                        }
+                        ("allowlist", (old, serde_json::json!(links.allowlist)))
+                    }
+                    LinksChange::Block(domain) => {
+                        let old = serde_json::json!(links.blocklist);
+                        links.allowlist.retain(|d| *d != domain);
+                        if !links.blocklist.contains(&domain) {
+                            links.blocklist.push(domain);
+                        }
+                        ("blocklist", (old, serde_json::json!(links.blocklist)))
+                    }
+                    LinksChange::Remove(domain) => {
+                        let old = serde_json::json!([&links.allowlist, &links.blocklist]);
+                        links.allowlist.retain(|d| *d != domain);
+                        links.blocklist.retain(|d| *d != domain);
+                        let new = serde_json::json!([&links.allowlist, &links.blocklist]);
+                        ("lists", (old, new))
+                    }
+                    LinksChange::BlockInvites(v) => ("block_invites", swap(&mut links.block_invites, v)),
+                    LinksChange::NewUserHours(v) => ("new_user_hours", swap(&mut links.new_user_hours, v)),
+                };
+                settings.links = serde_json::to_value(&links).unwrap_or_default();
+                (format!("links.{name}"), values)
+            }
+            Change::Captcha(change) => {
+                let mut captcha = settings.captcha();
+                let (name, values) = match change {
+                    CaptchaChange::Kind(v) => ("kind", swap(&mut captcha.kind, v)),
+                    CaptchaChange::Timeout(v) => ("timeout", swap(&mut captcha.timeout, v)),
+                };
+                settings.captcha = serde_json::to_value(&captcha).unwrap_or_default();
+                (format!("captcha.{name}"), values)
+            }
+            Change::Greetings(change) => {
+                let mut greetings = settings.greetings();
+                let (name, values) = match change {
+                    GreetingsChange::Welcome(v) => ("welcome", swap(&mut greetings.welcome, v)),
+                    GreetingsChange::GoodbyeText(v) => ("goodbye_text", swap(&mut greetings.goodbye_text, v)),
+                    GreetingsChange::Goodbye(v) => ("goodbye", swap(&mut greetings.goodbye, v)),
+                    GreetingsChange::DeleteAfter(v) => ("delete_after", swap(&mut greetings.delete_after, v)),
+                    GreetingsChange::CleanService(v) => ("clean_service", swap(&mut greetings.clean_service, v)),
+                };
+                settings.greetings = serde_json::to_value(&greetings).unwrap_or_default();
+                (format!("greetings.{name}"), values)
+            }
+            Change::Night(change) => {
+                let mut night = settings.night();
+                let (name, values) = match change {
+                    NightChange::Start(v) => ("start", swap(&mut night.start, v)),
+                    NightChange::End(v) => ("end", swap(&mut night.end, v)),
+                    NightChange::Restrict(v) => ("restrict", swap(&mut night.restrict, v)),
+                    NightChange::Timezone(v) => ("timezone", swap(&mut night.timezone, v)),
+                    NightChange::Announce(v) => ("announce", swap(&mut night.announce, v)),
+                };
+                settings.night = serde_json::to_value(&night).unwrap_or_default();
+                (format!("night.{name}"), values)
+            }
+            Change::Quotas(change) => {
+                let mut quotas = settings.quotas();
+                let (name, values) = match change {
+                    QuotasChange::Add(quota) => {
+                        let old = serde_json::json!(quotas.rules);
+                        quotas.rules.push(quota);
+                        ("rules", (old, serde_json::json!(quotas.rules)))
+                    }
+                    QuotasChange::Remove(index) => {
+                        let old = serde_json::json!(quotas.rules);
+                        if index < quotas.rules.len() {
+                            quotas.rules.remove(index);
+                        }
+                        ("rules", (old, serde_json::json!(quotas.rules)))
+                    }
+                    QuotasChange::NewcomerHours(v) => ("newcomer_hours", swap(&mut quotas.newcomer_hours, v)),
+                };
+                settings.quotas = serde_json::to_value(&quotas).unwrap_or_default();
+                (format!("quotas.{name}"), values)
+            }
+        };
+        (key, old, new)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
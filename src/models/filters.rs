@@ -0,0 +1,60 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A blocklist entry of a chat.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "filters")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub chat_id: i64,
+    pub kind: Kind,
+    pub pattern: String,
+    pub action: Action,
+    /// Sent to the chat when the entry matches.
+    pub reply: Option<String>,
+    pub created_by: i64,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+/// How the pattern is matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::None)",
+    rename_all = "snake_case"
+)]
+pub enum Kind {
+    /// A whole word.
+    Word,
+    /// A regular expression.
+    Regex,
+    /// A phrase where `*` stands for any text and `?` for any character.
+    Wildcard,
+}
+
+/// What happens to the author of a matching message, which is always deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::None)",
+    rename_all = "snake_case"
+)]
+pub enum Action {
+    Delete,
+    Warn,
+    Mute,
+    Ban,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::CreatedBy",
+        to = "super::users::Column::Id"
+    )]
+    User,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,32 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A user banned from every chat the bot administers.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "global_bans")]
+pub struct Model {
+    /// Not necessarily a known user, since bans can be imported.
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub banned_by: i64,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::BannedBy",
+        to = "super::users::Column::Id"
+    )]
+    BannedBy,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BannedBy.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
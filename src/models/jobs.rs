@@ -0,0 +1,131 @@
+use crate::permission::Permission;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Work the bot does at a set time, once or on a cron schedule.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "jobs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub kind: Kind,
+    pub chat_id: i64,
+    pub created_by: i64,
+    /// Serialized payload of the kind, e.g. [`Post`].
+    pub payload: Json,
+    /// When the job runs next.
+    pub run_at: DateTimeWithTimeZone,
+    /// Five-field cron expression of a recurring job.
+    pub cron: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl Model {
+    pub fn post(&self) -> Post {
+        serde_json::from_value(self.payload.clone()).unwrap_or_default()
+    }
+
+    pub fn reminder(&self) -> Reminder {
+        serde_json::from_value(self.payload.clone()).unwrap_or_default()
+    }
+
+    pub fn captcha(&self) -> Captcha {
+        serde_json::from_value(self.payload.clone()).unwrap_or_default()
+    }
+
+    pub fn delete_message(&self) -> DeleteMessage {
+        serde_json::from_value(self.payload.clone()).unwrap_or_default()
+    }
+}
+
+/// What a job does when it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::None)",
+    rename_all = "snake_case"
+)]
+pub enum Kind {
+    /// Posts a message into the chat.
+    Post,
+    /// Reminds its creator of something.
+    Reminder,
+    /// Kicks its creator, a new member, unless they solve the captcha first.
+    Captcha,
+    /// Deletes a message the bot sent, e.g. a welcome.
+    DeleteMessage,
+}
+
+impl Kind {
+    /// What it takes to create a job of this kind, or to delete somebody else's.
+    pub fn permission(self) -> Permission {
+        match self {
+            Kind::Post => Permission::EditSettings,
+            Kind::Reminder => Permission::UseCommands,
+            // Scheduled by the bot itself and personal, so never checked.
+            Kind::Captcha | Kind::DeleteMessage => Permission::EditSettings,
+        }
+    }
+
+    /// Whether jobs of this kind are their creator's own business, which nobody else may delete.
+    pub fn personal(self) -> bool {
+        matches!(self, Kind::Reminder | Kind::Captcha | Kind::DeleteMessage)
+    }
+}
+
+/// Payload of a [`Kind::Post`] job.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Post {
+    pub text: String,
+    /// Whether the posted message is pinned.
+    pub pin: bool,
+}
+
+/// Payload of a [`Kind::Reminder`] job.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reminder {
+    pub text: String,
+    /// Message the reminder replies to in the chat.
+    pub reply_to: Option<i32>,
+    /// Whether the reminder is sent privately rather than into the chat.
+    pub dm: bool,
+    /// Code of the locale the reminder was set in.
+    pub locale: String,
+}
+
+/// Payload of a [`Kind::Captcha`] job, which is the pending challenge itself.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Captcha {
+    /// Callback data of the right button.
+    pub answer: String,
+    /// Message with the challenge, deleted once it is over.
+    pub message: i32,
+}
+
+/// Payload of a [`Kind::DeleteMessage`] job.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeleteMessage {
+    pub message: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::CreatedBy",
+        to = "super::users::Column::Id"
+    )]
+    Creator,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Creator.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,9 +1,31 @@
 pub use super::actions::ActiveModel as Action;
 pub use super::actions::Entity as ActionEntity;
 pub use super::actions::Model as ActionModel;
+pub use super::bot_state::ActiveModel as BotState;
+pub use super::bot_state::Entity as BotStateEntity;
+pub use super::chat_settings::ActiveModel as ChatSettings;
+pub use super::chat_settings::Entity as ChatSettingsEntity;
+pub use super::chat_settings::Model as ChatSettingsModel;
 pub use super::commands::ActiveModel as Command;
 pub use super::commands::Entity as CommandEntity;
 pub use super::commands::Model as CommandModel;
+pub use super::filters::ActiveModel as Filter;
+pub use super::filters::Entity as FilterEntity;
+pub use super::filters::Model as FilterModel;
+pub use super::global_bans::ActiveModel as GlobalBan;
+pub use super::global_bans::Entity as GlobalBanEntity;
+pub use super::global_bans::Model as GlobalBanModel;
+pub use super::jobs::ActiveModel as Job;
+pub use super::jobs::Entity as JobEntity;
+pub use super::jobs::Model as JobModel;
+pub use super::permission_overrides::ActiveModel as PermissionOverride;
+pub use super::permission_overrides::Entity as PermissionOverrideEntity;
+pub use super::reports::ActiveModel as Report;
+pub use super::reports::Entity as ReportEntity;
+pub use super::reports::Model as ReportModel;
+pub use super::roles::ActiveModel as CustomRole;
+pub use super::roles::Entity as CustomRoleEntity;
+pub use super::roles::Model as CustomRoleModel;
 pub use super::users::ActiveModel as User;
 pub use super::users::Entity as UserEntity;
 pub use super::users::Model as UserModel;
@@ -0,0 +1,53 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A message flagged by a user for the moderators.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "reports")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub chat_id: i64,
+    pub message_id: i32,
+    pub reporter_id: i64,
+    pub target_id: i64,
+    pub reason: Option<String>,
+    pub status: Status,
+    pub resolved_by: Option<i64>,
+    pub created_at: DateTimeWithTimeZone,
+    pub resolved_at: Option<DateTimeWithTimeZone>,
+}
+
+/// What a moderator did about a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::None)",
+    rename_all = "snake_case"
+)]
+pub enum Status {
+    Open,
+    Warned,
+    Muted,
+    Banned,
+    Deleted,
+    Dismissed,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::ReporterId",
+        to = "super::users::Column::Id"
+    )]
+    Reporter,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::TargetId",
+        to = "super::users::Column::Id"
+    )]
+    Target,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,34 @@
+use crate::permission::Permission;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Custom role that adds permissions on top of a user's built-in role.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, Default)]
+#[sea_orm(table_name = "roles")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub name: String,
+    /// Array of [`Permission`] names.
+    pub permissions: Json,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+impl Model {
+    pub fn permissions(&self) -> Vec<Permission> {
+        serde_json::from_value(self.permissions.clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::users::Entity")]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
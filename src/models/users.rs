@@ -1,5 +1,6 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, Default)]
 #[sea_orm(table_name = "users")]
@@ -11,10 +12,20 @@ pub struct Model {
     pub nickname: String,
     pub warns: i64,
     pub created_at: DateTimeWithTimeZone,
+    pub custom_role: Option<String>,
+    /// Messages the bot has seen from the user.
+    pub messages: i64,
+}
+
+impl Model {
+    /// Channels posting into a chat are stored under their (negative) chat id.
+    pub fn is_channel(&self) -> bool {
+        self.id < 0
+    }
 }
 
 #[derive(
-    Clone, Debug, PartialEq, PartialOrd, EnumIter, DeriveActiveEnum, Serialize, Deserialize, Default,
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, EnumIter, DeriveActiveEnum, Serialize, Deserialize, Default,
 )]
 #[sea_orm(
     rs_type = "String",
@@ -29,12 +40,29 @@ pub enum Role {
     Creator,
 }
 
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Role::Blocked => "blocked",
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Creator => "creator",
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::actions::Entity")]
     Actions,
     #[sea_orm(has_many = "super::commands::Entity")]
     Commands,
+    #[sea_orm(
+        belongs_to = "super::roles::Entity",
+        from = "Column::CustomRole",
+        to = "super::roles::Column::Name"
+    )]
+    CustomRole,
 }
 
 impl Related<super::commands::Entity> for Entity {
@@ -49,4 +77,10 @@ impl Related<super::actions::Entity> for Entity {
     }
 }
 
+impl Related<super::roles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CustomRole.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,315 @@
+use crate::command::Context;
+use crate::error::Error;
+use crate::models::prelude::UserModel;
+use crate::nickname::mention;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::target::{reply_target, Targets};
+use crate::tr;
+use teloxide::prelude::*;
+use chrono::{DateTime, Utc};
+use teloxide::types::{ChatPermissions, MessageId, UserId};
+use teloxide::utils::html;
+use std::future::Future;
+
+/// Messages Telegram deletes in one request.
+const DELETE_BATCH: usize = 100;
+/// Most messages one `/purge` deletes.
+const MAX_PURGE: i32 = 1000;
+
+/// `/warn <targets> [reason]` adds a warning and bans whoever reaches the chat's limit.
+///
+/// `/dwarn` also deletes the replied-to message.
+pub async fn warn(ctx: &Context<'_>, args: String, delete: bool) -> Result<(), Error> {
+    let Some((by, targets)) = targets(ctx, &args, delete).await? else {
+        return Ok(());
+    };
+    let limit = ctx.settings.warn_limit.unwrap_or(ctx.config.warns.max);
+    let summary = each(ctx, &targets.users, |target| async move {
+        let banned = ctx.repo.warn(by, target.id, ctx.msg.chat.id.0).await?;
+        Ok(if banned {
+            ban_in_chat(ctx.bot, ctx.msg.chat.id, target).await?;
+            tr!(ctx.i18n, ctx.locale, "moderation-warn-banned", user = mention(target))
+        } else {
+            tr!(
+                ctx.i18n,
+                ctx.locale,
+                "moderation-warned",
+                user = mention(target),
+                warns = tr!(ctx.i18n, ctx.locale, "warns", count: target.warns + 1),
+                limit = limit
+            )
+        })
+    })
+    .await;
+    if delete && summary.done > 0 {
+        delete_reply(ctx, by).await?;
+    }
+    report(ctx, summary.lines, &targets.rest).await
+}
+
+/// `/unwarn <targets>` takes one warning back.
+pub async fn unwarn(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some((by, targets)) = targets(ctx, &args, false).await? else {
+        return Ok(());
+    };
+    let summary = each(ctx, &targets.users, |target| async move {
+        ctx.repo.un_warn(by, target.id, ctx.msg.chat.id.0).await?;
+        Ok(tr!(ctx.i18n, ctx.locale, "moderation-unwarned", user = mention(target)))
+    })
+    .await;
+    report(ctx, summary.lines, &targets.rest).await
+}
+
+/// `/ban <targets> [reason]` blocks the targets in the bot and bans them from the chat.
+///
+/// `/dban` also deletes the replied-to message.
+pub async fn ban(ctx: &Context<'_>, args: String, delete: bool) -> Result<(), Error> {
+    let Some((by, targets)) = targets(ctx, &args, delete).await? else {
+        return Ok(());
+    };
+    let summary = each(ctx, &targets.users, |target| async move {
+        ctx.repo.block_user(by, target.id).await?;
+        ban_in_chat(ctx.bot, ctx.msg.chat.id, target).await?;
+        Ok(tr!(ctx.i18n, ctx.locale, "moderation-banned", user = mention(target)))
+    })
+    .await;
+    if delete && summary.done > 0 {
+        delete_reply(ctx, by).await?;
+    }
+    report(ctx, summary.lines, &targets.rest).await
+}
+
+/// `/unban <targets>` lifts both the block and the chat ban.
+pub async fn unban(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some((by, targets)) = targets(ctx, &args, false).await? else {
+        return Ok(());
+    };
+    let chat = ctx.msg.chat.id;
+    let summary = each(ctx, &targets.users, |target| async move {
+        ctx.repo.unblock_user(by, target.id).await?;
+        if target.is_channel() {
+            ctx.bot.unban_chat_sender_chat(chat, ChatId(target.id)).await?;
+        } else {
+            ctx.bot
+                .unban_chat_member(chat, UserId(target.id as u64))
+                .only_if_banned(true)
+                .await?;
+        }
+        Ok(tr!(ctx.i18n, ctx.locale, "moderation-unbanned", user = mention(target)))
+    })
+    .await;
+    report(ctx, summary.lines, &targets.rest).await
+}
+
+/// `/del` deletes the replied-to message along with the command.
+pub async fn delete(ctx: &Context<'_>) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    if ctx.msg.reply_to_message().is_none() {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "moderation-delete-usage")).await?;
+        return Ok(());
+    }
+    delete_reply(ctx, by).await?;
+    ctx.bot.delete_message(ctx.msg.chat.id, ctx.msg.id).await?;
+    Ok(())
+}
+
+/// `/purge` deletes every message from the replied-to one up to the command.
+///
+/// Only the ids are logged, the bot does not know the text of most of these messages.
+pub async fn purge(ctx: &Context<'_>) -> Result<(), Error> {
+    let (Some(by), Some(reply)) = (ctx.sender_id(), ctx.msg.reply_to_message()) else {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "moderation-delete-usage")).await?;
+        return Ok(());
+    };
+    let first = reply.id.0.max(ctx.msg.id.0 - MAX_PURGE + 1);
+    let messages: Vec<i32> = (first..=ctx.msg.id.0).collect();
+    let chat = ctx.msg.chat.id;
+    ctx.repo
+        .delete_messages(by, None, chat.0, messages.clone(), None)
+        .await?;
+    for batch in messages.chunks(DELETE_BATCH) {
+        ctx.bot
+            .delete_messages(chat, batch.iter().copied().map(MessageId))
+            .await?;
+    }
+    let count = messages.len() as i64 - 1;
+    ctx.bot
+        .send_message(chat, tr!(ctx.i18n, ctx.locale, "moderation-purged", count: count))
+        .await?;
+    Ok(())
+}
+
+/// Deletes the message the command replies to, keeping its text in the log.
+async fn delete_reply(ctx: &Context<'_>, by: i64) -> Result<(), Error> {
+    let Some(reply) = ctx.msg.reply_to_message() else {
+        return Ok(());
+    };
+    let author = reply_target(ctx.repo, ctx.roles, ctx.msg).await?;
+    let chat = ctx.msg.chat.id;
+    ctx.repo
+        .delete_messages(
+            by,
+            author.map(|u| u.id),
+            chat.0,
+            vec![reply.id.0],
+            deleted_text(reply),
+        )
+        .await?;
+    ctx.bot.delete_message(chat, reply.id).await?;
+    Ok(())
+}
+
+fn deleted_text(msg: &Message) -> Option<String> {
+    msg.text().or_else(|| msg.caption()).map(str::to_owned)
+}
+
+/// Sender and targets of the command, or `None` after explaining the usage.
+///
+/// With `reply` the command must also answer a message, the one to delete.
+async fn targets(
+    ctx: &Context<'_>,
+    args: &str,
+    reply: bool,
+) -> Result<Option<(i64, Targets)>, Error> {
+    let targets = ctx.targets(args, 0).await?;
+    let replied = !reply || ctx.msg.reply_to_message().is_some();
+    match ctx.sender_id() {
+        Some(by) if !targets.users.is_empty() && replied => Ok(Some((by, targets))),
+        _ => {
+            ctx.reply(tr!(ctx.i18n, ctx.locale, "moderation-usage")).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Lines reporting what a command did to each of its targets.
+struct Summary {
+    lines: Vec<String>,
+    /// Targets the command succeeded on.
+    done: usize,
+}
+
+/// Runs `action` on every target, one after the other, so that failing on one target does not
+/// spare the others. Each target gets the line `action` returns, or the reason it failed.
+async fn each<'t, F, Fut>(ctx: &Context<'_>, targets: &'t [UserModel], action: F) -> Summary
+where
+    F: Fn(&'t UserModel) -> Fut,
+    Fut: Future<Output = Result<String, Error>>,
+{
+    let mut summary = Summary {
+        lines: Vec::new(),
+        done: 0,
+    };
+    for target in targets {
+        match action(target).await {
+            Ok(line) => {
+                summary.lines.push(line);
+                summary.done += 1;
+            }
+            Err(err) => {
+                if !err.is_expected() {
+                    let chat = ctx.msg.chat.id;
+                    tracing::error!("Failed to act on {} in chat {}: {}", target.id, chat, err);
+                }
+                let reason = tr!(ctx.i18n, ctx.locale, err.message_key());
+                summary.lines.push(tr!(
+                    ctx.i18n,
+                    ctx.locale,
+                    "moderation-failed",
+                    user = mention(target),
+                    reason = html::escape(&reason)
+                ));
+            }
+        }
+    }
+    summary
+}
+
+async fn report(ctx: &Context<'_>, mut lines: Vec<String>, reason: &str) -> Result<(), Error> {
+    if !reason.is_empty() {
+        let reason = html::escape(reason);
+        lines.push(tr!(ctx.i18n, ctx.locale, "moderation-reason", reason = reason));
+    }
+    ctx.reply_html(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// A punishment the bot hands out on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Penalty {
+    Warn,
+    /// Mute for the given number of seconds.
+    Mute(u64),
+    Ban,
+}
+
+/// What [`penalize`] ended up doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Warned, with the new number of warnings.
+    Warned(i64),
+    /// Warned up to the limit and banned.
+    WarnBanned,
+    /// Muted for the given number of minutes.
+    Muted(i64),
+    Banned,
+}
+
+/// Applies `penalty` to `target` in `chat` on behalf of `by`, usually the bot itself.
+pub async fn penalize(
+    bot: &Bot,
+    repo: &Repo,
+    by: i64,
+    chat: ChatId,
+    target: &UserModel,
+    penalty: Penalty,
+) -> Result<Outcome, Error> {
+    Ok(match penalty {
+        Penalty::Warn => {
+            if repo.warn(by, target.id, chat.0).await? {
+                ban_in_chat(bot, chat, target).await?;
+                Outcome::WarnBanned
+            } else {
+                Outcome::Warned(target.warns + 1)
+            }
+        }
+        Penalty::Mute(seconds) => {
+            let until = Utc::now() + chrono::Duration::seconds(seconds as i64);
+            repo.mute_user(by, target.id, chat.0, until.fixed_offset()).await?;
+            mute_in_chat(bot, chat, target, until).await?;
+            Outcome::Muted((seconds / 60).max(1) as i64)
+        }
+        Penalty::Ban => {
+            repo.block_user(by, target.id).await?;
+            ban_in_chat(bot, chat, target).await?;
+            Outcome::Banned
+        }
+    })
+}
+
+/// Bans a user, or a channel posting into the chat, from `chat`.
+pub async fn ban_in_chat(bot: &Bot, chat: ChatId, target: &UserModel) -> Result<(), Error> {
+    if target.is_channel() {
+        bot.ban_chat_sender_chat(chat, ChatId(target.id)).await?;
+    } else {
+        bot.ban_chat_member(chat, UserId(target.id as u64)).await?;
+    }
+    Ok(())
+}
+
+/// Takes every permission in `chat` from a user until `until`; channels cannot be muted.
+pub async fn mute_in_chat(
+    bot: &Bot,
+    chat: ChatId,
+    target: &UserModel,
+    until: DateTime<Utc>,
+) -> Result<(), Error> {
+    if !target.is_channel() {
+        bot.restrict_chat_member(chat, UserId(target.id as u64), ChatPermissions::empty())
+            .until_date(until)
+            .await?;
+    }
+    Ok(())
+}
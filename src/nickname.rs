@@ -0,0 +1,145 @@
+use crate::command::Context;
+use crate::config::NicknamesConfig;
+use crate::error::Error;
+use crate::models::prelude::UserModel;
+use crate::repository::RepositoryTrait;
+use crate::tr;
+use teloxide::types::UserId;
+use teloxide::utils::html;
+
+/// Word stems refused in every nickname.
+const PROFANITY: &[&str] = &[
+    "fuck", "shit", "bitch", "cunt", "nigg", "faggot", "whore", "хуй", "хуе", "хуё", "хуя",
+    "пизд", "ебан", "ебат", "ебал", "ёба", "бляд", "блят", "сука", "суки", "пидор", "пидар",
+    "мудак", "мудил",
+];
+/// Punctuation allowed in nicknames besides letters, digits and spaces.
+const ALLOWED_PUNCTUATION: &[char] = &['_', '-', '.', '\''];
+
+/// Why a nickname was refused; each has a message `nick-<reason>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalid {
+    Length,
+    Charset,
+    Profanity,
+}
+
+impl Invalid {
+    fn message_key(self) -> &'static str {
+        match self {
+            Invalid::Length => "nick-invalid-length",
+            Invalid::Charset => "nick-invalid-charset",
+            Invalid::Profanity => "nick-invalid-profanity",
+        }
+    }
+}
+
+/// Trims `name` and checks its length, characters and words.
+pub fn validate(name: &str, config: &NicknamesConfig) -> Result<String, Invalid> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let length = name.chars().count();
+    if length < config.min_length || length > config.max_length {
+        return Err(Invalid::Length);
+    }
+    let allowed = |c: char| c.is_alphanumeric() || c == ' ' || ALLOWED_PUNCTUATION.contains(&c);
+    if !name.chars().all(allowed) {
+        return Err(Invalid::Charset);
+    }
+    let folded: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let banned = PROFANITY
+        .iter()
+        .copied()
+        .chain(config.banned_words.iter().map(String::as_str));
+    if banned.into_iter().any(|word| folded.contains(&word.to_lowercase())) {
+        return Err(Invalid::Profanity);
+    }
+    Ok(name)
+}
+
+/// HTML mention of `user` under their nickname; channels are named without a link.
+pub fn mention(user: &UserModel) -> String {
+    if user.is_channel() {
+        html::escape(&user.nickname)
+    } else {
+        html::user_mention(UserId(user.id as u64), &user.nickname)
+    }
+}
+
+/// `/nick <name>` renames the sender, `/nick <target> <name>` renames someone else.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let targets = ctx.targets(&args, 0).await?;
+    if targets.rest.is_empty() {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "nick-usage")).await?;
+        return Ok(());
+    }
+    let name = match validate(&targets.rest, &ctx.config.nicknames) {
+        Ok(name) => name,
+        Err(invalid) => {
+            let text = tr!(
+                ctx.i18n,
+                ctx.locale,
+                invalid.message_key(),
+                min = ctx.config.nicknames.min_length,
+                max = ctx.config.nicknames.max_length
+            );
+            ctx.reply(text).await?;
+            return Ok(());
+        }
+    };
+    let id = targets.users.first().map_or(by, |user| user.id);
+    ctx.repo.change_nickname(by, id, name).await?;
+    let user = ctx.repo.get_user(id).await?;
+    ctx.reply_html(tr!(ctx.i18n, ctx.locale, "nick-changed", user = mention(&user)))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str) -> Result<String, Invalid> {
+        validate(name, &NicknamesConfig::default())
+    }
+
+    #[test]
+    fn trims_and_collapses_spaces() {
+        assert_eq!(check("  Tom   O'Neil  "), Ok("Tom O'Neil".to_owned()));
+        assert_eq!(check("Котик_42"), Ok("Котик_42".to_owned()));
+    }
+
+    #[test]
+    fn checks_length_in_characters() {
+        assert_eq!(check("a"), Err(Invalid::Length));
+        assert_eq!(check("  a  "), Err(Invalid::Length));
+        assert_eq!(check(&"я".repeat(32)), Ok("я".repeat(32)));
+        assert_eq!(check(&"я".repeat(33)), Err(Invalid::Length));
+    }
+
+    #[test]
+    fn refuses_other_characters() {
+        assert_eq!(check("<b>bold</b>"), Err(Invalid::Charset));
+        assert_eq!(check("cool 😎"), Err(Invalid::Charset));
+        assert_eq!(check("tab\u{200b}bed"), Err(Invalid::Charset));
+    }
+
+    #[test]
+    fn refuses_profanity_across_separators() {
+        assert_eq!(check("Shit Happens"), Err(Invalid::Profanity));
+        assert_eq!(check("s.h.i.t"), Err(Invalid::Profanity));
+        assert_eq!(check("СУКА"), Err(Invalid::Profanity));
+        let config = NicknamesConfig {
+            banned_words: vec!["Admin".to_owned()],
+            ..NicknamesConfig::default()
+        };
+        assert_eq!(validate("the_admin", &config), Err(Invalid::Profanity));
+        assert_eq!(validate("moderator", &config), Ok("moderator".to_owned()));
+    }
+}
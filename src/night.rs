@@ -0,0 +1,213 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::i18n::{I18n, Locale};
+use crate::models::chat_settings::{Module, NightRestriction};
+use crate::models::prelude::ChatSettingsModel;
+use crate::models::users::Role;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{ChatPermissions, User};
+
+/// Bot state key of the chats whose night has started, with the permissions to restore.
+const NIGHTS_STATE: &str = "night_chats";
+/// How often chats are checked for the start or end of their night.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Night mode of a chat with the configuration filled in.
+pub struct Night {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: Tz,
+    pub restrict: NightRestriction,
+    pub announce: bool,
+}
+
+impl Night {
+    pub fn of(settings: &ChatSettingsModel, config: &Config) -> Self {
+        let night = settings.night();
+        Self {
+            start: night.start.unwrap_or(config.night.start),
+            end: night.end.unwrap_or(config.night.end),
+            timezone: night.timezone.unwrap_or(config.scheduler.timezone),
+            restrict: night.restrict.unwrap_or(config.night.restrict),
+            announce: night.announce.unwrap_or(config.night.announce),
+        }
+    }
+
+    /// Whether it is night in the chat at `now`; the night may span midnight.
+    fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.timezone).time();
+        match self.start <= self.end {
+            true => self.start <= time && time < self.end,
+            false => time >= self.start || time < self.end,
+        }
+    }
+
+    /// `permissions` with what the night takes away removed.
+    fn restricted(&self, permissions: ChatPermissions) -> ChatPermissions {
+        let media = ChatPermissions::SEND_MEDIA_MESSAGES
+            | ChatPermissions::SEND_POLLS
+            | ChatPermissions::SEND_OTHER_MESSAGES
+            | ChatPermissions::ADD_WEB_PAGE_PREVIEWS;
+        match self.restrict {
+            NightRestriction::Media => permissions - media,
+            NightRestriction::All => permissions - media - ChatPermissions::SEND_MESSAGES,
+        }
+    }
+}
+
+async fn nights(repo: &Repo) -> Result<BTreeMap<i64, ChatPermissions>, Error> {
+    Ok(repo
+        .get_state(NIGHTS_STATE.to_owned())
+        .await?
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default())
+}
+
+/// Moderators present in `chat` that are not its administrators.
+///
+/// Telegram holds chat-wide permissions against every member but administrators, and no member
+/// can be given more than the chat allows, so the night would silence these moderators too.
+async fn silenced(bot: &Bot, repo: &Repo, chat: ChatId) -> Result<Vec<User>, Error> {
+    let mut silenced = Vec::new();
+    for role in [Role::Moderator, Role::Creator] {
+        for user in repo.get_users_by_role(role).await? {
+            // Users who never joined the chat are not found.
+            let Ok(member) = bot.get_chat_member(chat, UserId(user.id as u64)).await else {
+                continue;
+            };
+            if member.is_present() && !member.is_privileged() && !member.user.is_bot {
+                silenced.push(member.user);
+            }
+        }
+    }
+    Ok(silenced)
+}
+
+/// Why night mode may not be turned on in `chat` yet, if it may not: the moderators it would
+/// silence, who have to be made administrators first.
+pub async fn refusal(
+    bot: &Bot,
+    i18n: &I18n,
+    repo: &Repo,
+    locale: Locale,
+    chat: ChatId,
+) -> Result<Option<String>, Error> {
+    let silenced = silenced(bot, repo, chat).await?;
+    if silenced.is_empty() {
+        return Ok(None);
+    }
+    let users: Vec<_> = silenced.iter().map(User::full_name).collect();
+    Ok(Some(tr!(i18n, locale, "night-moderators", users = users.join(", "))))
+}
+
+/// Restricts the chats of the night mode module when their night starts and restores their
+/// permissions when it ends, for as long as the bot runs.
+///
+/// The restriction is chat-wide, which Telegram does not apply to administrators, so moderators
+/// keep writing only as administrators, as role sync makes them; night mode cannot be turned on
+/// while moderators in the chat are not, see [`refusal`]. The permissions to restore are
+/// kept in the repository, so a night that ends while the bot is down ends once it is back.
+pub async fn run(bot: Bot, i18n: Arc<I18n>, repo: Arc<Repo>, config: Arc<Config>, me: UserId) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&bot, &i18n, &repo, &config, me.0 as i64).await {
+            tracing::error!("Failed to check night mode: {}", e);
+        }
+    }
+}
+
+async fn check(bot: &Bot, i18n: &I18n, repo: &Repo, config: &Config, me: i64) -> Result<(), Error> {
+    let mut nights = nights(repo).await?;
+    let mut chats: BTreeMap<i64, ChatSettingsModel> = repo
+        .get_chats_with_module(Module::NightMode)
+        .await?
+        .into_iter()
+        .map(|settings| (settings.chat_id, settings))
+        .collect();
+    // Chats that turned the module off at night still get their permissions back.
+    for &chat in nights.keys() {
+        if let Entry::Vacant(entry) = chats.entry(chat) {
+            entry.insert(repo.get_chat_settings(chat).await?);
+        }
+    }
+    let now = Utc::now();
+    for (chat, settings) in chats {
+        let night = Night::of(&settings, config);
+        let is_night = settings.module_enabled(Module::NightMode) && night.contains(now);
+        let result = match (is_night, nights.get(&chat).cloned()) {
+            (true, None) => begin(bot, &night, ChatId(chat)).await.map(|saved| {
+                nights.insert(chat, saved);
+            }),
+            (false, Some(saved)) => bot
+                .set_chat_permissions(ChatId(chat), saved)
+                .await
+                .map(|_| {
+                    nights.remove(&chat);
+                })
+                .map_err(Error::from),
+            _ => continue,
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to switch night mode of chat {}: {}", chat, e);
+            continue;
+        }
+        // Saved at once, so that the permissions to restore are not lost if anything else fails.
+        repo.set_state(NIGHTS_STATE.to_owned(), serde_json::json!(nights))
+            .await?;
+        if let Err(e) = report(bot, i18n, repo, &night, &settings, me, is_night).await {
+            tracing::warn!("Failed to announce night mode of chat {}: {}", chat, e);
+        }
+    }
+    Ok(())
+}
+
+/// Restricts the chat, returning the permissions it had before.
+async fn begin(bot: &Bot, night: &Night, chat: ChatId) -> Result<ChatPermissions, Error> {
+    let permissions = bot
+        .get_chat(chat)
+        .await?
+        .permissions()
+        .unwrap_or_else(ChatPermissions::all);
+    bot.set_chat_permissions(chat, night.restricted(permissions.clone()))
+        .await?;
+    Ok(permissions)
+}
+
+/// Logs the start or end of the night and announces it if the chat wants to.
+async fn report(
+    bot: &Bot,
+    i18n: &I18n,
+    repo: &Repo,
+    night: &Night,
+    settings: &ChatSettingsModel,
+    me: i64,
+    started: bool,
+) -> Result<(), Error> {
+    repo.night_mode(me, settings.chat_id, started).await?;
+    if !night.announce {
+        return Ok(());
+    }
+    let locale = i18n.locale(settings.locale.as_deref(), None);
+    let text = match (started, night.restrict) {
+        (true, NightRestriction::Media) => {
+            tr!(i18n, locale, "night-start-media", end = night.end.format("%H:%M"))
+        }
+        (true, NightRestriction::All) => {
+            tr!(i18n, locale, "night-start-all", end = night.end.format("%H:%M"))
+        }
+        (false, _) => tr!(i18n, locale, "night-end"),
+    };
+    bot.send_message(ChatId(settings.chat_id), text)
+        .disable_notification(true)
+        .await?;
+    Ok(())
+}
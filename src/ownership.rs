@@ -0,0 +1,141 @@
+use crate::command::Context;
+use crate::error::reply::alert;
+use crate::error::{Error, HandlerError};
+use crate::i18n::I18n;
+use crate::models::prelude::*;
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::{error, tr};
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+const CALLBACK: &str = "transfer:";
+/// How long the confirmation buttons stay valid, in seconds.
+const CONFIRM_TTL: i64 = 5 * 60;
+
+/// `/transfer_ownership` asks the creator to confirm handing their role to the target.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    ctx.repo
+        .authorize(by, Permission::TransferOwnership, None, None)
+        .await?;
+    let Some(target) = ctx.target(&args, 0).await? else {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "transfer-usage")).await?;
+        return Ok(());
+    };
+    error!(target.id < 0 => RepoError::NotAllowed {
+        reason: "channels cannot own the bot",
+    });
+    error!(
+        target.role == Role::Blocked ||
+        target.role == Role::Creator => RepoError::invalid_role(&target)
+    );
+    let now = chrono::Utc::now().timestamp();
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(
+            tr!(ctx.i18n, ctx.locale, "transfer-confirm-button"),
+            format!("{CALLBACK}ok:{by}:{}:{now}", target.id),
+        ),
+        InlineKeyboardButton::callback(
+            tr!(ctx.i18n, ctx.locale, "transfer-cancel-button"),
+            format!("{CALLBACK}cancel:{by}"),
+        ),
+    ]]);
+    ctx.bot
+        .send_message(
+            ctx.msg.chat.id,
+            tr!(ctx.i18n, ctx.locale, "transfer-confirm", user = mention(&target)),
+        )
+        .parse_mode(ParseMode::Html)
+        .reply_markup(keyboard)
+        .reply_to(ctx.msg.id)
+        .await?;
+    Ok(())
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(CALLBACK))
+}
+
+enum Answer {
+    Confirm { from: i64, to: i64, at: i64 },
+    Cancel { from: i64 },
+}
+
+fn parse(data: &str) -> Option<Answer> {
+    let mut parts = data.strip_prefix(CALLBACK)?.split(':');
+    match parts.next()? {
+        "ok" => Some(Answer::Confirm {
+            from: parts.next()?.parse().ok()?,
+            to: parts.next()?.parse().ok()?,
+            at: parts.next()?.parse().ok()?,
+        }),
+        "cancel" => Some(Answer::Cancel {
+            from: parts.next()?.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Handles the confirmation buttons. Only the creator who asked may press them.
+pub async fn callback(
+    bot: Bot,
+    q: CallbackQuery,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+) -> Result<(), HandlerError> {
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        return Ok(());
+    };
+    let Some(answer) = q.data.as_deref().and_then(parse) else {
+        return Ok(());
+    };
+    let locale = i18n.locale(
+        repo.get_chat_settings(message.chat.id.0)
+            .await
+            .ok()
+            .and_then(|s| s.locale)
+            .as_deref(),
+        q.from.language_code.as_deref(),
+    );
+    let presser = q.from.id.0 as i64;
+    let result: Result<(), Error> = async {
+        let from = match answer {
+            Answer::Confirm { from, .. } | Answer::Cancel { from } => from,
+        };
+        if presser != from {
+            bot.answer_callback_query(q.id.clone())
+                .text(tr!(i18n, locale, "transfer-not-yours"))
+                .await?;
+            return Ok(());
+        }
+        let text = match answer {
+            Answer::Cancel { .. } => tr!(i18n, locale, "transfer-cancelled"),
+            Answer::Confirm { at, .. } if chrono::Utc::now().timestamp() - at > CONFIRM_TTL => {
+                tr!(i18n, locale, "transfer-expired")
+            }
+            Answer::Confirm { from, to, .. } => {
+                repo.transfer_ownership(from, to).await?;
+                tracing::info!("User {} transferred ownership to {}", from, to);
+                let target = repo.get_user(to).await?;
+                tr!(i18n, locale, "transfer-done", user = mention(&target))
+            }
+        };
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .parse_mode(ParseMode::Html)
+            .await?;
+        bot.answer_callback_query(q.id.clone()).await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = result {
+        alert(&bot, &i18n, locale, &q, err).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::models::users::Role;
+use sea_orm::entity::prelude::*;
+use sea_orm::Iterable;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Something a user may be allowed to do.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::None)",
+    rename_all = "snake_case"
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Warn,
+    Mute,
+    Ban,
+    UseCommands,
+    CreateCommands,
+    ManageCommands,
+    ManageRoles,
+    ViewLog,
+    EditSettings,
+    EditNicknames,
+    TransferOwnership,
+    GlobalBan,
+    /// Not being held to the chat's message quotas.
+    BypassQuotas,
+    /// Not being punished by the anti-flood module.
+    BypassFlood,
+    /// Not being punished by the chat's word filters.
+    BypassFilters,
+    /// Posting links the link filter would refuse.
+    BypassLinks,
+    /// Joining without solving a captcha.
+    BypassCaptcha,
+}
+
+impl Permission {
+    pub fn name(self) -> String {
+        self.to_value()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::iter().find(|p| p.to_value() == name)
+    }
+
+    pub fn iter_all() -> impl Iterator<Item = Self> {
+        Self::iter()
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+/// Whether a user of role `actor` holding a permission may use it on one of role `target`.
+///
+/// Only moderators and creators are protected, and only from users not above them, so custom
+/// roles and overrides work against every ordinary user.
+pub fn may_act_on(actor: Role, target: Role) -> bool {
+    target < Role::Moderator || target < actor
+}
+
+/// Permissions every built-in [`Role`] grants.
+#[derive(Debug, Clone)]
+pub struct PermissionMap {
+    roles: HashMap<Role, HashSet<Permission>>,
+}
+
+impl Default for PermissionMap {
+    fn default() -> Self {
+        use Permission::*;
+        let user = HashSet::from([UseCommands, CreateCommands]);
+        let mut moderator = user.clone();
+        moderator.extend([
+            Warn,
+            Mute,
+            Ban,
+            ManageCommands,
+            ViewLog,
+            EditSettings,
+            EditNicknames,
+            BypassQuotas,
+            BypassFlood,
+            BypassFilters,
+            BypassLinks,
+            BypassCaptcha,
+        ]);
+        Self {
+            roles: HashMap::from([
+                (Role::Blocked, HashSet::new()),
+                (Role::User, user),
+                (Role::Moderator, moderator),
+                (Role::Creator, Permission::iter().collect()),
+            ]),
+        }
+    }
+}
+
+impl PermissionMap {
+    /// Replaces the permissions of `role`.
+    pub fn set(&mut self, role: Role, permissions: impl IntoIterator<Item = Permission>) {
+        self.roles.insert(role, permissions.into_iter().collect());
+    }
+
+    /// Effective permissions of a user with the given role, custom role and overrides.
+    ///
+    /// Blocked users get nothing, whatever their custom role or overrides say.
+    pub fn resolve(
+        &self,
+        role: Role,
+        custom: &[Permission],
+        overrides: impl IntoIterator<Item = (Permission, bool)>,
+    ) -> HashSet<Permission> {
+        if role == Role::Blocked {
+            return HashSet::new();
+        }
+        let mut granted = self.roles.get(&role).cloned().unwrap_or_default();
+        granted.extend(custom.iter().copied());
+        for (permission, allowed) in overrides {
+            if allowed {
+                granted.insert(permission);
+            } else {
+                granted.remove(&permission);
+            }
+        }
+        granted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_grant_their_defaults() {
+        let map = PermissionMap::default();
+        let user = map.resolve(Role::User, &[], []);
+        assert!(user.contains(&Permission::UseCommands));
+        assert!(!user.contains(&Permission::Warn));
+        let moderator = map.resolve(Role::Moderator, &[], []);
+        assert!(moderator.contains(&Permission::Ban));
+        assert!(moderator.contains(&Permission::BypassFlood));
+        assert!(!moderator.contains(&Permission::GlobalBan));
+        let creator = map.resolve(Role::Creator, &[], []);
+        assert_eq!(creator.len(), Permission::iter_all().count());
+    }
+
+    #[test]
+    fn custom_roles_and_overrides_apply_in_order() {
+        let map = PermissionMap::default();
+        let granted = map.resolve(
+            Role::User,
+            &[Permission::Warn, Permission::Mute],
+            [(Permission::Mute, false), (Permission::BypassLinks, true)],
+        );
+        assert!(granted.contains(&Permission::Warn));
+        assert!(!granted.contains(&Permission::Mute));
+        assert!(granted.contains(&Permission::BypassLinks));
+    }
+
+    #[test]
+    fn blocked_users_get_nothing() {
+        let map = PermissionMap::default();
+        let granted = map.resolve(Role::Blocked, &[Permission::Warn], [(Permission::Ban, true)]);
+        assert!(granted.is_empty());
+    }
+
+    #[test]
+    fn set_replaces_a_role() {
+        let mut map = PermissionMap::default();
+        map.set(Role::Moderator, [Permission::Warn]);
+        assert_eq!(
+            map.resolve(Role::Moderator, &[], []),
+            HashSet::from([Permission::Warn])
+        );
+    }
+
+    #[test]
+    fn only_staff_is_protected_from_peers() {
+        assert!(may_act_on(Role::User, Role::User));
+        assert!(may_act_on(Role::User, Role::Blocked));
+        assert!(may_act_on(Role::Moderator, Role::User));
+        assert!(!may_act_on(Role::User, Role::Moderator));
+        assert!(!may_act_on(Role::Moderator, Role::Moderator));
+        assert!(may_act_on(Role::Creator, Role::Moderator));
+        assert!(!may_act_on(Role::Creator, Role::Creator));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for permission in Permission::iter_all() {
+            assert_eq!(Permission::from_name(&permission.name()), Some(permission));
+        }
+        assert_eq!(Permission::BypassCaptcha.name(), "bypass_captcha");
+    }
+}
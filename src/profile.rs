@@ -0,0 +1,116 @@
+use crate::command::Context;
+use crate::error::Error;
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::repository::db::RepoError;
+use crate::repository::RepositoryTrait;
+use crate::tr;
+use sea_orm::ActiveEnum;
+use teloxide::utils::html;
+
+/// Commands and actions listed in a profile.
+const LIST_SIZE: u64 = 5;
+/// Commands counted towards the usage total.
+const MAX_COMMANDS: u64 = 1000;
+
+/// `/profile` (`/info`) shows what the bot knows about the sender, or about the target.
+///
+/// Users allowed to view the log also see the id, the custom role, permissions and the details
+/// of every action.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let user = match ctx.target(&args, 0).await? {
+        Some(user) => user,
+        None => ctx.repo.get_user(by).await?,
+    };
+    let chat = Some(ctx.msg.chat.id.0);
+    let detailed = match ctx.repo.authorize(by, Permission::ViewLog, None, chat).await {
+        Ok(_) => true,
+        Err(RepoError::Forbidden { .. }) => false,
+        Err(e) => return Err(e.into()),
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let role = tr!(i18n, locale, &format!("role-name-{}", user.role));
+    let limit = ctx.settings.warn_limit.unwrap_or(ctx.config.warns.max);
+    let mut lines = vec![
+        tr!(i18n, locale, "profile-title", user = mention(&user)),
+        tr!(
+            i18n,
+            locale,
+            "profile-username",
+            username = user.username.as_deref().map_or("-".to_owned(), |u| format!("@{u}"))
+        ),
+        tr!(i18n, locale, "profile-role", role = role),
+        tr!(
+            i18n,
+            locale,
+            "profile-warns",
+            warns = tr!(i18n, locale, "warns", count: user.warns),
+            limit = limit
+        ),
+        tr!(
+            i18n,
+            locale,
+            "profile-joined",
+            date = user.created_at.format("%Y-%m-%d")
+        ),
+        tr!(i18n, locale, "profile-messages", count = user.messages),
+    ];
+    if detailed {
+        let mut permissions: Vec<String> = ctx
+            .repo
+            .get_permissions(user.id, chat)
+            .await?
+            .into_iter()
+            .map(Permission::name)
+            .collect();
+        permissions.sort();
+        lines.push(tr!(i18n, locale, "profile-id", id = user.id));
+        lines.push(tr!(
+            i18n,
+            locale,
+            "profile-custom-role",
+            role = html::escape(user.custom_role.as_deref().unwrap_or("-"))
+        ));
+        lines.push(tr!(
+            i18n,
+            locale,
+            "profile-permissions",
+            permissions = permissions.join(", ")
+        ));
+    }
+
+    let commands = ctx.repo.get_user_commands(user.id, 0, MAX_COMMANDS).await?;
+    if !commands.is_empty() {
+        let uses: i64 = commands.iter().map(|c| c.times_used).sum();
+        lines.push(String::new());
+        lines.push(tr!(
+            i18n,
+            locale,
+            "profile-commands",
+            count: commands.len() as i64,
+            uses = uses
+        ));
+        for command in commands.iter().take(LIST_SIZE as usize) {
+            lines.push(format!("• {} ({})", html::escape(&command.name), command.times_used));
+        }
+    }
+
+    let actions = ctx.repo.get_user_actions(user.id, 0, LIST_SIZE).await?;
+    if !actions.is_empty() {
+        lines.push(String::new());
+        lines.push(tr!(i18n, locale, "profile-actions"));
+        for action in &actions {
+            let name = tr!(i18n, locale, &format!("action-{}", action.action_type.to_value()));
+            let date = action.created_at.format("%Y-%m-%d %H:%M");
+            lines.push(match detailed {
+                true => format!("• {date} {name} {}", html::escape(&action.description.to_string())),
+                false => format!("• {date} {name}"),
+            });
+        }
+    }
+    ctx.reply_html(lines.join("\n")).await?;
+    Ok(())
+}
@@ -0,0 +1,379 @@
+use crate::command::Context;
+use crate::config::Config;
+use crate::error::Error;
+use crate::i18n::{I18n, Locale};
+use crate::models::chat_settings::{Change, Module, Quota, QuotaKind, QuotasChange};
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::register::sender_id;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use crate::when::{duration, format_duration, word};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{Me, MessageKind, ParseMode};
+
+/// Bot state key of the saved usage counters.
+const USAGE_STATE: &str = "quota_usage";
+/// Longest window a quota may have; older usage is forgotten.
+const MAX_WINDOW: u64 = 24 * 60 * 60;
+/// Most quotas a chat may have.
+const MAX_RULES: usize = 10;
+/// Seconds between two notices to the same member, so that a burst of refused messages is not
+/// answered by a burst of notices.
+const NOTICE_COOLDOWN: i64 = 60;
+
+/// When a member of a chat sent their recent messages of a kind, by chat, member and kind.
+type Usage = HashMap<(i64, i64, QuotaKind), VecDeque<DateTime<Utc>>>;
+
+/// Usage of one kind of messages by a member of a chat, as saved.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    chat: i64,
+    user: i64,
+    kind: QuotaKind,
+    sent: Vec<DateTime<Utc>>,
+}
+
+/// When members of chats with quotas sent their recent messages, by kind.
+///
+/// Kept in memory and saved every `quotas.persist_interval`, so that a restart does not hand
+/// everybody a fresh quota.
+#[derive(Default)]
+pub struct Quotas {
+    usage: Mutex<Usage>,
+    notified: Mutex<HashMap<(i64, i64), DateTime<Utc>>>,
+}
+
+impl Quotas {
+    /// Counters as last saved, or none.
+    pub async fn load(repo: &Repo) -> Self {
+        let entries: Vec<Entry> = match repo.get_state(USAGE_STATE.to_owned()).await {
+            Ok(value) => value
+                .and_then(|value| serde_json::from_value(value).ok())
+                .unwrap_or_default(),
+            Err(e) => {
+                tracing::error!("Failed to load quota usage: {}", e);
+                Vec::new()
+            }
+        };
+        let usage = entries
+            .into_iter()
+            .map(|e| ((e.chat, e.user, e.kind), e.sent.into()))
+            .collect();
+        Self {
+            usage: Mutex::new(usage),
+            ..Default::default()
+        }
+    }
+
+    /// Forgets usage older than any window and returns the rest for saving.
+    fn snapshot(&self, now: DateTime<Utc>) -> Vec<Entry> {
+        let forget = now - chrono::Duration::seconds(MAX_WINDOW as i64);
+        let mut usage = self.usage.lock().unwrap();
+        usage.retain(|_, sent| {
+            sent.retain(|at| *at > forget);
+            !sent.is_empty()
+        });
+        self.notified
+            .lock()
+            .unwrap()
+            .retain(|_, at| (now - *at).num_seconds() < NOTICE_COOLDOWN);
+        usage
+            .iter()
+            .map(|(&(chat, user, kind), sent)| Entry {
+                chat,
+                user,
+                kind,
+                sent: sent.iter().copied().collect(),
+            })
+            .collect()
+    }
+
+    /// Returns the first of `rules` a message would break, with when the member may send it
+    /// again; otherwise counts the message against them.
+    fn take<'a>(
+        &self,
+        chat: i64,
+        user: i64,
+        rules: &[&'a Quota],
+        now: DateTime<Utc>,
+    ) -> Option<(&'a Quota, DateTime<Utc>)> {
+        let mut usage = self.usage.lock().unwrap();
+        for &rule in rules {
+            let window = chrono::Duration::seconds(rule.window as i64);
+            let limit = rule.limit.max(1) as usize;
+            let recent: Vec<_> = usage
+                .get(&(chat, user, rule.kind))
+                .into_iter()
+                .flatten()
+                .filter(|at| now - **at < window)
+                .collect();
+            if recent.len() >= limit {
+                return Some((rule, *recent[recent.len() - limit] + window));
+            }
+        }
+        let forget = now - chrono::Duration::seconds(MAX_WINDOW as i64);
+        let mut kinds = Vec::new();
+        for rule in rules {
+            if !kinds.contains(&rule.kind) {
+                kinds.push(rule.kind);
+            }
+        }
+        for kind in kinds {
+            let sent = usage.entry((chat, user, kind)).or_default();
+            while sent.front().is_some_and(|at| *at <= forget) {
+                sent.pop_front();
+            }
+            sent.push_back(now);
+        }
+        None
+    }
+
+    /// Whether the member may be told about a refused message now.
+    fn notify(&self, chat: i64, user: i64, now: DateTime<Utc>) -> bool {
+        let mut notified = self.notified.lock().unwrap();
+        match notified.get(&(chat, user)) {
+            Some(at) if (now - *at).num_seconds() < NOTICE_COOLDOWN => false,
+            _ => {
+                notified.insert((chat, user), now);
+                true
+            }
+        }
+    }
+
+    /// Saves the usage counters, so that a restart does not reset them.
+    pub async fn flush(&self, repo: &Repo) {
+        let entries = self.snapshot(Utc::now());
+        if let Err(e) = repo.set_state(USAGE_STATE.to_owned(), serde_json::json!(entries)).await {
+            tracing::error!("Failed to save quota usage: {}", e);
+        }
+    }
+}
+
+/// Saves the usage counters every `quotas.persist_interval`, for as long as the bot runs.
+pub async fn persist(repo: Arc<Repo>, config: Arc<Config>, quotas: Arc<Quotas>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.quotas.persist_interval));
+    loop {
+        interval.tick().await;
+        quotas.flush(&repo).await;
+    }
+}
+
+/// Kinds of messages `msg` counts as.
+fn kinds(msg: &Message) -> Vec<QuotaKind> {
+    let mut kinds = vec![QuotaKind::Messages];
+    let media = msg.photo().is_some()
+        || msg.video().is_some()
+        || msg.animation().is_some()
+        || msg.document().is_some()
+        || msg.audio().is_some()
+        || msg.voice().is_some()
+        || msg.video_note().is_some();
+    if media {
+        kinds.push(QuotaKind::Media);
+    }
+    if msg.sticker().is_some() {
+        kinds.push(QuotaKind::Stickers);
+    }
+    kinds
+}
+
+/// A rule as shown to users, e.g. "3 media per 1h".
+fn describe(i18n: &I18n, locale: Locale, rule: &Quota) -> String {
+    let kind = tr!(i18n, locale, &format!("quota-kind-{}", rule.kind.name()));
+    let key = if rule.newcomers { "quota-rule-newcomers" } else { "quota-rule" };
+    tr!(
+        i18n,
+        locale,
+        key,
+        limit = rule.limit,
+        kind = kind,
+        window = format_duration(rule.window)
+    )
+}
+
+/// Deletes, on behalf of the bot, group messages beyond the chat's quotas and tells their
+/// authors when they may write again.
+///
+/// Members with the `bypass_quotas` permission, moderators by default, are exempt.
+pub async fn check(
+    bot: Bot,
+    msg: Message,
+    me: Me,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+    quotas: Arc<Quotas>,
+) -> bool {
+    if msg.chat.is_private() || !matches!(msg.kind, MessageKind::Common(_)) {
+        return true;
+    }
+    let Some(sender) = sender_id(&msg) else {
+        return true;
+    };
+    match enforce(&bot, &msg, &me, &i18n, &repo, &config, &quotas, sender).await {
+        Ok(deleted) => !deleted,
+        Err(e) => {
+            tracing::error!("Quotas failed for {} in chat {}: {}", sender, msg.chat.id, e);
+            true
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn enforce(
+    bot: &Bot,
+    msg: &Message,
+    me: &Me,
+    i18n: &I18n,
+    repo: &Repo,
+    config: &Config,
+    quotas: &Quotas,
+    sender: i64,
+) -> Result<bool, Error> {
+    let chat = msg.chat.id;
+    let settings = repo.get_chat_settings(chat.0).await?;
+    if !settings.module_enabled(Module::Quotas) {
+        return Ok(false);
+    }
+    let rules = settings.quotas();
+    let kinds = kinds(msg);
+    if !rules.rules.iter().any(|rule| kinds.contains(&rule.kind)) {
+        return Ok(false);
+    }
+    if repo.get_permissions(sender, Some(chat.0)).await?.contains(&Permission::BypassQuotas) {
+        return Ok(false);
+    }
+    let user = repo.get_user(sender).await?;
+    let hours = rules.newcomer_hours.unwrap_or(config.quotas.newcomer_hours);
+    let now = Utc::now();
+    let newcomer = now < user.created_at + chrono::Duration::hours(hours.into());
+    let applicable: Vec<&Quota> = rules
+        .rules
+        .iter()
+        .filter(|rule| kinds.contains(&rule.kind) && (newcomer || !rule.newcomers))
+        .collect();
+    let Some((rule, until)) = quotas.take(chat.0, sender, &applicable, now) else {
+        return Ok(false);
+    };
+
+    let text = msg.text().or_else(|| msg.caption()).map(str::to_owned);
+    repo.delete_messages(me.id.0 as i64, Some(sender), chat.0, vec![msg.id.0], text)
+        .await?;
+    bot.delete_message(chat, msg.id).await?;
+    if !quotas.notify(chat.0, sender, now) {
+        return Ok(true);
+    }
+    let locale = i18n.locale(settings.locale.as_deref(), None);
+    let wait = (until - now).num_seconds().max(1) as u64;
+    let text = tr!(
+        i18n,
+        locale,
+        "quota-exceeded",
+        user = mention(&user),
+        rule = describe(i18n, locale, rule),
+        wait = format_duration(wait)
+    );
+    if let Err(e) = bot.send_message(chat, text).parse_mode(ParseMode::Html).await {
+        tracing::warn!("Failed to explain a quota in chat {}: {}", chat, e);
+    }
+    Ok(true)
+}
+
+/// `<limit> <kind> [per] <window> [newcomers]`, e.g. `3 media per 1h`.
+fn parse_rule(args: &str) -> Option<Quota> {
+    let (limit, rest) = word(args);
+    let (kind, rest) = word(rest);
+    let rest = match word(rest) {
+        ("per", rest) => rest,
+        _ => rest,
+    };
+    let (window, rest) = word(rest);
+    let newcomers = match rest.trim() {
+        "" => false,
+        "newcomers" => true,
+        _ => return None,
+    };
+    let window = duration(window)?.num_seconds() as u64;
+    Some(Quota {
+        kind: QuotaKind::from_name(kind)?,
+        limit: limit.parse().ok().filter(|l| *l > 0)?,
+        window: Some(window).filter(|w| *w <= MAX_WINDOW)?,
+        newcomers,
+    })
+}
+
+/// `/quota` lists the chat's quotas, `/quota add <limit> <kind> per <window> [newcomers]` adds
+/// one, `/quota remove <number>` removes one and `/quota newcomers <hours>` sets how long members
+/// count as newcomers.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let chat = ctx.msg.chat.id.0;
+    let quotas = ctx.settings.quotas();
+    let (action, rest) = word(&args);
+    let change = match action {
+        "" | "list" => {
+            let hours = quotas.newcomer_hours.unwrap_or(ctx.config.quotas.newcomer_hours);
+            if quotas.rules.is_empty() {
+                ctx.reply(tr!(i18n, locale, "quota-empty")).await?;
+                return Ok(());
+            }
+            let mut lines = vec![tr!(i18n, locale, "quota-list", hours = hours)];
+            for (i, rule) in quotas.rules.iter().enumerate() {
+                lines.push(format!("{}. {}", i + 1, describe(i18n, locale, rule)));
+            }
+            if !ctx.settings.module_enabled(Module::Quotas) {
+                lines.push(tr!(i18n, locale, "quota-module-off"));
+            }
+            ctx.reply(lines.join("\n")).await?;
+            return Ok(());
+        }
+        "add" => {
+            let Some(rule) = parse_rule(rest) else {
+                ctx.reply(tr!(i18n, locale, "quota-usage")).await?;
+                return Ok(());
+            };
+            if quotas.rules.len() >= MAX_RULES {
+                ctx.reply(tr!(i18n, locale, "quota-limit", count: MAX_RULES as i64))
+                    .await?;
+                return Ok(());
+            }
+            QuotasChange::Add(rule)
+        }
+        "remove" => {
+            let index = rest.trim().parse::<usize>().ok().filter(|n| (1..=quotas.rules.len()).contains(n));
+            let Some(index) = index else {
+                ctx.reply(tr!(i18n, locale, "quota-usage")).await?;
+                return Ok(());
+            };
+            QuotasChange::Remove(index - 1)
+        }
+        "newcomers" => match rest.trim() {
+            "default" => QuotasChange::NewcomerHours(None),
+            hours => match hours.parse() {
+                Ok(hours) => QuotasChange::NewcomerHours(Some(hours)),
+                Err(_) => {
+                    ctx.reply(tr!(i18n, locale, "quota-usage")).await?;
+                    return Ok(());
+                }
+            },
+        },
+        _ => {
+            ctx.reply(tr!(i18n, locale, "quota-usage")).await?;
+            return Ok(());
+        }
+    };
+    ctx.repo
+        .update_chat_settings(by, chat, Change::Quotas(change))
+        .await?;
+    ctx.reply(tr!(i18n, locale, "quota-saved")).await?;
+    Ok(())
+}
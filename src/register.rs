@@ -0,0 +1,137 @@
+use crate::config::Config;
+use crate::models::users::Role;
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{Chat, User};
+
+/// Makes sure the author of every message is known to the repository and counts the message.
+///
+/// Channels posting into the chat are stored as principals of their own under the (negative)
+/// channel id. Anonymous admins post as the chat itself and are not registered.
+pub async fn register_sender(
+    msg: Message,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    counter: Arc<MessageCounter>,
+) {
+    // New members are registered once they pass the captcha or speak.
+    if msg.new_chat_members().is_some() {
+        return;
+    }
+    if let Some(sender) = msg.sender_chat.as_ref().filter(|c| c.id != msg.chat.id) {
+        if let Err(e) = register_chat(&repo, sender).await {
+            tracing::error!("Failed to create/update channel {}: {}", sender.id, e);
+        }
+        counter.add(sender.id.0);
+        return;
+    }
+    let Some(user) = msg.from.as_ref().filter(|u| is_person(u)) else {
+        return;
+    };
+    if let Err(e) = register(&repo, &roles, user).await {
+        tracing::error!("Failed to create/update user {}: {}", user.id, e);
+    }
+    counter.add(user.id.0 as i64);
+}
+
+/// Principal a group message is attributed to: the channel it was posted as, or its author.
+///
+/// `None` for anonymous admins and service users.
+pub fn sender_id(msg: &Message) -> Option<i64> {
+    match msg.sender_chat.as_ref() {
+        Some(chat) if chat.id == msg.chat.id => None,
+        Some(chat) => Some(chat.id.0),
+        None => msg
+            .from
+            .as_ref()
+            .filter(|u| is_person(u))
+            .map(|u| u.id.0 as i64),
+    }
+}
+
+/// Messages sent since the last save, by sender.
+///
+/// Kept in memory and saved every `cache.count_interval` in one transaction, rather than with
+/// one update per message.
+#[derive(Default)]
+pub struct MessageCounter {
+    counts: Mutex<HashMap<i64, i64>>,
+}
+
+impl MessageCounter {
+    pub fn add(&self, id: i64) {
+        *self.counts.lock().unwrap().entry(id).or_default() += 1;
+    }
+
+    /// Saves the counts, keeping them for the next save if that fails.
+    pub async fn flush(&self, repo: &Repo) {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap());
+        if counts.is_empty() {
+            return;
+        }
+        if let Err(e) = repo.count_messages(counts.clone().into_iter().collect()).await {
+            tracing::error!("Failed to save the counts of {} senders: {}", counts.len(), e);
+            let mut pending = self.counts.lock().unwrap();
+            for (id, count) in counts {
+                *pending.entry(id).or_default() += count;
+            }
+        }
+    }
+}
+
+/// Saves the message counts every `cache.count_interval`, for as long as the bot runs.
+pub async fn flush_counts(repo: Arc<Repo>, config: Arc<Config>, counter: Arc<MessageCounter>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.cache.count_interval));
+    loop {
+        interval.tick().await;
+        counter.flush(&repo).await;
+    }
+}
+
+/// Whether `user` is a real account rather than one of the service users Telegram posts
+/// anonymous, channel and automatically forwarded messages as.
+pub fn is_person(user: &User) -> bool {
+    !user.is_anonymous() && !user.is_channel() && !user.is_telegram()
+}
+
+/// Creates a principal for a channel, or refreshes its username if it changed.
+pub async fn register_chat(repo: &Repo, chat: &Chat) -> Result<(), RepoError> {
+    let id = chat.id.0;
+    let username = chat.username().map(str::to_owned);
+    match repo.get_user(id).await {
+        Ok(known) if known.username == username => Ok(()),
+        Ok(_) | Err(RepoError::NotFound { .. }) => {
+            let title = chat.title().unwrap_or("_").to_owned();
+            repo.new_user(id, Role::User, username, title).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates the user, or refreshes their username if it changed.
+pub async fn register(repo: &Repo, roles: &RoleSelector, user: &User) -> Result<(), RepoError> {
+    let id = user.id.0 as i64;
+    match repo.get_user(id).await {
+        Ok(known) if known.username == user.username => Ok(()),
+        Ok(_) | Err(RepoError::NotFound { .. }) => {
+            repo.new_user(id, roles.select(id), user.username.clone(), user.full_name())
+                .await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Registers the bot itself as a moderator, so that the actions it takes on its own, such as
+/// muting flooders, are attributed to it.
+pub async fn register_bot(repo: &Repo, me: &User) -> Result<(), RepoError> {
+    let id = me.id.0 as i64;
+    repo.new_user(id, Role::Moderator, me.username.clone(), me.full_name())
+        .await?;
+    repo.assign_system_role(id, Role::Moderator, "startup").await?;
+    Ok(())
+}
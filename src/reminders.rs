@@ -0,0 +1,120 @@
+use crate::command::Context;
+use crate::error::Error;
+use crate::models::jobs::{Kind, Reminder};
+use crate::repository::RepositoryTrait;
+use crate::schedule::preview;
+use crate::tr;
+use crate::when::{self, format_time, word};
+use chrono::Utc;
+
+/// `/remindme [dm] <when> <text>` reminds the sender of `text` at a time such as `2h`,
+/// `2026-11-01 10:00` or `завтра в 10`, replying in the chat or, with `dm`, privately.
+///
+/// In reply to a message the reminder replies to it, and the text may be left out.
+pub async fn remind(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let tz = ctx.config.scheduler.timezone;
+    let now = Utc::now();
+    let (dm, args) = match word(&args) {
+        (flag, rest) if matches!(flag.to_lowercase().as_str(), "dm" | "лс") => (true, rest),
+        _ => (false, args.as_str()),
+    };
+    let reply = ctx.msg.reply_to_message();
+    let parsed = when::parse(args, tz, now).map(|(at, text)| (at, text.trim()));
+    let Some((at, text)) = parsed.filter(|(_, text)| !text.is_empty() || reply.is_some()) else {
+        ctx.reply(tr!(i18n, locale, "remindme-usage", timezone = tz.name()))
+            .await?;
+        return Ok(());
+    };
+    if at <= now {
+        ctx.reply(tr!(i18n, locale, "schedule-past")).await?;
+        return Ok(());
+    }
+    let pending = ctx.repo.get_jobs(Kind::Reminder, None, Some(by)).await?;
+    if pending.len() >= ctx.config.scheduler.max_reminders {
+        let limit = ctx.config.scheduler.max_reminders as i64;
+        ctx.reply(tr!(i18n, locale, "remindme-limit", count: limit)).await?;
+        return Ok(());
+    }
+    let payload = Reminder {
+        text: text.to_owned(),
+        reply_to: Some(reply.map_or(ctx.msg.id, |r| r.id).0),
+        dm,
+        locale: locale.code().to_owned(),
+    };
+    let job = ctx
+        .repo
+        .create_job(
+            by,
+            Kind::Reminder,
+            ctx.msg.chat.id.0,
+            serde_json::to_value(&payload).unwrap_or_default(),
+            at.fixed_offset(),
+            None,
+        )
+        .await?;
+    let key = if dm && !ctx.msg.chat.is_private() { "remindme-created-dm" } else { "remindme-created" };
+    let text = tr!(
+        i18n,
+        locale,
+        key,
+        id = job.id,
+        time = format_time(job.run_at, tz),
+        timezone = tz.name()
+    );
+    ctx.reply(text).await?;
+    Ok(())
+}
+
+/// `/reminders` lists the sender's pending reminders from every chat.
+pub async fn list(ctx: &Context<'_>) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let reminders = ctx.repo.get_jobs(Kind::Reminder, None, Some(by)).await?;
+    if reminders.is_empty() {
+        ctx.reply(tr!(i18n, locale, "reminders-empty")).await?;
+        return Ok(());
+    }
+    let tz = ctx.config.scheduler.timezone;
+    let mut lines = vec![tr!(
+        i18n,
+        locale,
+        "reminders-list",
+        count: reminders.len() as i64,
+        timezone = tz.name()
+    )];
+    for job in &reminders {
+        let reminder = job.reminder();
+        let mut line = format!("<b>#{}</b> {}", job.id, format_time(job.run_at, tz));
+        if reminder.dm {
+            line.push_str(" ✉️");
+        }
+        if !reminder.text.is_empty() {
+            line.push('\n');
+            line.push_str(&preview(&reminder.text));
+        }
+        lines.push(line);
+    }
+    ctx.reply_html(lines.join("\n\n")).await?;
+    Ok(())
+}
+
+/// `/unremind <id>` cancels one of the sender's reminders.
+pub async fn cancel(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let Ok(id) = args.trim().trim_start_matches('#').parse::<i64>() else {
+        ctx.reply(tr!(i18n, locale, "unremind-usage")).await?;
+        return Ok(());
+    };
+    ctx.repo.delete_job(by, Kind::Reminder, id, None).await?;
+    ctx.reply(tr!(i18n, locale, "unremind-done", id = id)).await?;
+    Ok(())
+}
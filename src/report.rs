@@ -0,0 +1,306 @@
+use crate::command::Context;
+use crate::config::Config;
+use crate::error::reply::alert;
+use crate::error::{Error, HandlerError};
+use crate::i18n::{I18n, Locale};
+use crate::models::prelude::{ReportModel, UserModel};
+use crate::models::reports::Status;
+use crate::models::users::Role;
+use crate::moderation::{ban_in_chat, mute_in_chat};
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::register::register;
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use crate::target::reply_target;
+use crate::tr;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode};
+use teloxide::utils::html;
+
+const CALLBACK: &str = "report:";
+
+/// What a moderator can do about a report, one button each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    Warn,
+    Mute,
+    Ban,
+    Delete,
+    Dismiss,
+}
+
+impl Resolution {
+    const ALL: [Resolution; 5] = [
+        Resolution::Warn,
+        Resolution::Mute,
+        Resolution::Ban,
+        Resolution::Delete,
+        Resolution::Dismiss,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Resolution::Warn => "warn",
+            Resolution::Mute => "mute",
+            Resolution::Ban => "ban",
+            Resolution::Delete => "delete",
+            Resolution::Dismiss => "dismiss",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    fn status(self) -> Status {
+        match self {
+            Resolution::Warn => Status::Warned,
+            Resolution::Mute => Status::Muted,
+            Resolution::Ban => Status::Banned,
+            Resolution::Delete => Status::Deleted,
+            Resolution::Dismiss => Status::Dismissed,
+        }
+    }
+
+    fn permission(self) -> Permission {
+        match self {
+            Resolution::Mute => Permission::Mute,
+            Resolution::Ban => Permission::Ban,
+            Resolution::Warn | Resolution::Delete | Resolution::Dismiss => Permission::Warn,
+        }
+    }
+}
+
+/// `/report [reason]` in reply to a message sends it to the moderators.
+///
+/// Reports go to the configured moderator chat, or privately to every moderator and creator.
+pub async fn command(ctx: &Context<'_>, reason: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (Some(reported), Some(target)) = (
+        ctx.msg.reply_to_message(),
+        reply_target(ctx.repo, ctx.roles, ctx.msg).await?,
+    ) else {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "report-usage")).await?;
+        return Ok(());
+    };
+    if target.id == by {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "report-self")).await?;
+        return Ok(());
+    }
+    if let Some(last) = ctx.repo.get_last_report(by).await? {
+        let elapsed = chrono::Utc::now().signed_duration_since(last.created_at);
+        let wait = ctx.config.reports.cooldown as i64 - elapsed.num_seconds();
+        if wait > 0 {
+            ctx.reply(tr!(ctx.i18n, ctx.locale, "report-cooldown", count: wait))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let reason = Some(reason.trim().to_owned()).filter(|r| !r.is_empty());
+    let report = ctx
+        .repo
+        .create_report(ctx.msg.chat.id.0, reported.id.0, by, target.id, reason)
+        .await?;
+    let reporter = ctx.repo.get_user(by).await?;
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let mut lines = vec![
+        tr!(
+            i18n,
+            locale,
+            "report-new",
+            id = report.id,
+            chat = html::escape(ctx.msg.chat.title().unwrap_or_default())
+        ),
+        tr!(i18n, locale, "report-reporter", user = mention(&reporter)),
+        tr!(i18n, locale, "report-target", user = mention(&target)),
+    ];
+    if let Some(reason) = &report.reason {
+        lines.push(tr!(i18n, locale, "moderation-reason", reason = html::escape(reason)));
+    }
+    if let Some(url) = reported.url() {
+        lines.push(tr!(i18n, locale, "report-link", url = url));
+    }
+    let text = lines.join("\n");
+    let keyboard = keyboard(i18n, locale, &report);
+
+    let recipients = match ctx.config.reports.mod_chat {
+        Some(chat) => vec![ChatId(chat)],
+        None => {
+            let mut moderators = ctx.repo.get_users_by_role(Role::Moderator).await?;
+            moderators.extend(ctx.repo.get_users_by_role(Role::Creator).await?);
+            let me = ctx.bot.get_me().await?.id.0 as i64;
+            moderators
+                .iter()
+                .filter(|u| !u.is_channel() && u.id != me)
+                .map(|u| ChatId(u.id))
+                .collect()
+        }
+    };
+    let mut delivered = false;
+    for chat in recipients {
+        let sent = ctx
+            .bot
+            .send_message(chat, &text)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard.clone())
+            .await;
+        match sent {
+            Ok(_) => delivered = true,
+            // Moderators who never started the bot cannot be written to.
+            Err(e) => tracing::warn!("Failed to deliver report {} to {}: {}", report.id, chat, e),
+        }
+    }
+    let key = if delivered { "report-sent" } else { "report-undelivered" };
+    ctx.reply(tr!(i18n, locale, key)).await?;
+    Ok(())
+}
+
+fn keyboard(i18n: &I18n, locale: Locale, report: &ReportModel) -> InlineKeyboardMarkup {
+    let button = |r: Resolution| {
+        InlineKeyboardButton::callback(
+            tr!(i18n, locale, &format!("report-button-{}", r.name())),
+            format!("{CALLBACK}{}:{}", report.id, r.name()),
+        )
+    };
+    InlineKeyboardMarkup::new([
+        vec![
+            button(Resolution::Warn),
+            button(Resolution::Mute),
+            button(Resolution::Ban),
+        ],
+        vec![button(Resolution::Delete), button(Resolution::Dismiss)],
+    ])
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(CALLBACK))
+}
+
+/// Applies the resolution a moderator picked under a report notification.
+pub async fn callback(
+    bot: Bot,
+    q: CallbackQuery,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let locale = i18n.locale(None, q.from.language_code.as_deref());
+    let parsed = q
+        .data
+        .as_deref()
+        .and_then(|d| d.strip_prefix(CALLBACK))
+        .and_then(|d| d.split_once(':'))
+        .and_then(|(id, r)| Some((id.parse::<i64>().ok()?, Resolution::from_name(r)?)));
+    let Some((id, resolution)) = parsed else {
+        return Ok(());
+    };
+    let result = async {
+        register(&repo, &roles, &q.from).await?;
+        let by = q.from.id.0 as i64;
+        let report = repo.get_report(id).await?;
+        let target = repo.get_user(report.target_id).await?;
+        let aimed = (resolution != Resolution::Dismiss).then_some(target.id);
+        repo.authorize(by, resolution.permission(), aimed, Some(report.chat_id)).await?;
+        // Claimed before acting, so that of two moderators pressing at once only one acts.
+        let report = match repo.resolve_report(id, by, resolution.status()).await {
+            Ok(report) => report,
+            Err(RepoError::NotModified) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if let Err(e) = resolve(&bot, &repo, &config, by, &report, &target, resolution).await {
+            if let Err(e) = repo.reopen_report(id).await {
+                tracing::error!("Failed to reopen report {}: {}", id, e);
+            }
+            return Err(e);
+        }
+        Ok::<_, Error>(Some(report))
+    }
+    .await;
+    let report = match result {
+        Ok(Some(report)) => report,
+        Ok(None) => {
+            bot.answer_callback_query(q.id.clone())
+                .text(tr!(i18n, locale, "report-already-resolved"))
+                .show_alert(true)
+                .await
+                .ok();
+            return Ok(());
+        }
+        Err(err) => {
+            alert(&bot, &i18n, locale, &q, err).await;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(q.id.clone()).await.ok();
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        return Ok(());
+    };
+    let moderator = match repo.get_user(q.from.id.0 as i64).await {
+        Ok(user) => mention(&user),
+        Err(_) => html::escape(&q.from.full_name()),
+    };
+    let text = tr!(
+        i18n,
+        locale,
+        "report-resolved",
+        id = report.id,
+        user = moderator,
+        resolution = tr!(i18n, locale, &format!("report-status-{}", resolution.name()))
+    );
+    bot.edit_message_reply_markup(message.chat.id, message.id).await.ok();
+    if let Err(e) = bot
+        .send_message(message.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .reply_to(message.id)
+        .await
+    {
+        tracing::warn!("Failed to announce resolution of report {}: {}", report.id, e);
+    }
+    Ok(())
+}
+
+/// Applies `resolution` in the bot and in the reported chat.
+async fn resolve(
+    bot: &Bot,
+    repo: &Repo,
+    config: &Config,
+    by: i64,
+    report: &ReportModel,
+    target: &UserModel,
+    resolution: Resolution,
+) -> Result<(), Error> {
+    let chat = ChatId(report.chat_id);
+    match resolution {
+        Resolution::Warn => {
+            if repo.warn(by, target.id, report.chat_id).await? {
+                ban_in_chat(bot, chat, target).await?;
+            }
+        }
+        Resolution::Mute => {
+            let until = chrono::Utc::now()
+                + chrono::Duration::seconds(config.reports.mute_duration as i64);
+            repo.mute_user(by, target.id, report.chat_id, until.fixed_offset())
+                .await?;
+            mute_in_chat(bot, chat, target, until).await?;
+        }
+        Resolution::Ban => {
+            repo.block_user(by, target.id).await?;
+            ban_in_chat(bot, chat, target).await?;
+        }
+        Resolution::Delete => {
+            repo.delete_messages(by, Some(target.id), report.chat_id, vec![report.message_id], None)
+                .await?;
+            bot.delete_message(chat, MessageId(report.message_id)).await?;
+        }
+        Resolution::Dismiss => {}
+    }
+    Ok(())
+}
@@ -1,8 +1,18 @@
 use crate::models::actions::Type;
+use crate::models::chat_settings::{Change, Module};
+use crate::models::filters;
+use crate::models::jobs;
+use crate::models::reports::Status;
 use crate::models::prelude::*;
-use sea_orm::prelude::Json;
+use crate::permission::Permission;
+use sea_orm::prelude::{DateTimeWithTimeZone, Json};
+use std::collections::{HashMap, HashSet};
+pub mod cache;
 pub mod db;
 
+/// The repository the bot runs with.
+pub type Repo = cache::CachedRepository<db::Repository>;
+
 pub trait RepositoryTrait {
     type Error;
     type Options;
@@ -17,22 +27,205 @@ pub trait RepositoryTrait {
     async fn change_nickname(&self, by: i64, id: i64, nickname: String) -> Result<(), Self::Error>;
     async fn block_user(&self, by: i64, user: i64) -> Result<(), Self::Error>;
     async fn unblock_user(&self, by: i64, user: i64) -> Result<(), Self::Error>;
+    // No command promotes or demotes by hand yet.
+    #[allow(dead_code)]
     async fn promote_user(&self, by: i64, user: i64) -> Result<(), Self::Error>;
+    #[allow(dead_code)]
     async fn demote_user(&self, by: i64, user: i64) -> Result<(), Self::Error>;
+    async fn transfer_ownership(&self, from: i64, to: i64) -> Result<(), Self::Error>;
+    /// Applies changes of the configured creator list since the last call. An empty list
+    /// changes nothing.
+    ///
+    /// Returns every user whose role changed together with the new role.
+    async fn reconcile_creators(&self, creators: Vec<i64>) -> Result<Vec<(i64, Role)>, Self::Error>;
+    /// Sets the role of `user` on behalf of the bot itself, logging `source` as who changed it.
+    ///
+    /// Returns the previous role if it changed.
+    async fn assign_system_role(
+        &self,
+        user: i64,
+        role: Role,
+        source: &'static str,
+    ) -> Result<Option<Role>, Self::Error>;
+    /// Replaces the roles Telegram gives in `chat`, logging every change as made by
+    /// `telegram_sync`.
+    ///
+    /// These roles only count in `chat` and never change the own role of anyone. Returns every
+    /// user whose role there changed, with the previous and the new role.
+    async fn set_chat_roles(
+        &self,
+        chat: i64,
+        roles: HashMap<i64, Role>,
+    ) -> Result<Vec<(i64, Option<Role>, Option<Role>)>, Self::Error>;
+    async fn get_users_by_role(&self, role: Role) -> Result<Vec<UserModel>, Self::Error>;
+    async fn get_state(&self, key: String) -> Result<Option<Json>, Self::Error>;
+    async fn set_state(&self, key: String, value: Json) -> Result<(), Self::Error>;
+    /// Adds the given number of messages to the count of each user.
+    async fn count_messages(&self, counts: Vec<(i64, i64)>) -> Result<(), Self::Error>;
     async fn get_user(&self, user: i64) -> Result<UserModel, Self::Error>;
     async fn get_user_by_username(&self, username: String) -> Result<UserModel, Self::Error>;
 
-    async fn warn(&self, by: i64, user: i64) -> Result<bool, Self::Error>;
-    async fn un_warn(&self, by: i64, user: i64) -> Result<(), Self::Error>;
+    /// Returns `actor` if it holds `permission` and outranks `target`.
+    ///
+    /// With a `chat`, both act with the roles Telegram gives them there as well; global actions
+    /// pass none.
+    async fn authorize(
+        &self,
+        actor: i64,
+        permission: Permission,
+        target: Option<i64>,
+        chat: Option<i64>,
+    ) -> Result<UserModel, Self::Error>;
+    async fn get_permissions(
+        &self,
+        user: i64,
+        chat: Option<i64>,
+    ) -> Result<HashSet<Permission>, Self::Error>;
+    async fn create_role(
+        &self,
+        by: i64,
+        name: String,
+        permissions: Vec<Permission>,
+    ) -> Result<(), Self::Error>;
+    async fn delete_role(&self, by: i64, name: String) -> Result<(), Self::Error>;
+    async fn get_roles(&self) -> Result<Vec<CustomRoleModel>, Self::Error>;
+    async fn assign_role(&self, by: i64, user: i64, role: Option<String>) -> Result<(), Self::Error>;
+    /// Grants (`Some(true)`), revokes (`Some(false)`) or resets (`None`) one permission of `user`.
+    async fn set_permission(
+        &self,
+        by: i64,
+        user: i64,
+        permission: Permission,
+        allowed: Option<bool>,
+    ) -> Result<(), Self::Error>;
+
+    async fn warn(&self, by: i64, user: i64, chat: i64) -> Result<bool, Self::Error>;
+    async fn un_warn(&self, by: i64, user: i64, chat: i64) -> Result<(), Self::Error>;
+    /// Logs that `user` is muted in `chat` until `until`; restricting them is up to the caller.
+    async fn mute_user(
+        &self,
+        by: i64,
+        user: i64,
+        chat: i64,
+        until: DateTimeWithTimeZone,
+    ) -> Result<(), Self::Error>;
 
+    /// Logs the deletion of `messages` in `chat`, sent by `user` or, for a purge, by anyone.
+    async fn delete_messages(
+        &self,
+        by: i64,
+        user: Option<i64>,
+        chat: i64,
+        messages: Vec<i32>,
+        text: Option<String>,
+    ) -> Result<(), Self::Error>;
+
+    async fn add_filter(
+        &self,
+        by: i64,
+        chat: i64,
+        kind: filters::Kind,
+        pattern: String,
+        action: filters::Action,
+        reply: Option<String>,
+    ) -> Result<FilterModel, Self::Error>;
+    async fn delete_filter(&self, by: i64, chat: i64, pattern: String) -> Result<(), Self::Error>;
+    async fn get_filters(&self, chat: i64) -> Result<Vec<FilterModel>, Self::Error>;
+    /// Logs that a message of `user` matched `filter`; the punishment logs itself.
+    async fn filter_hit(
+        &self,
+        by: i64,
+        user: i64,
+        filter: &FilterModel,
+        text: String,
+    ) -> Result<(), Self::Error>;
+
+    /// Logs whether `user` solved the join captcha of `chat`.
+    async fn captcha_result(&self, user: i64, chat: i64, passed: bool) -> Result<(), Self::Error>;
+    /// Logs the start or end of the night in `chat`, switched by `by`.
+    async fn night_mode(&self, by: i64, chat: i64, started: bool) -> Result<(), Self::Error>;
+
+    async fn global_ban(&self, by: i64, user: i64, reason: Option<String>) -> Result<(), Self::Error>;
+    async fn global_unban(&self, by: i64, user: i64) -> Result<(), Self::Error>;
+    async fn get_global_ban(&self, user: i64) -> Result<Option<GlobalBanModel>, Self::Error>;
+    async fn get_global_bans(&self) -> Result<Vec<GlobalBanModel>, Self::Error>;
+    /// Adds the bans not on the list yet, skipping moderators and creators, and returns how
+    /// many were added.
+    async fn import_global_bans(
+        &self,
+        by: i64,
+        bans: Vec<(i64, Option<String>)>,
+    ) -> Result<u64, Self::Error>;
+
+    async fn create_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        chat: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+        cron: Option<String>,
+    ) -> Result<JobModel, Self::Error>;
+    /// Adds a one-off job the bot runs on its own account, e.g. the expiry of a captcha, which
+    /// needs no permission and is not logged.
+    async fn schedule_job(
+        &self,
+        kind: jobs::Kind,
+        chat: i64,
+        created_by: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+    ) -> Result<JobModel, Self::Error>;
+    /// Deletes a job of `kind`, of `chat` if given; others' jobs need the permission of the kind,
+    /// personal ones are not found.
+    async fn delete_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        id: i64,
+        chat: Option<i64>,
+    ) -> Result<JobModel, Self::Error>;
+    async fn get_jobs(
+        &self,
+        kind: jobs::Kind,
+        chat: Option<i64>,
+        created_by: Option<i64>,
+    ) -> Result<Vec<JobModel>, Self::Error>;
+    async fn get_due_jobs(&self, now: DateTimeWithTimeZone) -> Result<Vec<JobModel>, Self::Error>;
+    async fn get_next_job_at(&self) -> Result<Option<DateTimeWithTimeZone>, Self::Error>;
+    async fn reschedule_job(&self, id: i64, run_at: DateTimeWithTimeZone) -> Result<(), Self::Error>;
+    /// Deletes a job that ran or is about to, returning whether it was still there, so that only
+    /// one of those racing for it acts on it.
+    async fn finish_job(&self, id: i64) -> Result<bool, Self::Error>;
+
+    async fn create_report(
+        &self,
+        chat: i64,
+        message: i32,
+        reporter: i64,
+        target: i64,
+        reason: Option<String>,
+    ) -> Result<ReportModel, Self::Error>;
+    async fn get_report(&self, id: i64) -> Result<ReportModel, Self::Error>;
+    async fn get_last_report(&self, reporter: i64) -> Result<Option<ReportModel>, Self::Error>;
+    /// Closes an open report with `status`, remembering who resolved it.
+    async fn resolve_report(&self, id: i64, by: i64, status: Status) -> Result<ReportModel, Self::Error>;
+    /// Opens a report again after the resolution it was closed with failed.
+    async fn reopen_report(&self, id: i64) -> Result<(), Self::Error>;
+
+    // Custom commands have no handler yet; their storage is kept for when they get one.
+    #[allow(dead_code)]
     async fn create_command(
         &self,
         name: String,
         action: String,
         creator: i64,
     ) -> Result<(), Self::Error>;
+    #[allow(dead_code)]
     async fn update_command(&self, id: String, by: i64, action: String) -> Result<(), Self::Error>;
+    #[allow(dead_code)]
     async fn delete_command(&self, id: String, by: i64) -> Result<(), Self::Error>;
+    #[allow(dead_code)]
     async fn get_command(&self, id: String) -> Result<CommandModel, Self::Error>;
     async fn get_user_commands(
         &self,
@@ -40,11 +233,13 @@ pub trait RepositoryTrait {
         page: u64,
         page_size: u64,
     ) -> Result<Vec<CommandModel>, Self::Error>;
+    #[allow(dead_code)]
     async fn get_commands(
         &self,
         page: u64,
         page_size: u64,
     ) -> Result<Vec<CommandModel>, Self::Error>;
+    #[allow(dead_code)]
     async fn use_command(&self, id: String, by: i64) -> Result<(), Self::Error>;
 
     async fn new_action(
@@ -53,6 +248,8 @@ pub trait RepositoryTrait {
         action_type: Type,
         description: Json,
     ) -> Result<i64, Self::Error>;
+    // Nothing reads single actions or the log of everyone yet.
+    #[allow(dead_code)]
     async fn get_action(&self, id: i64) -> Result<ActionModel, Self::Error>;
     async fn get_user_actions(
         &self,
@@ -60,6 +257,17 @@ pub trait RepositoryTrait {
         page: u64,
         page_size: u64,
     ) -> Result<Vec<ActionModel>, Self::Error>;
+    #[allow(dead_code)]
     async fn get_actions(&self, page: u64, page_size: u64)
         -> Result<Vec<ActionModel>, Self::Error>;
+
+    async fn get_chat_settings(&self, chat: i64) -> Result<ChatSettingsModel, Self::Error>;
+    /// Settings of the chats that have `module` enabled, among those that ever changed a setting.
+    async fn get_chats_with_module(&self, module: Module) -> Result<Vec<ChatSettingsModel>, Self::Error>;
+    async fn update_chat_settings(
+        &self,
+        by: i64,
+        chat: i64,
+        change: Change,
+    ) -> Result<ChatSettingsModel, Self::Error>;
 }
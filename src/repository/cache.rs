@@ -0,0 +1,805 @@
+use super::RepositoryTrait;
+use crate::models::actions::Type;
+use crate::models::chat_settings::{Change, Module};
+use crate::models::filters;
+use crate::models::jobs;
+use crate::models::reports::Status;
+use crate::models::prelude::*;
+use crate::permission::Permission;
+use sea_orm::prelude::{DateTimeWithTimeZone, Json};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Caching decorator around any [`RepositoryTrait`] implementation.
+///
+/// Keeps the hot read paths (`get_user`, `get_permissions`, `get_command`, `get_chat_settings`,
+/// `get_filters`) in an in-process LRU with TTL. Every write method invalidates the entries it
+/// can touch once the write is done, and a value read before an invalidation is never cached
+/// after it.
+pub struct CachedRepository<R> {
+    inner: R,
+    users: Lru<i64, UserModel>,
+    /// Keyed by user and chat, see [`RepositoryTrait::get_permissions`].
+    permissions: Lru<(i64, Option<i64>), HashSet<Permission>>,
+    commands: Lru<String, CommandModel>,
+    chats: Lru<i64, ChatSettingsModel>,
+    filters: Lru<i64, Vec<FilterModel>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<R> CachedRepository<R> {
+    const DEFAULT_CAPACITY: usize = 1024;
+    const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drops everything cached about `user`.
+    fn forget_user(&self, user: i64) {
+        self.users.remove(&user);
+        self.permissions.remove_where(|&(id, _)| id == user);
+    }
+
+    /// Drops everything cached about every user.
+    fn forget_users(&self) {
+        self.users.clear();
+        self.permissions.clear();
+    }
+
+    fn hit<V>(&self, value: Option<V>) -> Option<V> {
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        value
+    }
+}
+
+pub struct CacheOptions<O> {
+    pub inner: O,
+    pub capacity: Option<usize>,
+    pub ttl: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Share of lookups served from the cache, in percent.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+/// Logs the hit and miss counters of `repo` every `every`, for as long as the bot runs.
+pub async fn log_stats<R>(repo: Arc<CachedRepository<R>>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let stats = repo.stats();
+        tracing::info!(
+            "Repository cache: {} hits, {} misses, {:.1}% hit rate",
+            stats.hits,
+            stats.misses,
+            stats.hit_rate()
+        );
+    }
+}
+
+impl<R> RepositoryTrait for CachedRepository<R>
+where
+    R: RepositoryTrait,
+{
+    type Error = R::Error;
+    type Options = CacheOptions<R::Options>;
+
+    fn new(options: Self::Options) -> Self {
+        let capacity = options.capacity.unwrap_or(Self::DEFAULT_CAPACITY);
+        let ttl = options.ttl.unwrap_or(Self::DEFAULT_TTL);
+        Self {
+            inner: R::new(options.inner),
+            users: Lru::new(capacity, ttl),
+            permissions: Lru::new(capacity, ttl),
+            commands: Lru::new(capacity, ttl),
+            chats: Lru::new(capacity, ttl),
+            filters: Lru::new(capacity, ttl),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    async fn new_user(
+        &self,
+        id: i64,
+        role: Role,
+        username: Option<String>,
+        nickname: String,
+    ) -> Result<(), Self::Error> {
+        let res = self.inner.new_user(id, role, username, nickname).await;
+        self.forget_user(id);
+        res
+    }
+
+    async fn change_nickname(&self, by: i64, id: i64, nickname: String) -> Result<(), Self::Error> {
+        let res = self.inner.change_nickname(by, id, nickname).await;
+        self.forget_user(id);
+        res
+    }
+
+    async fn block_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let res = self.inner.block_user(by, user).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn unblock_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let res = self.inner.unblock_user(by, user).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn promote_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let res = self.inner.promote_user(by, user).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn demote_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let res = self.inner.demote_user(by, user).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn transfer_ownership(&self, from: i64, to: i64) -> Result<(), Self::Error> {
+        let res = self.inner.transfer_ownership(from, to).await;
+        self.forget_user(from);
+        self.forget_user(to);
+        res
+    }
+
+    async fn reconcile_creators(&self, creators: Vec<i64>) -> Result<Vec<(i64, Role)>, Self::Error> {
+        let res = self.inner.reconcile_creators(creators).await;
+        self.forget_users();
+        res
+    }
+
+    async fn assign_system_role(
+        &self,
+        user: i64,
+        role: Role,
+        source: &'static str,
+    ) -> Result<Option<Role>, Self::Error> {
+        let res = self.inner.assign_system_role(user, role, source).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn set_chat_roles(
+        &self,
+        chat: i64,
+        roles: HashMap<i64, Role>,
+    ) -> Result<Vec<(i64, Option<Role>, Option<Role>)>, Self::Error> {
+        let res = self.inner.set_chat_roles(chat, roles).await;
+        self.permissions.remove_where(|&(_, cached)| cached == Some(chat));
+        res
+    }
+
+    async fn get_users_by_role(&self, role: Role) -> Result<Vec<UserModel>, Self::Error> {
+        self.inner.get_users_by_role(role).await
+    }
+
+    async fn get_state(&self, key: String) -> Result<Option<Json>, Self::Error> {
+        self.inner.get_state(key).await
+    }
+
+    async fn set_state(&self, key: String, value: Json) -> Result<(), Self::Error> {
+        self.inner.set_state(key, value).await
+    }
+
+    async fn count_messages(&self, counts: Vec<(i64, i64)>) -> Result<(), Self::Error> {
+        self.inner.count_messages(counts.clone()).await?;
+        for (user, count) in counts {
+            self.users.update(&user, |model| model.messages += count);
+        }
+        Ok(())
+    }
+
+    async fn get_user(&self, user: i64) -> Result<UserModel, Self::Error> {
+        if let Some(model) = self.hit(self.users.get(&user)) {
+            return Ok(model);
+        }
+        let generation = self.users.generation();
+        let model = self.inner.get_user(user).await?;
+        self.users.insert(user, model.clone(), generation);
+        Ok(model)
+    }
+
+    async fn get_user_by_username(&self, username: String) -> Result<UserModel, Self::Error> {
+        let generation = self.users.generation();
+        let model = self.inner.get_user_by_username(username).await?;
+        self.users.insert(model.id, model.clone(), generation);
+        Ok(model)
+    }
+
+    async fn authorize(
+        &self,
+        actor: i64,
+        permission: Permission,
+        target: Option<i64>,
+        chat: Option<i64>,
+    ) -> Result<UserModel, Self::Error> {
+        self.inner.authorize(actor, permission, target, chat).await
+    }
+
+    async fn get_permissions(
+        &self,
+        user: i64,
+        chat: Option<i64>,
+    ) -> Result<HashSet<Permission>, Self::Error> {
+        if let Some(permissions) = self.hit(self.permissions.get(&(user, chat))) {
+            return Ok(permissions);
+        }
+        let generation = self.permissions.generation();
+        let permissions = self.inner.get_permissions(user, chat).await?;
+        self.permissions.insert((user, chat), permissions.clone(), generation);
+        Ok(permissions)
+    }
+
+    async fn create_role(
+        &self,
+        by: i64,
+        name: String,
+        permissions: Vec<Permission>,
+    ) -> Result<(), Self::Error> {
+        self.inner.create_role(by, name, permissions).await
+    }
+
+    async fn delete_role(&self, by: i64, name: String) -> Result<(), Self::Error> {
+        let res = self.inner.delete_role(by, name).await;
+        self.forget_users();
+        res
+    }
+
+    async fn get_roles(&self) -> Result<Vec<CustomRoleModel>, Self::Error> {
+        self.inner.get_roles().await
+    }
+
+    async fn assign_role(&self, by: i64, user: i64, role: Option<String>) -> Result<(), Self::Error> {
+        let res = self.inner.assign_role(by, user, role).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn set_permission(
+        &self,
+        by: i64,
+        user: i64,
+        permission: Permission,
+        allowed: Option<bool>,
+    ) -> Result<(), Self::Error> {
+        let res = self.inner.set_permission(by, user, permission, allowed).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn warn(&self, by: i64, user: i64, chat: i64) -> Result<bool, Self::Error> {
+        let res = self.inner.warn(by, user, chat).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn un_warn(&self, by: i64, user: i64, chat: i64) -> Result<(), Self::Error> {
+        let res = self.inner.un_warn(by, user, chat).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn mute_user(
+        &self,
+        by: i64,
+        user: i64,
+        chat: i64,
+        until: DateTimeWithTimeZone,
+    ) -> Result<(), Self::Error> {
+        let res = self.inner.mute_user(by, user, chat, until).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn delete_messages(
+        &self,
+        by: i64,
+        user: Option<i64>,
+        chat: i64,
+        messages: Vec<i32>,
+        text: Option<String>,
+    ) -> Result<(), Self::Error> {
+        self.inner.delete_messages(by, user, chat, messages, text).await
+    }
+
+    async fn add_filter(
+        &self,
+        by: i64,
+        chat: i64,
+        kind: filters::Kind,
+        pattern: String,
+        action: filters::Action,
+        reply: Option<String>,
+    ) -> Result<FilterModel, Self::Error> {
+        let res = self
+            .inner
+            .add_filter(by, chat, kind, pattern, action, reply)
+            .await;
+        self.filters.remove(&chat);
+        res
+    }
+
+    async fn delete_filter(&self, by: i64, chat: i64, pattern: String) -> Result<(), Self::Error> {
+        let res = self.inner.delete_filter(by, chat, pattern).await;
+        self.filters.remove(&chat);
+        res
+    }
+
+    async fn get_filters(&self, chat: i64) -> Result<Vec<FilterModel>, Self::Error> {
+        if let Some(filters) = self.hit(self.filters.get(&chat)) {
+            return Ok(filters);
+        }
+        let generation = self.filters.generation();
+        let filters = self.inner.get_filters(chat).await?;
+        self.filters.insert(chat, filters.clone(), generation);
+        Ok(filters)
+    }
+
+    async fn filter_hit(
+        &self,
+        by: i64,
+        user: i64,
+        filter: &FilterModel,
+        text: String,
+    ) -> Result<(), Self::Error> {
+        self.inner.filter_hit(by, user, filter, text).await
+    }
+
+    async fn captcha_result(&self, user: i64, chat: i64, passed: bool) -> Result<(), Self::Error> {
+        self.inner.captcha_result(user, chat, passed).await
+    }
+
+    async fn night_mode(&self, by: i64, chat: i64, started: bool) -> Result<(), Self::Error> {
+        self.inner.night_mode(by, chat, started).await
+    }
+
+    async fn global_ban(&self, by: i64, user: i64, reason: Option<String>) -> Result<(), Self::Error> {
+        let res = self.inner.global_ban(by, user, reason).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn global_unban(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let res = self.inner.global_unban(by, user).await;
+        self.forget_user(user);
+        res
+    }
+
+    async fn get_global_ban(&self, user: i64) -> Result<Option<GlobalBanModel>, Self::Error> {
+        self.inner.get_global_ban(user).await
+    }
+
+    async fn get_global_bans(&self) -> Result<Vec<GlobalBanModel>, Self::Error> {
+        self.inner.get_global_bans().await
+    }
+
+    async fn import_global_bans(
+        &self,
+        by: i64,
+        bans: Vec<(i64, Option<String>)>,
+    ) -> Result<u64, Self::Error> {
+        let res = self.inner.import_global_bans(by, bans).await;
+        self.forget_users();
+        res
+    }
+
+    async fn create_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        chat: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+        cron: Option<String>,
+    ) -> Result<JobModel, Self::Error> {
+        self.inner.create_job(by, kind, chat, payload, run_at, cron).await
+    }
+
+    async fn schedule_job(
+        &self,
+        kind: jobs::Kind,
+        chat: i64,
+        created_by: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+    ) -> Result<JobModel, Self::Error> {
+        self.inner.schedule_job(kind, chat, created_by, payload, run_at).await
+    }
+
+    async fn delete_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        id: i64,
+        chat: Option<i64>,
+    ) -> Result<JobModel, Self::Error> {
+        self.inner.delete_job(by, kind, id, chat).await
+    }
+
+    async fn get_jobs(
+        &self,
+        kind: jobs::Kind,
+        chat: Option<i64>,
+        created_by: Option<i64>,
+    ) -> Result<Vec<JobModel>, Self::Error> {
+        self.inner.get_jobs(kind, chat, created_by).await
+    }
+
+    async fn get_due_jobs(&self, now: DateTimeWithTimeZone) -> Result<Vec<JobModel>, Self::Error> {
+        self.inner.get_due_jobs(now).await
+    }
+
+    async fn get_next_job_at(&self) -> Result<Option<DateTimeWithTimeZone>, Self::Error> {
+        self.inner.get_next_job_at().await
+    }
+
+    async fn reschedule_job(&self, id: i64, run_at: DateTimeWithTimeZone) -> Result<(), Self::Error> {
+        self.inner.reschedule_job(id, run_at).await
+    }
+
+    async fn finish_job(&self, id: i64) -> Result<bool, Self::Error> {
+        self.inner.finish_job(id).await
+    }
+
+    async fn create_report(
+        &self,
+        chat: i64,
+        message: i32,
+        reporter: i64,
+        target: i64,
+        reason: Option<String>,
+    ) -> Result<ReportModel, Self::Error> {
+        self.inner
+            .create_report(chat, message, reporter, target, reason)
+            .await
+    }
+
+    async fn get_report(&self, id: i64) -> Result<ReportModel, Self::Error> {
+        self.inner.get_report(id).await
+    }
+
+    async fn get_last_report(&self, reporter: i64) -> Result<Option<ReportModel>, Self::Error> {
+        self.inner.get_last_report(reporter).await
+    }
+
+    async fn resolve_report(&self, id: i64, by: i64, status: Status) -> Result<ReportModel, Self::Error> {
+        self.inner.resolve_report(id, by, status).await
+    }
+
+    async fn reopen_report(&self, id: i64) -> Result<(), Self::Error> {
+        self.inner.reopen_report(id).await
+    }
+
+    async fn create_command(
+        &self,
+        name: String,
+        action: String,
+        creator: i64,
+    ) -> Result<(), Self::Error> {
+        let res = self.inner.create_command(name.clone(), action, creator).await;
+        self.commands.remove(&name);
+        res
+    }
+
+    async fn update_command(&self, id: String, by: i64, action: String) -> Result<(), Self::Error> {
+        let res = self.inner.update_command(id.clone(), by, action).await;
+        self.commands.remove(&id);
+        res
+    }
+
+    async fn delete_command(&self, id: String, by: i64) -> Result<(), Self::Error> {
+        let res = self.inner.delete_command(id.clone(), by).await;
+        self.commands.remove(&id);
+        res
+    }
+
+    async fn get_command(&self, id: String) -> Result<CommandModel, Self::Error> {
+        if let Some(model) = self.hit(self.commands.get(&id)) {
+            return Ok(model);
+        }
+        let generation = self.commands.generation();
+        let model = self.inner.get_command(id.clone()).await?;
+        self.commands.insert(id, model.clone(), generation);
+        Ok(model)
+    }
+
+    async fn get_user_commands(
+        &self,
+        user: i64,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<CommandModel>, Self::Error> {
+        self.inner.get_user_commands(user, page, page_size).await
+    }
+
+    async fn get_commands(
+        &self,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<CommandModel>, Self::Error> {
+        self.inner.get_commands(page, page_size).await
+    }
+
+    async fn use_command(&self, id: String, by: i64) -> Result<(), Self::Error> {
+        self.inner.use_command(id.clone(), by).await?;
+        self.commands.update(&id, |model| model.times_used += 1);
+        Ok(())
+    }
+
+    async fn new_action(
+        &self,
+        user_id: i64,
+        action_type: Type,
+        description: Json,
+    ) -> Result<i64, Self::Error> {
+        self.inner.new_action(user_id, action_type, description).await
+    }
+
+    async fn get_action(&self, id: i64) -> Result<ActionModel, Self::Error> {
+        self.inner.get_action(id).await
+    }
+
+    async fn get_user_actions(
+        &self,
+        user: i64,
+        page: u64,
+        page_size: u64,
+    ) -> Result<Vec<ActionModel>, Self::Error> {
+        self.inner.get_user_actions(user, page, page_size).await
+    }
+
+    async fn get_actions(&self, page: u64, page_size: u64) -> Result<Vec<ActionModel>, Self::Error> {
+        self.inner.get_actions(page, page_size).await
+    }
+
+    async fn get_chat_settings(&self, chat: i64) -> Result<ChatSettingsModel, Self::Error> {
+        if let Some(model) = self.hit(self.chats.get(&chat)) {
+            return Ok(model);
+        }
+        let generation = self.chats.generation();
+        let model = self.inner.get_chat_settings(chat).await?;
+        self.chats.insert(chat, model.clone(), generation);
+        Ok(model)
+    }
+
+    async fn get_chats_with_module(&self, module: Module) -> Result<Vec<ChatSettingsModel>, Self::Error> {
+        self.inner.get_chats_with_module(module).await
+    }
+
+    async fn update_chat_settings(
+        &self,
+        by: i64,
+        chat: i64,
+        change: Change,
+    ) -> Result<ChatSettingsModel, Self::Error> {
+        let res = self.inner.update_chat_settings(by, chat, change).await;
+        self.chats.remove(&chat);
+        res
+    }
+}
+
+/// Least-recently-used map with a per-entry time to live.
+struct Lru<K, V> {
+    state: Mutex<LruState<K, V>>,
+}
+
+struct LruState<K, V> {
+    entries: HashMap<K, LruEntry<V>>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    /// Bumped by every removal, see [`Lru::insert`].
+    generation: u64,
+    capacity: usize,
+    ttl: Duration,
+}
+
+struct LruEntry<V> {
+    value: V,
+    expires_at: Instant,
+    tick: u64,
+}
+
+impl<K, V> Lru<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            state: Mutex::new(LruState {
+                entries: HashMap::with_capacity(capacity),
+                order: BTreeMap::new(),
+                tick: 0,
+                generation: 0,
+                capacity,
+                ttl,
+            }),
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        let mut state = self.state.lock().unwrap();
+        let tick = state.next_tick();
+        let entry = state.entries.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            let old = entry.tick;
+            state.entries.remove(key);
+            state.order.remove(&old);
+            return None;
+        }
+        let old = std::mem::replace(&mut entry.tick, tick);
+        let value = entry.value.clone();
+        state.order.remove(&old);
+        state.order.insert(tick, key.clone());
+        Some(value)
+    }
+
+    /// Current [`Lru::insert`] generation, to be taken before reading the value to cache.
+    fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    /// Caches `value` unless anything was removed since `generation` was taken, in which case
+    /// the value may predate a write and is dropped.
+    fn insert(&self, key: K, value: V, generation: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.capacity == 0 || state.generation != generation {
+            return;
+        }
+        let tick = state.next_tick();
+        let expires_at = Instant::now() + state.ttl;
+        if let Some(old) = state.entries.insert(
+            key.clone(),
+            LruEntry {
+                value,
+                expires_at,
+                tick,
+            },
+        ) {
+            state.order.remove(&old.tick);
+        }
+        state.order.insert(tick, key);
+        while state.entries.len() > state.capacity {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
+
+    /// Changes a cached value in place, if there is one.
+    fn update(&self, key: &K, change: impl FnOnce(&mut V)) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.entries.get_mut(key) {
+            change(&mut entry.value);
+        }
+    }
+
+    fn remove(&self, key: &K) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        if let Some(entry) = state.entries.remove(key) {
+            state.order.remove(&entry.tick);
+        }
+    }
+
+    /// Removes every entry whose key `matches`.
+    fn remove_where(&self, matches: impl Fn(&K) -> bool) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        let LruState { entries, order, .. } = &mut *state;
+        entries.retain(|key, entry| {
+            let keep = !matches(key);
+            if !keep {
+                order.remove(&entry.tick);
+            }
+            keep
+        });
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.entries.clear();
+        state.order.clear();
+    }
+}
+
+impl<K, V> LruState<K, V> {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let lru = Lru::new(2, Duration::from_secs(60));
+        lru.insert(1, "a", lru.generation());
+        lru.insert(2, "b", lru.generation());
+        assert_eq!(lru.get(&1), Some("a"));
+        lru.insert(3, "c", lru.generation());
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some("a"));
+        assert_eq!(lru.get(&3), Some("c"));
+    }
+
+    #[test]
+    fn drops_values_read_before_a_removal() {
+        let lru = Lru::new(2, Duration::from_secs(60));
+        let generation = lru.generation();
+        lru.remove(&1);
+        lru.insert(1, "stale", generation);
+        assert_eq!(lru.get(&1), None);
+        lru.insert(1, "fresh", lru.generation());
+        assert_eq!(lru.get(&1), Some("fresh"));
+    }
+
+    #[test]
+    fn removes_matching_keys() {
+        let lru = Lru::new(4, Duration::from_secs(60));
+        let generation = lru.generation();
+        lru.insert((1, None), "a", generation);
+        lru.insert((1, Some(10)), "b", generation);
+        lru.insert((2, Some(10)), "c", generation);
+        lru.remove_where(|&(user, _)| user == 1);
+        assert_eq!(lru.get(&(1, None)), None);
+        assert_eq!(lru.get(&(1, Some(10))), None);
+        assert_eq!(lru.get(&(2, Some(10))), Some("c"));
+        lru.insert((3, None), "stale", generation);
+        assert_eq!(lru.get(&(3, None)), None);
+    }
+
+    #[test]
+    fn updates_values_in_place() {
+        let lru = Lru::new(2, Duration::from_secs(60));
+        let generation = lru.generation();
+        lru.insert(1, 1, generation);
+        lru.update(&1, |used| *used += 1);
+        lru.update(&2, |used| *used += 1);
+        assert_eq!(lru.get(&1), Some(2));
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.generation(), generation);
+    }
+
+    #[test]
+    fn counts_hit_rate() {
+        let stats = CacheStats { hits: 3, misses: 1 };
+        assert_eq!(stats.hit_rate(), 75.0);
+        assert_eq!(CacheStats::default().hit_rate(), 0.0);
+    }
+}
@@ -1,36 +1,228 @@
+use super::RepositoryTrait;
 use crate::models::actions::Type;
-use crate::models::{actions, commands, prelude::*, users};
+use crate::models::chat_settings::{Change, Module};
+use crate::models::filters;
+use crate::models::reports::{self, Status};
+use crate::models::{
+    actions, bot_state, chat_settings, commands, global_bans, jobs, permission_overrides,
+    prelude::*, users,
+};
+use crate::permission::{may_act_on, Permission, PermissionMap};
 use crate::{action, error, models, update};
-use sea_orm::prelude::Json;
+use sea_orm::prelude::{DateTimeWithTimeZone, Json};
 use sea_orm::{
-    sqlx, ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
-    PaginatorTrait, QueryFilter, QuerySelect, RuntimeErr,
+    sqlx, ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RuntimeErr, TransactionTrait,
 };
-use sea_orm_migration::sea_query::Expr;
+use std::collections::{HashMap, HashSet};
+use sea_orm_migration::sea_query::{Expr, Func, OnConflict};
 use serde_json::json;
+use std::fmt;
 
 pub struct Repository {
     db: DatabaseConnection,
     max_warns: i64,
+    permissions: PermissionMap,
 }
 
 impl Repository {
     const DEFAULT_MAX_WARNS: i64 = 5;
+    const CREATORS_STATE: &'static str = "creators";
+    /// Nickname blocked users carry until they are unblocked.
+    const BLOCKED_NICKNAME: &'static str = "_";
+
+    /// Renames `user`, keeping the previous nickname in the action log.
+    async fn set_nickname<C: ConnectionTrait>(
+        db: &C,
+        by: i64,
+        user: &UserModel,
+        nickname: String,
+    ) -> Result<(), RepoError> {
+        update!(UserEntity: user.id => {
+            Nickname: &nickname,
+        })
+        .exec(db)
+        .await?;
+        Action {
+            user_id: Set(user.id),
+            action_type: Set(Type::ChangeNickname),
+            description: Set(json!({
+                "by": by,
+                "old": user.nickname,
+                "new": nickname,
+            })),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Blocks `user` and hides their nickname, without checking `by` may do so.
+    async fn block<C: ConnectionTrait>(db: &C, by: i64, user: &UserModel) -> Result<(), RepoError> {
+        Self::set_role(db, user.id, Role::Blocked, Type::BlockUser, json!({"by": by})).await?;
+        Self::set_nickname(db, by, user, Self::BLOCKED_NICKNAME.to_owned()).await
+    }
+
+    async fn set_role<C: ConnectionTrait>(
+        db: &C,
+        user: i64,
+        role: Role,
+        action_type: Type,
+        description: Json,
+    ) -> Result<(), RepoError> {
+        update!(UserEntity: user => {
+            Role: role,
+        })
+        .exec(db)
+        .await?;
+        Action {
+            user_id: Set(user),
+            action_type: Set(action_type),
+            description: Set(description),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(())
+    }
+
+    async fn put_state<C: ConnectionTrait>(db: &C, key: &str, value: Json) -> Result<(), RepoError> {
+        BotStateEntity::insert(BotState {
+            key: Set(key.to_owned()),
+            value: Set(value),
+            updated_at: Set(chrono::Utc::now().fixed_offset()),
+        })
+        .on_conflict(
+            OnConflict::column(bot_state::Column::Key)
+                .update_columns([bot_state::Column::Value, bot_state::Column::UpdatedAt])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_user<C: ConnectionTrait>(db: &C, id: i64) -> Result<UserModel, RepoError> {
+        UserEntity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(RepoError::user_not_found(id))
+    }
+
+    fn chat_roles_key(chat: i64) -> String {
+        format!("telegram_admins:{chat}")
+    }
+
+    /// Roles Telegram gives in `chat`, as of its last sync.
+    async fn chat_roles<C: ConnectionTrait>(
+        db: &C,
+        chat: i64,
+    ) -> Result<HashMap<i64, Role>, RepoError> {
+        Ok(BotStateEntity::find_by_id(Self::chat_roles_key(chat))
+            .one(db)
+            .await?
+            .and_then(|state| serde_json::from_value(state.value).ok())
+            .unwrap_or_default())
+    }
+
+    /// Role `user` acts with in `chat`: the higher of its own role and the one Telegram gives it
+    /// there. Blocked users stay blocked, and without a chat only the own role counts.
+    async fn role_in<C: ConnectionTrait>(
+        db: &C,
+        user: &UserModel,
+        chat: Option<i64>,
+    ) -> Result<Role, RepoError> {
+        let Some(chat) = chat.filter(|_| user.role != Role::Blocked) else {
+            return Ok(user.role);
+        };
+        Ok(match Self::chat_roles(db, chat).await?.get(&user.id) {
+            Some(&role) if role > user.role => role,
+            _ => user.role,
+        })
+    }
+
+    /// Effective permissions of `user` in `chat`: its role there, custom role and personal
+    /// overrides.
+    async fn permissions_of<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        user: &UserModel,
+        chat: Option<i64>,
+    ) -> Result<HashSet<Permission>, RepoError> {
+        let role = Self::role_in(db, user, chat).await?;
+        let custom = match &user.custom_role {
+            Some(name) => CustomRoleEntity::find_by_id(name)
+                .one(db)
+                .await?
+                .map(|role| role.permissions())
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let overrides = PermissionOverrideEntity::find()
+            .filter(permission_overrides::Column::UserId.eq(user.id))
+            .all(db)
+            .await?;
+        Ok(self.permissions.resolve(
+            role,
+            &custom,
+            overrides.into_iter().map(|o| (o.permission, o.allowed)),
+        ))
+    }
+
+    /// Fails unless `actor` holds `permission` and, when acting on someone else, may act on
+    /// `target`, see [`may_act_on`]. Both act with their roles in `chat`, if the action is
+    /// bound to one.
+    async fn check<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        actor: &UserModel,
+        permission: Permission,
+        target: Option<&UserModel>,
+        chat: Option<i64>,
+    ) -> Result<(), RepoError> {
+        let granted = self.permissions_of(db, actor, chat).await?;
+        error!(!granted.contains(&permission) => RepoError::forbidden(actor, permission));
+        if let Some(target) = target.filter(|target| target.id != actor.id) {
+            let actor_role = Self::role_in(db, actor, chat).await?;
+            let target_role = Self::role_in(db, target, chat).await?;
+            error!(!may_act_on(actor_role, target_role) => RepoError::InvalidRole {
+                user: target.id,
+                actual: target_role,
+            });
+        }
+        Ok(())
+    }
+
+    /// Authors edit their own commands, everyone else needs to manage commands.
+    fn command_permission(user: &UserModel, command: &CommandModel) -> Permission {
+        if user.id == command.creator_id {
+            Permission::CreateCommands
+        } else {
+            Permission::ManageCommands
+        }
+    }
+
+    async fn load_pair(&self, by: i64, user: i64) -> Result<(UserModel, UserModel), RepoError> {
+        tokio::try_join!(Self::find_user(&self.db, by), Self::find_user(&self.db, user))
+    }
 }
 
 #[derive(Default)]
 pub struct RepositoryOptions {
     pub database: DatabaseConnection,
     pub max_warns: Option<i64>,
+    pub permissions: PermissionMap,
 }
 
-impl super::RepositoryTrait for Repository {
+impl RepositoryTrait for Repository {
     type Error = RepoError;
     type Options = RepositoryOptions;
     fn new(options: Self::Options) -> Self {
         Self {
             db: options.database,
             max_warns: options.max_warns.unwrap_or(Self::DEFAULT_MAX_WARNS),
+            permissions: options.permissions,
         }
     }
 
@@ -63,63 +255,51 @@ impl super::RepositoryTrait for Repository {
     }
 
     async fn change_nickname(&self, by: i64, id: i64, nickname: String) -> Result<(), Self::Error> {
+        let (by_user, target_user) = self.load_pair(by, id).await?;
         if by != id {
-            error!(UserEntity::find_by_id(by).one(&self.db).await?.ok_or(RepoError::NotFound)?.role < Role::Moderator => RepoError::Forbidden);
-        };
-        update!(UserEntity: id => {
-            Nickname: nickname,
-        })
-        .exec(&self.db)
-        .await?;
-        Ok(())
+            self.check(&self.db, &by_user, Permission::EditNicknames, Some(&target_user), None)
+                .await?;
+        }
+        error!(target_user.role == Role::Blocked => RepoError::invalid_role(&target_user));
+        Self::set_nickname(&self.db, by, &target_user, nickname).await
     }
 
     async fn block_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role < Role::Moderator => RepoError::Forbidden);
-        error!(target_user.role != Role::User => RepoError::InvalidRole);
-        update!(UserEntity: target_user.id => {
-            Role: Role::Blocked,
-            Nickname: "_".to_string(),
-        })
-        .exec(&self.db)
-        .await?;
-        action!(self; BlockUser@user => json!({"by":by}));
-        Ok(())
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::Ban, Some(&target_user), None).await?;
+        error!(target_user.role != Role::User => RepoError::invalid_role(&target_user));
+        Self::block(&self.db, by, &target_user).await
     }
 
     async fn unblock_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role < Role::Moderator => RepoError::Forbidden);
-        error!(target_user.role != Role::Moderator => RepoError::InvalidRole);
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::Ban, Some(&target_user), None).await?;
+        error!(target_user.role != Role::Blocked => RepoError::invalid_role(&target_user));
         update!(UserEntity: target_user.id => {
             Role: Role::User
         })
         .exec(&self.db)
         .await?;
         action!(self; UnblockUser@user => json!({"by":by}));
+        let last_change = ActionEntity::find()
+            .filter(actions::Column::UserId.eq(user))
+            .filter(actions::Column::ActionType.eq(Type::ChangeNickname))
+            .order_by_desc(actions::Column::Id)
+            .one(&self.db)
+            .await?;
+        let previous = last_change
+            .filter(|action| action.description["new"] == Self::BLOCKED_NICKNAME)
+            .and_then(|action| action.description["old"].as_str().map(str::to_owned));
+        if let Some(nickname) = previous {
+            Self::set_nickname(&self.db, by, &target_user, nickname).await?;
+        }
         Ok(())
     }
 
     async fn promote_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role != Role::Creator => RepoError::Forbidden);
-        error!(target_user.role != Role::User => RepoError::InvalidRole);
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, Some(&target_user), None).await?;
+        error!(target_user.role != Role::User => RepoError::invalid_role(&target_user));
         update!(UserEntity: target_user.id => {
             Role: Role::Moderator
         })
@@ -130,14 +310,9 @@ impl super::RepositoryTrait for Repository {
     }
 
     async fn demote_user(&self, by: i64, user: i64) -> Result<(), Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role != Role::Creator => RepoError::Forbidden);
-        error!(target_user.role != Role::Moderator => RepoError::InvalidRole);
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, Some(&target_user), None).await?;
+        error!(target_user.role != Role::Moderator => RepoError::invalid_role(&target_user));
         update!(UserEntity: target_user.id => {
             Role: Role::User
         })
@@ -147,55 +322,338 @@ impl super::RepositoryTrait for Repository {
         Ok(())
     }
 
+    async fn transfer_ownership(&self, from: i64, to: i64) -> Result<(), Self::Error> {
+        error!(to < 0 => RepoError::NotAllowed {
+            reason: "channels cannot own the bot",
+        });
+        let txn = self.db.begin().await?;
+        let by_user = Self::find_user(&txn, from).await?;
+        let target_user = Self::find_user(&txn, to).await?;
+        self.check(&txn, &by_user, Permission::TransferOwnership, None, None).await?;
+        error!(
+            target_user.role == Role::Blocked ||
+            target_user.role == Role::Creator => RepoError::invalid_role(&target_user)
+        );
+        Self::set_role(&txn, to, Role::Creator, Type::TransferOwnership, json!({"by": from})).await?;
+        Self::set_role(&txn, from, Role::Moderator, Type::TransferOwnership, json!({"to": to})).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn reconcile_creators(&self, creators: Vec<i64>) -> Result<Vec<(i64, Role)>, Self::Error> {
+        // An empty list is a misconfiguration, not a reason to demote every creator.
+        if creators.is_empty() {
+            return Ok(Vec::new());
+        }
+        let txn = self.db.begin().await?;
+        let previous: HashSet<i64> = match BotStateEntity::find_by_id(Self::CREATORS_STATE)
+            .one(&txn)
+            .await?
+        {
+            Some(state) => serde_json::from_value(state.value).unwrap_or_default(),
+            None => UserEntity::find()
+                .filter(users::Column::Role.eq(Role::Creator))
+                .all(&txn)
+                .await?
+                .into_iter()
+                .map(|user| user.id)
+                .collect(),
+        };
+        let current: HashSet<i64> = creators.iter().copied().collect();
+        let mut changed = Vec::new();
+        for &id in current.difference(&previous) {
+            let Some(user) = UserEntity::find_by_id(id).one(&txn).await? else {
+                continue;
+            };
+            if user.role != Role::Creator {
+                Self::set_role(&txn, id, Role::Creator, Type::PromoteUser, json!({"by": "config"})).await?;
+                changed.push((id, Role::Creator));
+            }
+        }
+        for &id in previous.difference(&current) {
+            let Some(user) = UserEntity::find_by_id(id).one(&txn).await? else {
+                continue;
+            };
+            if user.role == Role::Creator {
+                Self::set_role(&txn, id, Role::User, Type::DemoteUser, json!({"by": "config"})).await?;
+                changed.push((id, Role::User));
+            }
+        }
+        Self::put_state(&txn, Self::CREATORS_STATE, json!(creators)).await?;
+        txn.commit().await?;
+        Ok(changed)
+    }
+
+    async fn assign_system_role(
+        &self,
+        user: i64,
+        role: Role,
+        source: &'static str,
+    ) -> Result<Option<Role>, Self::Error> {
+        let current = Self::find_user(&self.db, user).await?.role;
+        if current == role {
+            return Ok(None);
+        }
+        let action_type = if role > current { Type::PromoteUser } else { Type::DemoteUser };
+        Self::set_role(&self.db, user, role, action_type, json!({"by": source})).await?;
+        Ok(Some(current))
+    }
+
+    async fn set_chat_roles(
+        &self,
+        chat: i64,
+        roles: HashMap<i64, Role>,
+    ) -> Result<Vec<(i64, Option<Role>, Option<Role>)>, Self::Error> {
+        let txn = self.db.begin().await?;
+        let previous = Self::chat_roles(&txn, chat).await?;
+        let mut changed: Vec<_> = roles
+            .iter()
+            .filter(|&(id, role)| previous.get(id) != Some(role))
+            .map(|(&id, &role)| (id, previous.get(&id).copied(), Some(role)))
+            .collect();
+        changed.extend(
+            previous
+                .iter()
+                .filter(|&(id, _)| !roles.contains_key(id))
+                .map(|(&id, &role)| (id, Some(role), None)),
+        );
+        for &(id, old, new) in &changed {
+            let action_type = if new > old { Type::PromoteUser } else { Type::DemoteUser };
+            Action {
+                user_id: Set(id),
+                action_type: Set(action_type),
+                description: Set(json!({
+                    "by": "telegram_sync",
+                    "chat": chat,
+                    "role": new,
+                })),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+        Self::put_state(&txn, &Self::chat_roles_key(chat), json!(roles)).await?;
+        txn.commit().await?;
+        Ok(changed)
+    }
+
+    async fn get_users_by_role(&self, role: Role) -> Result<Vec<UserModel>, Self::Error> {
+        Ok(UserEntity::find()
+            .filter(users::Column::Role.eq(role))
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn get_state(&self, key: String) -> Result<Option<Json>, Self::Error> {
+        Ok(BotStateEntity::find_by_id(key)
+            .one(&self.db)
+            .await?
+            .map(|state| state.value))
+    }
+
+    async fn set_state(&self, key: String, value: Json) -> Result<(), Self::Error> {
+        Self::put_state(&self.db, &key, value).await
+    }
+
+    async fn count_messages(&self, counts: Vec<(i64, i64)>) -> Result<(), Self::Error> {
+        let txn = self.db.begin().await?;
+        for (user, count) in counts {
+            update!(UserEntity: user => {
+                Messages: Expr::col(users::Column::Messages).add(count),
+            })
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
     async fn get_user(&self, user: i64) -> Result<UserModel, Self::Error> {
         UserEntity::find_by_id(user)
             .one(&self.db)
             .await?
-            .ok_or(RepoError::NotFound)
+            .ok_or(RepoError::user_not_found(user))
     }
 
     async fn get_user_by_username(&self, username: String) -> Result<UserModel, Self::Error> {
         UserEntity::find()
-            .filter(users::Column::Username.eq(username))
+            .filter(Expr::expr(Func::lower(Expr::col(users::Column::Username))).eq(username.to_lowercase()))
             .one(&self.db)
             .await?
-            .ok_or(RepoError::NotFound)
+            .ok_or(RepoError::NotFound {
+                entity: EntityKind::User,
+                key: Key::Name(username),
+            })
     }
 
-    async fn warn(&self, by: i64, user: i64) -> Result<bool, Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role < Role::Moderator => RepoError::Forbidden);
-        error!(target_user.role > Role::User => RepoError::InvalidRole);
-        update!(UserEntity: target_user.id => {
-            Warns: target_user.warns+1
+    async fn authorize(
+        &self,
+        actor: i64,
+        permission: Permission,
+        target: Option<i64>,
+        chat: Option<i64>,
+    ) -> Result<UserModel, Self::Error> {
+        let actor = Self::find_user(&self.db, actor).await?;
+        let target = match target {
+            Some(target) => Some(Self::find_user(&self.db, target).await?),
+            None => None,
+        };
+        self.check(&self.db, &actor, permission, target.as_ref(), chat).await?;
+        Ok(actor)
+    }
+
+    async fn get_permissions(
+        &self,
+        user: i64,
+        chat: Option<i64>,
+    ) -> Result<HashSet<Permission>, Self::Error> {
+        let user = Self::find_user(&self.db, user).await?;
+        self.permissions_of(&self.db, &user, chat).await
+    }
+
+    async fn create_role(
+        &self,
+        by: i64,
+        name: String,
+        permissions: Vec<Permission>,
+    ) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, None, None).await?;
+        CustomRole {
+            name: Set(name.clone()),
+            permissions: Set(json!(permissions)),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+        }
+        .insert(&self.db)
+        .await
+        .map_err(|e| RepoError::from(e).with_subject(EntityKind::Role, Key::Name(name.clone())))?;
+        action!(self; CreateRole@by => json!({
+            "role": name,
+            "permissions": permissions,
+        }));
+        Ok(())
+    }
+
+    async fn delete_role(&self, by: i64, name: String) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, None, None).await?;
+        let res = CustomRoleEntity::delete_by_id(&name).exec(&self.db).await?;
+        error!(res.rows_affected == 0 => RepoError::role_not_found(&name));
+        action!(self; DeleteRole@by => json!({"role": name}));
+        Ok(())
+    }
+
+    async fn get_roles(&self) -> Result<Vec<CustomRoleModel>, Self::Error> {
+        Ok(CustomRoleEntity::find().all(&self.db).await?)
+    }
+
+    async fn assign_role(&self, by: i64, user: i64, role: Option<String>) -> Result<(), Self::Error> {
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, Some(&target_user), None).await?;
+        if let Some(name) = &role {
+            error!(CustomRoleEntity::find_by_id(name).count(&self.db).await? == 0 => RepoError::role_not_found(name));
+        }
+        update!(UserEntity: user => {
+            CustomRole: role.clone(),
         })
         .exec(&self.db)
         .await?;
-        action!(self; WarnUser@user => json!({"by":by, "warns":target_user.warns+1}));
-        if target_user.warns + 1 >= self.max_warns {
-            self.block_user(by, user).await?;
-            Ok(true)
-        } else {
-            Ok(false)
+        action!(self; AssignRole@user => json!({"by": by, "role": role}));
+        Ok(())
+    }
+
+    async fn set_permission(
+        &self,
+        by: i64,
+        user: i64,
+        permission: Permission,
+        allowed: Option<bool>,
+    ) -> Result<(), Self::Error> {
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::ManageRoles, Some(&target_user), None).await?;
+        match allowed {
+            Some(allowed) => {
+                PermissionOverrideEntity::insert(PermissionOverride {
+                    user_id: Set(user),
+                    permission: Set(permission),
+                    allowed: Set(allowed),
+                })
+                .on_conflict(
+                    OnConflict::columns([
+                        permission_overrides::Column::UserId,
+                        permission_overrides::Column::Permission,
+                    ])
+                    .update_column(permission_overrides::Column::Allowed)
+                    .to_owned(),
+                )
+                .exec(&self.db)
+                .await?;
+            }
+            None => {
+                PermissionOverrideEntity::delete_by_id((user, permission))
+                    .exec(&self.db)
+                    .await?;
+            }
         }
+        action!(self; OverridePermission@user => json!({
+            "by": by,
+            "permission": permission,
+            "allowed": allowed,
+        }));
+        Ok(())
     }
 
-    async fn un_warn(&self, by: i64, user: i64) -> Result<(), Self::Error> {
-        let (by_user, target_user) = tokio::try_join!(
-            UserEntity::find_by_id(by).one(&self.db),
-            UserEntity::find_by_id(user).one(&self.db),
-        )?;
-        let by_user = by_user.ok_or(RepoError::NotFound)?;
-        let target_user = target_user.ok_or(RepoError::NotFound)?;
-        error!(by_user.role < Role::Moderator => RepoError::Forbidden);
-        error!(target_user.role > Role::User => RepoError::InvalidRole);
+    /// Reaching the chat's warn limit blocks the user in the same transaction: the ban comes with
+    /// the warn, so it needs no ban permission of its own.
+    async fn warn(&self, by: i64, user: i64, chat: i64) -> Result<bool, Self::Error> {
+        let max_warns = self
+            .get_chat_settings(chat)
+            .await?
+            .warn_limit
+            .unwrap_or(self.max_warns);
+        let txn = self.db.begin().await?;
+        let by_user = Self::find_user(&txn, by).await?;
+        let target_user = Self::find_user(&txn, user).await?;
+        self.check(&txn, &by_user, Permission::Warn, Some(&target_user), Some(chat)).await?;
+        let role = Self::role_in(&txn, &target_user, Some(chat)).await?;
+        error!(role > Role::User => RepoError::InvalidRole {
+            user,
+            actual: role,
+        });
+        let warns = target_user.warns + 1;
+        update!(UserEntity: target_user.id => {
+            Warns: warns
+        })
+        .exec(&txn)
+        .await?;
+        Action {
+            user_id: Set(user),
+            action_type: Set(Type::WarnUser),
+            description: Set(json!({"by": by, "warns": warns})),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let banned = warns >= max_warns && target_user.role == Role::User;
+        if banned {
+            Self::block(&txn, by, &target_user).await?;
+        }
+        txn.commit().await?;
+        Ok(banned)
+    }
+
+    async fn un_warn(&self, by: i64, user: i64, chat: i64) -> Result<(), Self::Error> {
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::Warn, Some(&target_user), Some(chat)).await?;
+        let role = Self::role_in(&self.db, &target_user, Some(chat)).await?;
+        error!(role > Role::User => RepoError::InvalidRole {
+            user,
+            actual: role,
+        });
         if target_user.warns <= 0 {
-            Err(RepoError::NotAllowed)?;
+            Err(RepoError::NotAllowed {
+                reason: "user has no warns",
+            })?;
         }
         update!(UserEntity: target_user.id => {
             Warns: target_user.warns-1
@@ -206,20 +664,457 @@ impl super::RepositoryTrait for Repository {
         Ok(())
     }
 
+    async fn mute_user(
+        &self,
+        by: i64,
+        user: i64,
+        chat: i64,
+        until: DateTimeWithTimeZone,
+    ) -> Result<(), Self::Error> {
+        let (by_user, target_user) = self.load_pair(by, user).await?;
+        self.check(&self.db, &by_user, Permission::Mute, Some(&target_user), Some(chat)).await?;
+        action!(self; MuteUser@user => json!({
+            "by": by,
+            "chat": chat,
+            "until": until,
+        }));
+        Ok(())
+    }
+
+    async fn delete_messages(
+        &self,
+        by: i64,
+        user: Option<i64>,
+        chat: i64,
+        messages: Vec<i32>,
+        text: Option<String>,
+    ) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        let target_user = match user {
+            Some(user) => Some(Self::find_user(&self.db, user).await?),
+            None => None,
+        };
+        self.check(&self.db, &by_user, Permission::Warn, target_user.as_ref(), Some(chat)).await?;
+        action!(self; DeleteMessages@user.unwrap_or(by) => json!({
+            "by": by,
+            "chat": chat,
+            "messages": messages,
+            "text": text,
+        }));
+        Ok(())
+    }
+
+    async fn add_filter(
+        &self,
+        by: i64,
+        chat: i64,
+        kind: filters::Kind,
+        pattern: String,
+        action: filters::Action,
+        reply: Option<String>,
+    ) -> Result<FilterModel, Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::EditSettings, None, Some(chat)).await?;
+        let filter = Filter {
+            chat_id: Set(chat),
+            kind: Set(kind),
+            pattern: Set(pattern.clone()),
+            action: Set(action),
+            reply: Set(reply),
+            created_by: Set(by),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await
+        .map_err(|e| RepoError::from(e).with_subject(EntityKind::Filter, Key::Name(pattern.clone())))?;
+        action!(self; AddFilter@by => json!({
+            "chat": chat,
+            "kind": kind,
+            "pattern": pattern,
+            "action": action,
+        }));
+        Ok(filter)
+    }
+
+    async fn delete_filter(&self, by: i64, chat: i64, pattern: String) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::EditSettings, None, Some(chat)).await?;
+        let res = FilterEntity::delete_many()
+            .filter(filters::Column::ChatId.eq(chat))
+            .filter(filters::Column::Pattern.eq(&pattern))
+            .exec(&self.db)
+            .await?;
+        error!(res.rows_affected == 0 => RepoError::NotFound {
+            entity: EntityKind::Filter,
+            key: Key::Name(pattern.clone()),
+        });
+        action!(self; DeleteFilter@by => json!({
+            "chat": chat,
+            "pattern": pattern,
+        }));
+        Ok(())
+    }
+
+    async fn get_filters(&self, chat: i64) -> Result<Vec<FilterModel>, Self::Error> {
+        Ok(FilterEntity::find()
+            .filter(filters::Column::ChatId.eq(chat))
+            .order_by_asc(filters::Column::Id)
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn filter_hit(
+        &self,
+        by: i64,
+        user: i64,
+        filter: &FilterModel,
+        text: String,
+    ) -> Result<(), Self::Error> {
+        action!(self; FilterHit@user => json!({
+            "by": by,
+            "chat": filter.chat_id,
+            "filter": filter.id,
+            "pattern": filter.pattern,
+            "action": filter.action,
+            "text": text,
+        }));
+        Ok(())
+    }
+
+    async fn captcha_result(&self, user: i64, chat: i64, passed: bool) -> Result<(), Self::Error> {
+        let description = json!({ "chat": chat });
+        if passed {
+            action!(self; CaptchaPassed@user => description);
+        } else {
+            action!(self; CaptchaFailed@user => description);
+        }
+        Ok(())
+    }
+
+    async fn night_mode(&self, by: i64, chat: i64, started: bool) -> Result<(), Self::Error> {
+        let description = json!({ "chat": chat });
+        if started {
+            action!(self; NightModeStart@by => description);
+        } else {
+            action!(self; NightModeEnd@by => description);
+        }
+        Ok(())
+    }
+
+    async fn global_ban(&self, by: i64, user: i64, reason: Option<String>) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        let target = match Self::find_user(&self.db, user).await {
+            Ok(target) => Some(target),
+            Err(RepoError::NotFound { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        self.check(&self.db, &by_user, Permission::GlobalBan, target.as_ref(), None).await?;
+        error!(user == by => RepoError::NotAllowed {
+            reason: "cannot ban yourself",
+        });
+        GlobalBanEntity::insert(GlobalBan {
+            user_id: Set(user),
+            reason: Set(reason.clone()),
+            banned_by: Set(by),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+        })
+        .on_conflict(
+            OnConflict::column(global_bans::Column::UserId)
+                .update_columns([global_bans::Column::Reason, global_bans::Column::BannedBy])
+                .to_owned(),
+        )
+        .exec(&self.db)
+        .await?;
+        action!(self; GlobalBan@by => json!({
+            "user": user,
+            "reason": reason,
+        }));
+        Ok(())
+    }
+
+    async fn global_unban(&self, by: i64, user: i64) -> Result<(), Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::GlobalBan, None, None).await?;
+        let res = GlobalBanEntity::delete_by_id(user).exec(&self.db).await?;
+        error!(res.rows_affected == 0 => RepoError::NotFound {
+            entity: EntityKind::GlobalBan,
+            key: Key::Id(user),
+        });
+        action!(self; GlobalUnban@by => json!({ "user": user }));
+        Ok(())
+    }
+
+    async fn get_global_ban(&self, user: i64) -> Result<Option<GlobalBanModel>, Self::Error> {
+        Ok(GlobalBanEntity::find_by_id(user).one(&self.db).await?)
+    }
+
+    async fn get_global_bans(&self) -> Result<Vec<GlobalBanModel>, Self::Error> {
+        Ok(GlobalBanEntity::find()
+            .order_by_asc(global_bans::Column::CreatedAt)
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn import_global_bans(
+        &self,
+        by: i64,
+        bans: Vec<(i64, Option<String>)>,
+    ) -> Result<u64, Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::GlobalBan, None, None).await?;
+        let ids: Vec<i64> = bans.iter().map(|(id, _)| *id).collect();
+        let protected: HashSet<i64> = UserEntity::find()
+            .filter(users::Column::Id.is_in(ids))
+            .filter(users::Column::Role.is_in([Role::Moderator, Role::Creator]))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|u| u.id)
+            .collect();
+        let now = chrono::Utc::now().fixed_offset();
+        let models: Vec<GlobalBan> = bans
+            .into_iter()
+            .filter(|(id, _)| *id != by && !protected.contains(id))
+            .map(|(id, reason)| GlobalBan {
+                user_id: Set(id),
+                reason: Set(reason),
+                banned_by: Set(by),
+                created_at: Set(now),
+            })
+            .collect();
+        if models.is_empty() {
+            return Ok(0);
+        }
+        let added = GlobalBanEntity::insert_many(models)
+            .on_conflict(
+                OnConflict::column(global_bans::Column::UserId)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        action!(self; ImportGlobalBans@by => json!({ "added": added }));
+        Ok(added)
+    }
+
+    async fn create_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        chat: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+        cron: Option<String>,
+    ) -> Result<JobModel, Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, kind.permission(), None, Some(chat)).await?;
+        let job = Job {
+            kind: Set(kind),
+            chat_id: Set(chat),
+            created_by: Set(by),
+            payload: Set(payload),
+            run_at: Set(run_at),
+            cron: Set(cron.clone()),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        action!(self; CreateJob@by => json!({
+            "id": job.id,
+            "kind": kind,
+            "chat": chat,
+            "run_at": run_at,
+            "cron": cron,
+        }));
+        Ok(job)
+    }
+
+    async fn schedule_job(
+        &self,
+        kind: jobs::Kind,
+        chat: i64,
+        created_by: i64,
+        payload: Json,
+        run_at: DateTimeWithTimeZone,
+    ) -> Result<JobModel, Self::Error> {
+        Ok(Job {
+            kind: Set(kind),
+            chat_id: Set(chat),
+            created_by: Set(created_by),
+            payload: Set(payload),
+            run_at: Set(run_at),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?)
+    }
+
+    async fn delete_job(
+        &self,
+        by: i64,
+        kind: jobs::Kind,
+        id: i64,
+        chat: Option<i64>,
+    ) -> Result<JobModel, Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        let not_found = || RepoError::NotFound {
+            entity: EntityKind::Job,
+            key: Key::Id(id),
+        };
+        let job = JobEntity::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .filter(|job| job.kind == kind && chat.is_none_or(|chat| job.chat_id == chat))
+            .ok_or_else(not_found)?;
+        if job.created_by != by {
+            if job.kind.personal() {
+                return Err(not_found());
+            }
+            self.check(&self.db, &by_user, job.kind.permission(), None, chat).await?;
+        }
+        JobEntity::delete_by_id(id).exec(&self.db).await?;
+        action!(self; DeleteJob@by => json!({
+            "id": id,
+            "kind": job.kind,
+            "chat": job.chat_id,
+        }));
+        Ok(job)
+    }
+
+    async fn get_jobs(
+        &self,
+        kind: jobs::Kind,
+        chat: Option<i64>,
+        created_by: Option<i64>,
+    ) -> Result<Vec<JobModel>, Self::Error> {
+        let mut query = JobEntity::find().filter(jobs::Column::Kind.eq(kind));
+        if let Some(chat) = chat {
+            query = query.filter(jobs::Column::ChatId.eq(chat));
+        }
+        if let Some(created_by) = created_by {
+            query = query.filter(jobs::Column::CreatedBy.eq(created_by));
+        }
+        Ok(query.order_by_asc(jobs::Column::RunAt).all(&self.db).await?)
+    }
+
+    async fn get_due_jobs(&self, now: DateTimeWithTimeZone) -> Result<Vec<JobModel>, Self::Error> {
+        Ok(JobEntity::find()
+            .filter(jobs::Column::RunAt.lte(now))
+            .order_by_asc(jobs::Column::RunAt)
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn get_next_job_at(&self) -> Result<Option<DateTimeWithTimeZone>, Self::Error> {
+        Ok(JobEntity::find()
+            .order_by_asc(jobs::Column::RunAt)
+            .one(&self.db)
+            .await?
+            .map(|job| job.run_at))
+    }
+
+    async fn reschedule_job(&self, id: i64, run_at: DateTimeWithTimeZone) -> Result<(), Self::Error> {
+        update!(JobEntity: id => {
+            RunAt: run_at,
+        })
+        .exec(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn finish_job(&self, id: i64) -> Result<bool, Self::Error> {
+        let deleted = JobEntity::delete_by_id(id).exec(&self.db).await?;
+        Ok(deleted.rows_affected > 0)
+    }
+
+    async fn create_report(
+        &self,
+        chat: i64,
+        message: i32,
+        reporter: i64,
+        target: i64,
+        reason: Option<String>,
+    ) -> Result<ReportModel, Self::Error> {
+        let reporter_user = Self::find_user(&self.db, reporter).await?;
+        self.check(&self.db, &reporter_user, Permission::UseCommands, None, Some(chat)).await?;
+        Ok(Report {
+            chat_id: Set(chat),
+            message_id: Set(message),
+            reporter_id: Set(reporter),
+            target_id: Set(target),
+            reason: Set(reason),
+            status: Set(Status::Open),
+            resolved_by: Set(None),
+            created_at: Set(chrono::Utc::now().fixed_offset()),
+            resolved_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?)
+    }
+
+    async fn get_report(&self, id: i64) -> Result<ReportModel, Self::Error> {
+        ReportEntity::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(RepoError::NotFound {
+                entity: EntityKind::Report,
+                key: Key::Id(id),
+            })
+    }
+
+    async fn get_last_report(&self, reporter: i64) -> Result<Option<ReportModel>, Self::Error> {
+        Ok(ReportEntity::find()
+            .filter(reports::Column::ReporterId.eq(reporter))
+            .order_by_desc(reports::Column::CreatedAt)
+            .one(&self.db)
+            .await?)
+    }
+
+    async fn resolve_report(&self, id: i64, by: i64, status: Status) -> Result<ReportModel, Self::Error> {
+        let res = update!(ReportEntity: id => {
+            Status: status,
+            ResolvedBy: by,
+            ResolvedAt: chrono::Utc::now().fixed_offset(),
+        })
+        .filter(reports::Column::Status.eq(Status::Open))
+        .exec(&self.db)
+        .await?;
+        error!(res.rows_affected == 0 => RepoError::NotModified);
+        self.get_report(id).await
+    }
+
+    async fn reopen_report(&self, id: i64) -> Result<(), Self::Error> {
+        update!(ReportEntity: id => {
+            Status: Status::Open,
+            ResolvedBy: None::<i64>,
+            ResolvedAt: None::<DateTimeWithTimeZone>,
+        })
+        .exec(&self.db)
+        .await?;
+        Ok(())
+    }
+
     async fn create_command(
         &self,
         name: String,
         action: String,
         creator: i64,
     ) -> Result<(), Self::Error> {
+        let user = Self::find_user(&self.db, creator).await?;
+        self.check(&self.db, &user, Permission::CreateCommands, None, None).await?;
         Command {
             name: Set(name.clone()),
             action: Set(action.clone()),
-            creator_id: Set(creator.clone()),
+            creator_id: Set(creator),
             ..Default::default()
         }
         .insert(&self.db)
-        .await?;
+        .await
+        .map_err(|e| RepoError::from(e).with_subject(EntityKind::Command, Key::Name(name.clone())))?;
         action!(self; CreateCommand@creator => json!({
             "command": name,
             "action": action
@@ -232,13 +1127,10 @@ impl super::RepositoryTrait for Repository {
             CommandEntity::find_by_id(&id).one(&self.db),
             UserEntity::find_by_id(by).one(&self.db),
         )?;
-        let command = command.ok_or(RepoError::CommandNotFound)?;
-        let user = user.ok_or(RepoError::NotFound)?;
-        error!(
-            user.role == Role::Blocked ||
-            user.id != command.creator_id ||
-            user.role < Role::Moderator => RepoError::Forbidden
-        );
+        let command = command.ok_or(RepoError::command_not_found(&id))?;
+        let user = user.ok_or(RepoError::user_not_found(by))?;
+        self.check(&self.db, &user, Self::command_permission(&user, &command), None, None)
+            .await?;
 
         update!(CommandEntity where Name: command.name => {
             Action: &action
@@ -257,12 +1149,10 @@ impl super::RepositoryTrait for Repository {
             CommandEntity::find_by_id(&id).one(&self.db),
             UserEntity::find_by_id(by).one(&self.db),
         )?;
-        let command = command.ok_or(RepoError::CommandNotFound)?;
-        let user = user.ok_or(RepoError::NotFound)?;
-        error!(
-            user.id != command.creator_id ||
-            user.role < Role::Moderator => RepoError::Forbidden
-        );
+        let command = command.ok_or(RepoError::command_not_found(&id))?;
+        let user = user.ok_or(RepoError::user_not_found(by))?;
+        self.check(&self.db, &user, Self::command_permission(&user, &command), None, None)
+            .await?;
         CommandEntity::delete_by_id(&id).exec(&self.db).await?;
         action!(self; DeleteCommand@by => json!({
             "command": id,
@@ -271,10 +1161,10 @@ impl super::RepositoryTrait for Repository {
     }
 
     async fn get_command(&self, id: String) -> Result<CommandModel, Self::Error> {
-        CommandEntity::find_by_id(id)
+        CommandEntity::find_by_id(&id)
             .one(&self.db)
             .await?
-            .ok_or(RepoError::CommandNotFound)
+            .ok_or(RepoError::command_not_found(&id))
     }
 
     async fn get_user_commands(
@@ -285,7 +1175,8 @@ impl super::RepositoryTrait for Repository {
     ) -> Result<Vec<CommandModel>, Self::Error> {
         Ok(CommandEntity::find()
             .filter(commands::Column::CreatorId.eq(user))
-            .limit(Some(page))
+            .order_by_desc(commands::Column::TimesUsed)
+            .limit(Some(page_size))
             .offset(Some(page * page_size))
             .all(&self.db)
             .await?)
@@ -297,24 +1188,22 @@ impl super::RepositoryTrait for Repository {
         page_size: u64,
     ) -> Result<Vec<CommandModel>, Self::Error> {
         Ok(CommandEntity::find()
-            .limit(Some(page))
+            .order_by_asc(commands::Column::Name)
+            .limit(Some(page_size))
             .offset(Some(page * page_size))
             .all(&self.db)
             .await?)
     }
 
     async fn use_command(&self, id: String, by: i64) -> Result<(), Self::Error> {
-        let user = UserEntity::find_by_id(by)
-            .one(&self.db)
-            .await?
-            .ok_or(RepoError::NotFound)?;
-        error!(user.role == Role::Blocked => RepoError::Forbidden);
-        let res = update!(CommandEntity where Name: id => {
+        let user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &user, Permission::UseCommands, None, None).await?;
+        let res = update!(CommandEntity where Name: &id => {
             TimesUsed: Expr::col(commands::Column::TimesUsed).add(1)
         })
         .exec(&self.db)
         .await?;
-        error!(res.rows_affected <= 0 => RepoError::CommandNotFound);
+        error!(res.rows_affected == 0 => RepoError::command_not_found(&id));
         Ok(())
     }
 
@@ -339,7 +1228,10 @@ impl super::RepositoryTrait for Repository {
         ActionEntity::find_by_id(id)
             .one(&self.db)
             .await?
-            .ok_or(RepoError::ActionNotFound)
+            .ok_or(RepoError::NotFound {
+                entity: EntityKind::Action,
+                key: Key::Id(id),
+            })
     }
 
     async fn get_user_actions(
@@ -350,7 +1242,8 @@ impl super::RepositoryTrait for Repository {
     ) -> Result<Vec<ActionModel>, Self::Error> {
         Ok(ActionEntity::find()
             .filter(actions::Column::UserId.eq(user))
-            .limit(Some(page))
+            .order_by_desc(actions::Column::Id)
+            .limit(Some(page_size))
             .offset(Some(page * page_size))
             .all(&self.db)
             .await?)
@@ -359,11 +1252,108 @@ impl super::RepositoryTrait for Repository {
     async fn get_actions(&self,page: u64,
                          page_size: u64,) -> Result<Vec<ActionModel>, Self::Error> {
         Ok(ActionEntity::find()
-            .limit(Some(page))
+            .order_by_desc(actions::Column::Id)
+            .limit(Some(page_size))
             .offset(Some(page * page_size))
             .all(&self.db)
             .await?)
     }
+
+    async fn get_chat_settings(&self, chat: i64) -> Result<ChatSettingsModel, Self::Error> {
+        Ok(ChatSettingsEntity::find_by_id(chat)
+            .one(&self.db)
+            .await?
+            .unwrap_or_else(|| ChatSettingsModel::new(chat)))
+    }
+
+    async fn get_chats_with_module(&self, module: Module) -> Result<Vec<ChatSettingsModel>, Self::Error> {
+        let enabled = Expr::cust_with_values(
+            "coalesce((modules ->> $1)::boolean, $2)",
+            [sea_orm::Value::from(module.name()), module.enabled_by_default().into()],
+        );
+        Ok(ChatSettingsEntity::find().filter(enabled).all(&self.db).await?)
+    }
+
+    async fn update_chat_settings(
+        &self,
+        by: i64,
+        chat: i64,
+        change: Change,
+    ) -> Result<ChatSettingsModel, Self::Error> {
+        let by_user = Self::find_user(&self.db, by).await?;
+        self.check(&self.db, &by_user, Permission::EditSettings, None, Some(chat)).await?;
+        error!(matches!(change, Change::WarnLimit(Some(limit)) if limit < 1) => RepoError::NotAllowed {
+            reason: "warn limit must be positive",
+        });
+        let mut settings = self.get_chat_settings(chat).await?;
+        let column = change.column();
+        let (setting, old, new) = change.apply(&mut settings);
+        settings.updated_at = chrono::Utc::now().fixed_offset();
+        // Only the changed column is written, so that concurrent changes to other settings of
+        // the chat are kept.
+        let settings = ChatSettingsEntity::insert(ChatSettings::from(settings).reset_all())
+            .on_conflict(
+                OnConflict::column(chat_settings::Column::ChatId)
+                    .update_columns([column, chat_settings::Column::UpdatedAt])
+                    .to_owned(),
+            )
+            .exec_with_returning(&self.db)
+            .await?;
+        action!(self; ChangeSettings@by => json!({
+            "chat": chat,
+            "setting": setting,
+            "old": old,
+            "new": new,
+        }));
+        Ok(settings)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Unknown,
+    User,
+    Command,
+    Action,
+    Role,
+    Report,
+    Filter,
+    GlobalBan,
+    Job,
+}
+
+impl fmt::Display for EntityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntityKind::Unknown => "record",
+            EntityKind::User => "user",
+            EntityKind::Command => "command",
+            EntityKind::Action => "action",
+            EntityKind::Role => "role",
+            EntityKind::Report => "report",
+            EntityKind::Filter => "filter",
+            EntityKind::GlobalBan => "global ban",
+            EntityKind::Job => "job",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    Unknown,
+    Id(i64),
+    Name(String),
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Unknown => f.write_str("?"),
+            Key::Id(id) => write!(f, "{id}"),
+            Key::Name(name) => write!(f, "'{name}'"),
+        }
+    }
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -372,26 +1362,357 @@ pub enum RepoError {
     UnknownError,
     InternalDbError(DbErr),
     NotModified,
-    NotFound,
-    CommandNotFound,
-    ActionNotFound,
-    Forbidden,
-    InvalidRole,
-    NotAllowed,
-    AlreadyExists,
+    NotFound {
+        entity: EntityKind,
+        key: Key,
+    },
+    Forbidden {
+        actor: i64,
+        permission: Permission,
+        role: Role,
+    },
+    InvalidRole {
+        user: i64,
+        actual: Role,
+    },
+    NotAllowed {
+        reason: &'static str,
+    },
+    AlreadyExists {
+        entity: EntityKind,
+        key: Key,
+    },
+}
+
+impl RepoError {
+    pub fn user_not_found(id: i64) -> Self {
+        RepoError::NotFound {
+            entity: EntityKind::User,
+            key: Key::Id(id),
+        }
+    }
+
+    pub fn command_not_found(name: &str) -> Self {
+        RepoError::NotFound {
+            entity: EntityKind::Command,
+            key: Key::Name(name.to_owned()),
+        }
+    }
+
+    pub fn role_not_found(name: &str) -> Self {
+        RepoError::NotFound {
+            entity: EntityKind::Role,
+            key: Key::Name(name.to_owned()),
+        }
+    }
+
+    pub fn forbidden(actor: &UserModel, permission: Permission) -> Self {
+        RepoError::Forbidden {
+            actor: actor.id,
+            permission,
+            role: actor.role,
+        }
+    }
+
+    pub fn invalid_role(target: &UserModel) -> Self {
+        RepoError::InvalidRole {
+            user: target.id,
+            actual: target.role,
+        }
+    }
+
+    /// Fills in the entity of errors that were built from a bare [`DbErr`].
+    pub fn with_subject(self, entity: EntityKind, key: Key) -> Self {
+        match self {
+            RepoError::NotFound {
+                entity: EntityKind::Unknown,
+                ..
+            } => RepoError::NotFound { entity, key },
+            RepoError::AlreadyExists {
+                entity: EntityKind::Unknown,
+                ..
+            } => RepoError::AlreadyExists { entity, key },
+            err => err,
+        }
+    }
+
+    /// Stable machine-readable code, safe to log and match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RepoError::UnknownError => "repo.unknown",
+            RepoError::InternalDbError(_) => "repo.internal_db",
+            RepoError::NotModified => "repo.not_modified",
+            RepoError::NotFound { .. } => "repo.not_found",
+            RepoError::Forbidden { .. } => "repo.forbidden",
+            RepoError::InvalidRole { .. } => "repo.invalid_role",
+            RepoError::NotAllowed { .. } => "repo.not_allowed",
+            RepoError::AlreadyExists { .. } => "repo.already_exists",
+        }
+    }
+
+    /// Key of the user-facing message the chat reply layer should send.
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            RepoError::UnknownError | RepoError::InternalDbError(_) => "error-internal",
+            RepoError::NotModified => "error-not-modified",
+            RepoError::NotFound { entity, .. } => match entity {
+                EntityKind::User => "error-user-not-found",
+                EntityKind::Command => "error-command-not-found",
+                EntityKind::Action => "error-action-not-found",
+                EntityKind::Role => "error-role-not-found",
+                EntityKind::Report => "error-not-found",
+                EntityKind::Filter => "error-filter-not-found",
+                EntityKind::GlobalBan => "error-gban-not-found",
+                EntityKind::Job => "error-job-not-found",
+                EntityKind::Unknown => "error-not-found",
+            },
+            RepoError::Forbidden { .. } => "error-forbidden",
+            RepoError::InvalidRole { .. } => "error-invalid-role",
+            RepoError::NotAllowed { .. } => "error-not-allowed",
+            RepoError::AlreadyExists { entity, .. } => match entity {
+                EntityKind::Command => "error-command-exists",
+                EntityKind::Role => "error-role-exists",
+                EntityKind::Filter => "error-filter-exists",
+                _ => "error-already-exists",
+            },
+        }
+    }
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::UnknownError => f.write_str("unknown repository error"),
+            RepoError::InternalDbError(err) => write!(f, "database error: {err}"),
+            RepoError::NotModified => f.write_str("record was not modified"),
+            RepoError::NotFound { entity, key } => write!(f, "{entity} {key} not found"),
+            RepoError::Forbidden {
+                actor,
+                permission,
+                role,
+            } => write!(f, "user {actor} with role {role} lacks permission {permission}"),
+            RepoError::InvalidRole { user, actual } => {
+                write!(f, "user {user} has role {actual}, which is not valid here")
+            }
+            RepoError::NotAllowed { reason } => write!(f, "not allowed: {reason}"),
+            RepoError::AlreadyExists { entity, key } => write!(f, "{entity} {key} already exists"),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RepoError::InternalDbError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<DbErr> for RepoError {
     fn from(value: DbErr) -> Self {
         match value {
-            DbErr::RecordNotFound(_) => RepoError::NotFound,
+            DbErr::RecordNotFound(msg) => RepoError::NotFound {
+                entity: EntityKind::Unknown,
+                key: Key::Name(msg),
+            },
             DbErr::RecordNotInserted | DbErr::RecordNotUpdated => RepoError::NotModified,
             DbErr::Exec(RuntimeErr::SqlxError(sqlx::error::Error::Database(err)))
                 if err.is_unique_violation() =>
             {
-                RepoError::AlreadyExists
+                RepoError::AlreadyExists {
+                    entity: EntityKind::Unknown,
+                    key: Key::Unknown,
+                }
             }
             err => RepoError::InternalDbError(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn user(id: i64, role: Role) -> UserModel {
+        UserModel {
+            id,
+            role,
+            nickname: format!("user{id}"),
+            ..Default::default()
+        }
+    }
+
+    fn grant(user: i64, permission: Permission, allowed: bool) -> permission_overrides::Model {
+        permission_overrides::Model {
+            user_id: user,
+            permission,
+            allowed,
+        }
+    }
+
+    /// Runs `authorize` against a database holding `actor`, `target` and the overrides of `actor`.
+    async fn authorize(
+        actor: UserModel,
+        target: UserModel,
+        overrides: Vec<permission_overrides::Model>,
+        permission: Permission,
+    ) -> Result<UserModel, RepoError> {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![actor.clone()]])
+            .append_query_results([vec![target.clone()]])
+            .append_query_results([overrides])
+            .into_connection();
+        let repo = Repository::new(RepositoryOptions {
+            database: db,
+            ..Default::default()
+        });
+        repo.authorize(actor.id, permission, Some(target.id), None).await
+    }
+
+    /// Runs `authorize` in chat 10, where Telegram gives the roles in `chat_roles`.
+    async fn authorize_in_chat(
+        actor: UserModel,
+        target: UserModel,
+        chat_roles: Json,
+        permission: Permission,
+    ) -> Result<UserModel, RepoError> {
+        let state = bot_state::Model {
+            key: "telegram_admins:10".to_owned(),
+            value: chat_roles,
+            updated_at: chrono::Utc::now().fixed_offset(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![actor.clone()]])
+            .append_query_results([vec![target.clone()]])
+            .append_query_results([vec![state.clone()]])
+            .append_query_results([Vec::<permission_overrides::Model>::new()])
+            .append_query_results([vec![state.clone()], vec![state]])
+            .into_connection();
+        let repo = Repository::new(RepositoryOptions {
+            database: db,
+            ..Default::default()
+        });
+        repo.authorize(actor.id, permission, Some(target.id), Some(10)).await
+    }
+
+    #[tokio::test]
+    async fn roles_grant_permissions() {
+        let moderator = user(1, Role::Moderator);
+        let result = authorize(moderator.clone(), user(2, Role::User), vec![], Permission::Warn).await;
+        assert_eq!(result, Ok(moderator));
+        let result = authorize(user(1, Role::User), user(2, Role::User), vec![], Permission::Warn).await;
+        assert!(matches!(result, Err(RepoError::Forbidden { actor: 1, .. })));
+    }
+
+    #[tokio::test]
+    async fn overrides_grant_permissions_against_peers() {
+        let actor = user(1, Role::User);
+        let overrides = vec![grant(1, Permission::Ban, true)];
+        let result = authorize(actor.clone(), user(2, Role::User), overrides, Permission::Ban).await;
+        assert_eq!(result, Ok(actor));
+    }
+
+    #[tokio::test]
+    async fn overrides_revoke_permissions() {
+        let overrides = vec![grant(1, Permission::Ban, false)];
+        let result =
+            authorize(user(1, Role::Moderator), user(2, Role::User), overrides, Permission::Ban).await;
+        assert!(matches!(result, Err(RepoError::Forbidden { actor: 1, .. })));
+    }
+
+    #[tokio::test]
+    async fn staff_is_protected_from_those_not_above_them() {
+        let overrides = vec![grant(1, Permission::Warn, true)];
+        let result =
+            authorize(user(1, Role::User), user(2, Role::Moderator), overrides, Permission::Warn).await;
+        assert_eq!(result, Err(RepoError::InvalidRole { user: 2, actual: Role::Moderator }));
+        let result =
+            authorize(user(1, Role::Moderator), user(2, Role::Moderator), vec![], Permission::Warn)
+                .await;
+        assert_eq!(result, Err(RepoError::InvalidRole { user: 2, actual: Role::Moderator }));
+        let creator = user(1, Role::Creator);
+        let result = authorize(creator.clone(), user(2, Role::Moderator), vec![], Permission::Warn).await;
+        assert_eq!(result, Ok(creator));
+    }
+
+    #[tokio::test]
+    async fn channels_cannot_own_the_bot() {
+        let repo = Repository::new(RepositoryOptions {
+            database: MockDatabase::new(DatabaseBackend::Postgres).into_connection(),
+            ..Default::default()
+        });
+        let result = repo.transfer_ownership(1, -100).await;
+        assert!(matches!(result, Err(RepoError::NotAllowed { .. })));
+    }
+
+    #[tokio::test]
+    async fn empty_creator_lists_change_nothing() {
+        let repo = Repository::new(RepositoryOptions {
+            database: MockDatabase::new(DatabaseBackend::Postgres).into_connection(),
+            ..Default::default()
+        });
+        assert_eq!(repo.reconcile_creators(Vec::new()).await, Ok(Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn settings_changes_write_only_their_column() {
+        let settings = ChatSettingsModel::new(10);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user(1, Role::Creator)]])
+            .append_query_results([Vec::<bot_state::Model>::new()])
+            .append_query_results([Vec::<permission_overrides::Model>::new()])
+            .append_query_results([vec![settings.clone()]])
+            .append_query_results([vec![settings]])
+            .append_query_results([vec![actions::Model::default()]])
+            .into_connection();
+        let repo = Repository::new(RepositoryOptions {
+            database: db,
+            ..Default::default()
+        });
+        let change = Change::Locale(Some("ru".to_owned()));
+        repo.update_chat_settings(1, 10, change).await.unwrap();
+        let log = repo.db.into_transaction_log();
+        let upsert = log[4].statements()[0].sql.clone();
+        assert!(upsert.contains(r#"DO UPDATE SET "locale" = "excluded"."locale", "updated_at""#));
+        assert!(!upsert.contains(r#""modules" = "excluded"."modules""#));
+    }
+
+    #[tokio::test]
+    async fn chat_roles_count_in_their_chat() {
+        let admin = user(1, Role::User);
+        let roles = json!({"1": "Moderator"});
+        let result =
+            authorize_in_chat(admin.clone(), user(2, Role::User), roles, Permission::Warn).await;
+        assert_eq!(result, Ok(admin));
+        let roles = json!({"1": "Creator", "2": "Moderator"});
+        let (actor, target) = (user(1, Role::User), user(2, Role::User));
+        let result = authorize_in_chat(actor, target, roles, Permission::Warn).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn chat_roles_protect_chat_admins() {
+        let roles = json!({"1": "Moderator", "2": "Moderator"});
+        let (actor, target) = (user(1, Role::User), user(2, Role::User));
+        let result = authorize_in_chat(actor, target, roles, Permission::Warn).await;
+        assert_eq!(result, Err(RepoError::InvalidRole { user: 2, actual: Role::Moderator }));
+    }
+
+    #[tokio::test]
+    async fn chat_roles_do_not_lift_blocks() {
+        // Blocked users never get their chat roles read.
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user(1, Role::Blocked)]])
+            .append_query_results([vec![user(2, Role::User)]])
+            .append_query_results([Vec::<permission_overrides::Model>::new()])
+            .into_connection();
+        let repo = Repository::new(RepositoryOptions {
+            database: db,
+            ..Default::default()
+        });
+        let result = repo.authorize(1, Permission::Warn, Some(2), Some(10)).await;
+        assert!(matches!(result, Err(RepoError::Forbidden { actor: 1, .. })));
+    }
+}
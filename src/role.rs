@@ -1,17 +1,127 @@
+use crate::command::Context;
+use crate::error::Error;
 use crate::models::users::Role;
+use crate::nickname::mention;
+use crate::permission::Permission;
+use crate::repository::RepositoryTrait;
+use crate::tr;
+use teloxide::utils::html;
 
 pub struct RoleSelector {
-    creator: Option<i64>,
+    creators: Vec<i64>,
 }
 
 impl RoleSelector {
-    pub fn new(creator: Option<i64>) -> Self {
-        Self { creator }
+    pub fn new(creators: Vec<i64>) -> Self {
+        Self { creators }
     }
     pub fn select(&self, id: i64) -> Role {
-        match self.creator {
-            Some(creator) if creator == id => Role::Creator,
-            _ => Role::User,
+        if self.creators.contains(&id) {
+            Role::Creator
+        } else {
+            Role::User
         }
     }
 }
+
+/// `/role list|show|create|delete|assign|grant|revoke|reset`; user-level subcommands act on the
+/// user given after their arguments or on the author of the replied-to message.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let mut words = args.split_whitespace();
+    let subcommand = words.next().unwrap_or("list");
+    let rest: Vec<&str> = words.collect();
+    match (subcommand, rest.as_slice()) {
+        ("list", []) => {
+            let roles = ctx.repo.get_roles().await?;
+            let text = match roles.is_empty() {
+                true => tr!(ctx.i18n, ctx.locale, "role-list-empty"),
+                false => roles
+                    .iter()
+                    .map(|role| format!("{}: {}", role.name, join(role.permissions())))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            };
+            ctx.reply(text).await?;
+        }
+        ("show", _) => {
+            let user = match ctx.target(&args, 1).await? {
+                Some(user) => user,
+                None => ctx.repo.get_user(by).await?,
+            };
+            let permissions = ctx.repo.get_permissions(user.id, Some(ctx.msg.chat.id.0)).await?;
+            ctx.reply_html(tr!(
+                ctx.i18n,
+                ctx.locale,
+                "role-show",
+                user = mention(&user),
+                role = user.role,
+                custom = html::escape(user.custom_role.as_deref().unwrap_or("-")),
+                permissions = join(permissions)
+            ))
+            .await?;
+        }
+        ("create", [name, permissions @ ..]) if !permissions.is_empty() => {
+            let Some(permissions) = parse_permissions(permissions) else {
+                return usage(ctx).await;
+            };
+            ctx.repo
+                .create_role(by, name.to_string(), permissions)
+                .await?;
+            ctx.reply(tr!(ctx.i18n, ctx.locale, "role-created", role = name))
+                .await?;
+        }
+        ("delete", [name]) => {
+            ctx.repo.delete_role(by, name.to_string()).await?;
+            ctx.reply(tr!(ctx.i18n, ctx.locale, "role-deleted", role = name))
+                .await?;
+        }
+        ("assign", [name, ..]) => {
+            let Some(target) = ctx.target(&args, 2).await? else {
+                return usage(ctx).await;
+            };
+            let role = (*name != "none").then(|| name.to_string());
+            ctx.repo.assign_role(by, target.id, role).await?;
+            ctx.reply_html(tr!(ctx.i18n, ctx.locale, "role-assigned", user = mention(&target)))
+                .await?;
+        }
+        (change @ ("grant" | "revoke" | "reset"), [permission, ..]) => {
+            let (Some(permission), Some(target)) =
+                (Permission::from_name(permission), ctx.target(&args, 2).await?)
+            else {
+                return usage(ctx).await;
+            };
+            let allowed = match change {
+                "grant" => Some(true),
+                "revoke" => Some(false),
+                _ => None,
+            };
+            ctx.repo
+                .set_permission(by, target.id, permission, allowed)
+                .await?;
+            ctx.reply_html(tr!(ctx.i18n, ctx.locale, "role-assigned", user = mention(&target)))
+                .await?;
+        }
+        _ => return usage(ctx).await,
+    }
+    Ok(())
+}
+
+async fn usage(ctx: &Context<'_>) -> Result<(), Error> {
+    let permissions = join(Permission::iter_all());
+    ctx.reply(tr!(ctx.i18n, ctx.locale, "role-usage", permissions = permissions))
+        .await?;
+    Ok(())
+}
+
+fn parse_permissions(names: &[&str]) -> Option<Vec<Permission>> {
+    names.iter().map(|name| Permission::from_name(name)).collect()
+}
+
+fn join(permissions: impl IntoIterator<Item = Permission>) -> String {
+    let mut names: Vec<String> = permissions.into_iter().map(Permission::name).collect();
+    names.sort();
+    names.join(", ")
+}
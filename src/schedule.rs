@@ -0,0 +1,184 @@
+use crate::command::Context;
+use crate::error::Error;
+use crate::jobs::{next_run, parse_cron};
+use crate::models::jobs::{Kind, Post};
+use crate::permission::Permission;
+use crate::repository::RepositoryTrait;
+use crate::tr;
+use crate::when::{absolute, format_time, relative, word};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use teloxide::utils::html;
+
+/// Characters of a post or reminder shown in lists.
+const PREVIEW_LENGTH: usize = 60;
+
+/// When a post is due.
+enum When {
+    Once(DateTime<Utc>),
+    Cron(String),
+}
+
+/// `in <duration>`, `at <time>` or `cron <five fields>`, followed by the rest.
+fn parse_when(args: &str, tz: Tz, now: DateTime<Utc>) -> Option<(When, &str)> {
+    let (mode, rest) = word(args);
+    match mode {
+        "in" => {
+            let (duration, rest) = relative(rest)?;
+            Some((When::Once(now.checked_add_signed(duration)?), rest))
+        }
+        "at" => {
+            let (at, rest) = absolute(rest, tz, now)?;
+            Some((When::Once(at), rest))
+        }
+        "cron" => {
+            let mut rest = rest;
+            let mut fields = Vec::new();
+            for _ in 0..5 {
+                let (field, next) = word(rest);
+                fields.push(field);
+                rest = next;
+            }
+            let expression = fields.join(" ");
+            parse_cron(&expression)?;
+            Some((When::Cron(expression), rest))
+        }
+        _ => None,
+    }
+}
+
+/// The escaped start of `text`, for lists.
+pub fn preview(text: &str) -> String {
+    let preview: String = text.chars().take(PREVIEW_LENGTH).collect();
+    let ellipsis = if text.chars().count() > PREVIEW_LENGTH { "…" } else { "" };
+    format!("{}{ellipsis}", html::escape(&preview))
+}
+
+/// `/schedule in|at|cron <when> [pin] <text>` schedules a post into the chat, once or on a cron
+/// schedule in the configured time zone.
+pub async fn schedule(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let tz = ctx.config.scheduler.timezone;
+    let now = Utc::now();
+    let Some((when, rest)) = parse_when(&args, tz, now) else {
+        ctx.reply(tr!(i18n, locale, "schedule-usage", timezone = tz.name()))
+            .await?;
+        return Ok(());
+    };
+    let (pin, text) = match word(rest) {
+        ("pin", text) => (true, text.trim()),
+        _ => (false, rest.trim()),
+    };
+    if text.is_empty() {
+        ctx.reply(tr!(i18n, locale, "schedule-usage", timezone = tz.name()))
+            .await?;
+        return Ok(());
+    }
+    let (run_at, cron) = match when {
+        When::Once(at) if at <= now => {
+            ctx.reply(tr!(i18n, locale, "schedule-past")).await?;
+            return Ok(());
+        }
+        When::Once(at) => (at, None),
+        When::Cron(expression) => match next_run(&expression, tz, now) {
+            Some(at) => (at, Some(expression)),
+            None => {
+                ctx.reply(tr!(i18n, locale, "schedule-past")).await?;
+                return Ok(());
+            }
+        },
+    };
+    let chat = ctx.msg.chat.id.0;
+    let existing = ctx.repo.get_jobs(Kind::Post, Some(chat), None).await?;
+    if existing.len() >= ctx.config.scheduler.max_per_chat {
+        let limit = ctx.config.scheduler.max_per_chat as i64;
+        ctx.reply(tr!(i18n, locale, "schedule-limit", count: limit)).await?;
+        return Ok(());
+    }
+    let payload = Post {
+        text: text.to_owned(),
+        pin,
+    };
+    let job = ctx
+        .repo
+        .create_job(
+            by,
+            Kind::Post,
+            chat,
+            serde_json::to_value(&payload).unwrap_or_default(),
+            run_at.fixed_offset(),
+            cron,
+        )
+        .await?;
+    let text = tr!(
+        i18n,
+        locale,
+        "schedule-created",
+        id = job.id,
+        time = format_time(job.run_at, tz),
+        timezone = tz.name()
+    );
+    ctx.reply(text).await?;
+    Ok(())
+}
+
+/// `/schedules` lists the chat's scheduled posts.
+pub async fn list(ctx: &Context<'_>) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    ctx.repo.authorize(by, Kind::Post.permission(), None, Some(ctx.msg.chat.id.0)).await?;
+    let jobs = ctx
+        .repo
+        .get_jobs(Kind::Post, Some(ctx.msg.chat.id.0), None)
+        .await?;
+    if jobs.is_empty() {
+        ctx.reply(tr!(i18n, locale, "schedule-empty")).await?;
+        return Ok(());
+    }
+    let tz = ctx.config.scheduler.timezone;
+    let mut lines = vec![tr!(
+        i18n,
+        locale,
+        "schedule-list",
+        count: jobs.len() as i64,
+        timezone = tz.name()
+    )];
+    for job in &jobs {
+        let post = job.post();
+        let mut line = format!("<b>#{}</b> {}", job.id, format_time(job.run_at, tz));
+        if let Some(cron) = &job.cron {
+            line.push_str(&format!(" <code>{}</code>", html::escape(cron)));
+        }
+        if post.pin {
+            line.push_str(" 📌");
+        }
+        line.push('\n');
+        line.push_str(&preview(&post.text));
+        lines.push(line);
+    }
+    ctx.reply_html(lines.join("\n\n")).await?;
+    Ok(())
+}
+
+/// `/unschedule <id>` deletes a scheduled post of the chat.
+pub async fn unschedule(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let (i18n, locale) = (ctx.i18n, ctx.locale);
+    let Ok(id) = args.trim().trim_start_matches('#').parse::<i64>() else {
+        ctx.reply(tr!(i18n, locale, "unschedule-usage")).await?;
+        return Ok(());
+    };
+    ctx.repo.authorize(by, Permission::EditSettings, None, Some(ctx.msg.chat.id.0)).await?;
+    ctx.repo
+        .delete_job(by, Kind::Post, id, Some(ctx.msg.chat.id.0))
+        .await?;
+    ctx.reply(tr!(i18n, locale, "schedule-deleted", id = id)).await?;
+    Ok(())
+}
@@ -0,0 +1,456 @@
+use crate::command::Context;
+use crate::config::Config;
+use crate::error::reply::alert;
+use crate::error::{Error, HandlerError};
+use crate::i18n::{I18n, Locale};
+use crate::models::chat_settings::{
+    CaptchaChange, CaptchaKind, Change, FloodAction, FloodChange, GreetingsChange, LinksChange,
+    Module, NightChange, NightRestriction, QuotasChange,
+};
+use crate::night::{self, Night};
+use crate::models::prelude::*;
+use crate::permission::Permission;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::tr;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::sugar::request::RequestReplyExt;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+const CALLBACK: &str = "settings:";
+
+/// `/settings` shows the menu, `/settings <name> <value>` changes a single setting.
+pub async fn command(ctx: &Context<'_>, args: String) -> Result<(), Error> {
+    let Some(by) = ctx.sender_id() else {
+        return Ok(());
+    };
+    let args = args.trim();
+    if args.is_empty() {
+        ctx.repo.authorize(by, Permission::EditSettings, None, Some(ctx.msg.chat.id.0)).await?;
+        ctx.bot
+            .send_message(ctx.msg.chat.id, render(ctx.i18n, ctx.locale, ctx.config, &ctx.settings))
+            .reply_markup(keyboard(ctx.i18n, ctx.locale, ctx.config, &ctx.settings))
+            .reply_to(ctx.msg.id)
+            .await?;
+        return Ok(());
+    }
+    let Some(change) = parse(args) else {
+        ctx.reply(tr!(ctx.i18n, ctx.locale, "settings-usage")).await?;
+        return Ok(());
+    };
+    if matches!(change, Change::Module(Module::NightMode, true)) {
+        ctx.repo.authorize(by, Permission::EditSettings, None, Some(ctx.msg.chat.id.0)).await?;
+        let chat = ctx.msg.chat.id;
+        if let Some(refusal) = night::refusal(ctx.bot, ctx.i18n, ctx.repo, ctx.locale, chat).await? {
+            ctx.reply(refusal).await?;
+            return Ok(());
+        }
+    }
+    let setting = setting_name(&change);
+    ctx.repo
+        .update_chat_settings(by, ctx.msg.chat.id.0, change)
+        .await?;
+    ctx.reply(tr!(ctx.i18n, ctx.locale, "settings-updated", setting = setting))
+        .await?;
+    Ok(())
+}
+
+fn parse(args: &str) -> Option<Change> {
+    let (name, value) = args
+        .split_once(char::is_whitespace)
+        .map(|(name, value)| (name, value.trim()))
+        .unwrap_or((args, ""));
+    let value = match value {
+        "" | "default" => None,
+        value => Some(value),
+    };
+    Some(match name {
+        "warns" => Change::WarnLimit(value.map(str::parse).transpose().ok()?),
+        "prefix" => {
+            if value.is_some_and(|p| p.chars().count() > 3 || p.contains(char::is_whitespace)) {
+                return None;
+            }
+            Change::CommandPrefix(value.map(str::to_owned))
+        }
+        "locale" => Change::Locale(match value {
+            Some(code) => Some(Locale::from_code(code)?.code().to_owned()),
+            None => None,
+        }),
+        "welcome" => Change::WelcomeText(value.map(str::to_owned)),
+        "module" => {
+            let (module, state) = value?.split_once(char::is_whitespace)?;
+            let enabled = match state.trim() {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            };
+            Change::Module(Module::from_name(module)?, enabled)
+        }
+        "flood" => {
+            let value = value?;
+            let (name, value) = value
+                .split_once(char::is_whitespace)
+                .map(|(name, value)| (name, value.trim()))
+                .unwrap_or((value, "default"));
+            let number = || -> Option<Option<u32>> {
+                match value {
+                    "default" => Some(None),
+                    value => value.parse().ok().filter(|n| *n > 0).map(Some),
+                }
+            };
+            Change::Flood(match name {
+                "messages" => FloodChange::Messages(number()?),
+                "window" => FloodChange::Window(number()?.map(u64::from)),
+                "repeats" => FloodChange::Repeats(number()?),
+                "stickers" => FloodChange::Stickers(number()?),
+                "action" => FloodChange::Action(match value {
+                    "default" => None,
+                    value => Some(FloodAction::from_name(value)?),
+                }),
+                _ => return None,
+            })
+        }
+        "links" => {
+            let (name, value) = value?.split_once(char::is_whitespace)?;
+            let value = value.trim();
+            Change::Links(match name {
+                "allow" => LinksChange::Allow(domain(value)?),
+                "block" => LinksChange::Block(domain(value)?),
+                "remove" => LinksChange::Remove(domain(value)?),
+                "invites" => LinksChange::BlockInvites(match value {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    "default" => None,
+                    _ => return None,
+                }),
+                "hours" => LinksChange::NewUserHours(match value {
+                    "default" => None,
+                    value => Some(value.parse().ok()?),
+                }),
+                _ => return None,
+            })
+        }
+        "captcha" => {
+            let (name, value) = value?.split_once(char::is_whitespace)?;
+            let value = match value.trim() {
+                "default" => None,
+                value => Some(value),
+            };
+            Change::Captcha(match name {
+                "kind" => CaptchaChange::Kind(match value {
+                    Some(kind) => Some(CaptchaKind::from_name(kind)?),
+                    None => None,
+                }),
+                "timeout" => CaptchaChange::Timeout(match value {
+                    Some(timeout) => Some(timeout.parse().ok().filter(|t| *t >= 10)?),
+                    None => None,
+                }),
+                _ => return None,
+            })
+        }
+        "greetings" => {
+            let (name, value) = value?.split_once(char::is_whitespace)?;
+            let value = value.trim();
+            Change::Greetings(match name {
+                "delete" => GreetingsChange::DeleteAfter(match value {
+                    "default" => None,
+                    value => Some(value.parse().ok()?),
+                }),
+                "clean" => GreetingsChange::CleanService(match value {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    "default" => None,
+                    _ => return None,
+                }),
+                _ => return None,
+            })
+        }
+        "night" => {
+            let (name, value) = value?.split_once(char::is_whitespace)?;
+            let value = match value.trim() {
+                "default" => None,
+                value => Some(value),
+            };
+            let time = || value.map(|v| chrono::NaiveTime::parse_from_str(v, "%H:%M")).transpose().ok();
+            Change::Night(match name {
+                "start" => NightChange::Start(time()?),
+                "end" => NightChange::End(time()?),
+                "restrict" => NightChange::Restrict(match value {
+                    Some(restrict) => Some(NightRestriction::from_name(restrict)?),
+                    None => None,
+                }),
+                "timezone" => NightChange::Timezone(value.map(str::parse).transpose().ok()?),
+                "announce" => NightChange::Announce(match value {
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    None => None,
+                    _ => return None,
+                }),
+                _ => return None,
+            })
+        }
+        "quotas" => match value?.split_once(char::is_whitespace)? {
+            ("newcomers", "default") => Change::Quotas(QuotasChange::NewcomerHours(None)),
+            ("newcomers", hours) => Change::Quotas(QuotasChange::NewcomerHours(Some(hours.trim().parse().ok()?))),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Lowercased domain of `value`, which may also be a whole link.
+fn domain(value: &str) -> Option<String> {
+    let value = value.to_lowercase();
+    let host = value
+        .split_once("://")
+        .map_or(value.as_str(), |(_, rest)| rest)
+        .split(['/', '?', '#'])
+        .next()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    (host.contains('.') && !host.contains(char::is_whitespace)).then(|| host.to_owned())
+}
+
+fn setting_name(change: &Change) -> &'static str {
+    match change {
+        Change::WarnLimit(_) => "warns",
+        Change::CommandPrefix(_) => "prefix",
+        Change::Locale(_) => "locale",
+        Change::WelcomeText(_) => "welcome",
+        Change::Module(..) => "module",
+        Change::Flood(_) => "flood",
+        Change::Links(_) => "links",
+        Change::Captcha(_) => "captcha",
+        Change::Greetings(_) => "greetings",
+        Change::Night(_) => "night",
+        Change::Quotas(_) => "quotas",
+    }
+}
+
+fn render(i18n: &I18n, locale: Locale, config: &Config, settings: &ChatSettingsModel) -> String {
+    let not_set = tr!(i18n, locale, "settings-not-set");
+    [
+        tr!(i18n, locale, "settings-title"),
+        tr!(
+            i18n,
+            locale,
+            "settings-warn-limit",
+            value = settings.warn_limit.unwrap_or(config.warns.max)
+        ),
+        tr!(
+            i18n,
+            locale,
+            "settings-locale",
+            value = settings.locale.as_deref().unwrap_or(&not_set)
+        ),
+        tr!(
+            i18n,
+            locale,
+            "settings-prefix",
+            value = settings.command_prefix.as_deref().unwrap_or(&config.command_prefix)
+        ),
+        tr!(
+            i18n,
+            locale,
+            "settings-welcome",
+            value = settings.welcome_text.as_deref().unwrap_or(&not_set)
+        ),
+        render_flood(i18n, locale, config, settings),
+        render_links(i18n, locale, config, settings),
+        tr!(
+            i18n,
+            locale,
+            "settings-captcha",
+            kind = settings.captcha().kind.unwrap_or(config.captcha.kind).name(),
+            timeout = settings.captcha().timeout.unwrap_or(config.captcha.timeout)
+        ),
+        render_greetings(i18n, locale, config, settings),
+        render_night(i18n, locale, config, settings),
+        tr!(
+            i18n,
+            locale,
+            "settings-quotas",
+            count: settings.quotas().rules.len() as i64,
+            hours = settings.quotas().newcomer_hours.unwrap_or(config.quotas.newcomer_hours)
+        ),
+        String::new(),
+        tr!(i18n, locale, "settings-usage"),
+    ]
+    .join("\n")
+}
+
+fn render_flood(i18n: &I18n, locale: Locale, config: &Config, settings: &ChatSettingsModel) -> String {
+    let flood = settings.flood();
+    let action = match flood.action.unwrap_or(config.flood.action) {
+        FloodAction::Mute => "mute",
+        FloodAction::Warn => "warn",
+    };
+    tr!(
+        i18n,
+        locale,
+        "settings-flood",
+        messages = flood.messages.unwrap_or(config.flood.messages),
+        window = flood.window.unwrap_or(config.flood.window),
+        repeats = flood.repeats.unwrap_or(config.flood.repeats),
+        stickers = flood.stickers.unwrap_or(config.flood.stickers),
+        action = action
+    )
+}
+
+fn render_links(i18n: &I18n, locale: Locale, config: &Config, settings: &ChatSettingsModel) -> String {
+    let links = settings.links();
+    let list = |own: &[String], shared: &[String]| {
+        let all: Vec<&str> = shared.iter().chain(own).map(String::as_str).collect();
+        match all.is_empty() {
+            true => "-".to_owned(),
+            false => all.join(", "),
+        }
+    };
+    let invites = links.block_invites.unwrap_or(config.links.block_invites);
+    tr!(
+        i18n,
+        locale,
+        "settings-links",
+        allowlist = list(&links.allowlist, &config.links.allowlist),
+        blocklist = list(&links.blocklist, &config.links.blocklist),
+        invites = if invites { "on" } else { "off" },
+        hours = links.new_user_hours.unwrap_or(config.links.new_user_hours)
+    )
+}
+
+fn render_greetings(i18n: &I18n, locale: Locale, config: &Config, settings: &ChatSettingsModel) -> String {
+    let greetings = settings.greetings();
+    let not_set = tr!(i18n, locale, "settings-not-set");
+    let clean = greetings.clean_service.unwrap_or(config.greetings.clean_service);
+    tr!(
+        i18n,
+        locale,
+        "settings-greetings",
+        goodbye = greetings.goodbye_text.as_deref().unwrap_or(&not_set),
+        delete = greetings.delete_after.unwrap_or(config.greetings.delete_after),
+        clean = if clean { "on" } else { "off" }
+    )
+}
+
+fn render_night(i18n: &I18n, locale: Locale, config: &Config, settings: &ChatSettingsModel) -> String {
+    let night = Night::of(settings, config);
+    tr!(
+        i18n,
+        locale,
+        "settings-night",
+        start = night.start.format("%H:%M"),
+        end = night.end.format("%H:%M"),
+        timezone = night.timezone.name(),
+        restrict = night.restrict.name(),
+        announce = if night.announce { "on" } else { "off" }
+    )
+}
+
+fn keyboard(
+    i18n: &I18n,
+    locale: Locale,
+    config: &Config,
+    settings: &ChatSettingsModel,
+) -> InlineKeyboardMarkup {
+    let button = |text: String, data: &str| InlineKeyboardButton::callback(text, format!("{CALLBACK}{data}"));
+    let mut rows = vec![
+        vec![
+            button("➖".to_owned(), "warns:dec"),
+            button(
+                format!("⚠️ {}", settings.warn_limit.unwrap_or(config.warns.max)),
+                "noop",
+            ),
+            button("➕".to_owned(), "warns:inc"),
+        ],
+        vec![button(
+            format!("🌐 {}", settings.locale.as_deref().unwrap_or("auto")),
+            "locale",
+        )],
+    ];
+    for module in Module::ALL {
+        let mark = if settings.module_enabled(module) { "✅" } else { "❌" };
+        let name = tr!(i18n, locale, &format!("module-{module}"));
+        rows.push(vec![button(format!("{mark} {name}"), &format!("module:{module}"))]);
+    }
+    InlineKeyboardMarkup::new(rows)
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(CALLBACK))
+}
+
+/// Handles a press on one of the menu buttons and redraws the menu.
+pub async fn callback(
+    bot: Bot,
+    q: CallbackQuery,
+    i18n: Arc<I18n>,
+    repo: Arc<Repo>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        return Ok(());
+    };
+    let user_locale = q.from.language_code.as_deref();
+    let chat = message.chat.id;
+    let result: Result<(), Error> = async {
+        let settings = repo.get_chat_settings(chat.0).await?;
+        let change = match q.data.as_deref().and_then(|d| d.strip_prefix(CALLBACK)) {
+            Some("warns:inc") => Some(Change::WarnLimit(Some(
+                settings.warn_limit.unwrap_or(config.warns.max) + 1,
+            ))),
+            Some("warns:dec") => Some(Change::WarnLimit(Some(
+                settings.warn_limit.unwrap_or(config.warns.max) - 1,
+            ))),
+            Some("locale") => Some(Change::Locale(next_locale(settings.locale.as_deref()))),
+            Some(data) => data
+                .strip_prefix("module:")
+                .and_then(Module::from_name)
+                .map(|module| Change::Module(module, !settings.module_enabled(module))),
+            None => None,
+        };
+        let Some(change) = change else {
+            bot.answer_callback_query(q.id.clone()).await?;
+            return Ok(());
+        };
+        if matches!(change, Change::Module(Module::NightMode, true)) {
+            let by = q.from.id.0 as i64;
+            repo.authorize(by, Permission::EditSettings, None, Some(chat.0)).await?;
+            let locale = i18n.locale(settings.locale.as_deref(), user_locale);
+            if let Some(refusal) = night::refusal(&bot, &i18n, &repo, locale, chat).await? {
+                bot.answer_callback_query(q.id.clone())
+                    .text(refusal)
+                    .show_alert(true)
+                    .await?;
+                return Ok(());
+            }
+        }
+        let settings = repo
+            .update_chat_settings(q.from.id.0 as i64, chat.0, change)
+            .await?;
+        let locale = i18n.locale(settings.locale.as_deref(), user_locale);
+        bot.edit_message_text(chat, message.id, render(&i18n, locale, &config, &settings))
+            .reply_markup(keyboard(&i18n, locale, &config, &settings))
+            .await?;
+        bot.answer_callback_query(q.id.clone()).await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = result {
+        let settings = repo.get_chat_settings(chat.0).await.ok();
+        let locale = i18n.locale(settings.and_then(|s| s.locale).as_deref(), user_locale);
+        alert(&bot, &i18n, locale, &q, err).await;
+    }
+    Ok(())
+}
+
+/// Cycles through the supported locales and then back to "not set".
+fn next_locale(current: Option<&str>) -> Option<String> {
+    let current = current.and_then(Locale::from_code);
+    let next = match current {
+        None => Locale::ALL.first(),
+        Some(locale) => Locale::ALL
+            .iter()
+            .skip_while(|l| **l != locale)
+            .nth(1),
+    };
+    next.map(|l| l.code().to_owned())
+}
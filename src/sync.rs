@@ -0,0 +1,122 @@
+use crate::config::{Config, SyncConfig, SyncMode};
+use crate::error::{Error, HandlerError};
+use crate::models::users::Role;
+use crate::register::register;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{Me, UserId};
+use teloxide::RequestError;
+
+/// Syncs every configured chat once per `sync.interval`.
+pub async fn run(
+    bot: Bot,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+    me: UserId,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.sync.interval));
+    loop {
+        interval.tick().await;
+        for &chat in &config.sync.chats {
+            if let Err(e) = sync_chat(&bot, &repo, &roles, &config.sync, ChatId(chat), me).await {
+                tracing::error!("Failed to sync administrators of chat {}: {}", chat, e);
+            }
+        }
+    }
+}
+
+/// Re-syncs a configured chat whenever somebody gains or loses admin rights in it.
+pub async fn chat_member(
+    bot: Bot,
+    me: Me,
+    update: ChatMemberUpdated,
+    repo: Arc<Repo>,
+    roles: Arc<RoleSelector>,
+    config: Arc<Config>,
+) -> Result<(), HandlerError> {
+    let chat = update.chat.id;
+    let watched = config.sync.chats.contains(&chat.0);
+    let changed = update.old_chat_member.kind.is_privileged()
+        != update.new_chat_member.kind.is_privileged();
+    if config.sync.mode == SyncMode::Off || !watched || !changed {
+        return Ok(());
+    }
+    if let Err(e) = sync_chat(&bot, &repo, &roles, &config.sync, chat, me.id).await {
+        tracing::error!("Failed to sync administrators of chat {}: {}", chat, e);
+    }
+    Ok(())
+}
+
+/// Gives the chat's owner the creator role and its other administrators the moderator role, in
+/// that chat only, see [`RepositoryTrait::set_chat_roles`].
+///
+/// In two-way mode, administrators blocked in the bot are demoted in Telegram, and moderators and
+/// creators of the bot who are members of the chat are promoted there. The bot itself is never
+/// touched.
+pub async fn sync_chat(
+    bot: &Bot,
+    repo: &Repo,
+    roles: &RoleSelector,
+    sync: &SyncConfig,
+    chat: ChatId,
+    me: UserId,
+) -> Result<(), Error> {
+    let two_way = sync.mode == SyncMode::TwoWay;
+    let mut current = HashMap::new();
+    for member in bot.get_chat_administrators(chat).await? {
+        if member.user.is_bot {
+            continue;
+        }
+        register(repo, roles, &member.user).await?;
+        let id = member.user.id.0 as i64;
+        let owner = member.is_owner();
+        if two_way && !owner && repo.get_user(id).await?.role == Role::Blocked {
+            bot.promote_chat_member(chat, member.user.id).await?;
+            continue;
+        }
+        current.insert(id, if owner { Role::Creator } else { Role::Moderator });
+    }
+
+    if two_way {
+        for role in [Role::Moderator, Role::Creator] {
+            for user in repo.get_users_by_role(role).await? {
+                if user.id == me.0 as i64 || current.contains_key(&user.id) {
+                    continue;
+                }
+                match promote(bot, chat, UserId(user.id as u64)).await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        tracing::debug!("Cannot promote {} in chat {}: {}", user.id, chat, e);
+                        continue;
+                    }
+                }
+                current.insert(user.id, Role::Moderator);
+            }
+        }
+    }
+
+    for (id, old, new) in repo.set_chat_roles(chat.0, current).await? {
+        tracing::info!("User {} is now {:?} (was {:?}) in chat {}", id, new, old, chat);
+    }
+    Ok(())
+}
+
+/// Makes `user` an administrator of `chat` if they are a member of it, returning whether they
+/// were.
+async fn promote(bot: &Bot, chat: ChatId, user: UserId) -> Result<bool, RequestError> {
+    if !bot.get_chat_member(chat, user).await?.is_present() {
+        return Ok(false);
+    }
+    bot.promote_chat_member(chat, user)
+        .can_delete_messages(true)
+        .can_restrict_members(true)
+        .can_pin_messages(true)
+        .await?;
+    Ok(true)
+}
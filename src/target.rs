@@ -0,0 +1,298 @@
+use crate::models::prelude::UserModel;
+use crate::register::{is_person, register, register_chat};
+use crate::repository::db::RepoError;
+use crate::repository::{Repo, RepositoryTrait};
+use crate::role::RoleSelector;
+use teloxide::types::{Message, MessageEntityKind, MessageEntityRef, User};
+
+const USER_LINK: &str = "tg://user?id=";
+/// Shorter numbers are taken for arguments such as counts rather than user ids.
+const MIN_ID_DIGITS: usize = 5;
+
+/// Users a command is aimed at and the argument text left after them.
+#[derive(Debug, Default)]
+pub struct Targets {
+    pub users: Vec<UserModel>,
+    pub rest: String,
+}
+
+/// Resolves the targets of a command message.
+///
+/// `args` are the command's arguments, the end of the message text. After `skip` of their words,
+/// consecutive `@username` mentions, text mentions and `tg://user?id=` links are taken as
+/// targets, as is a numeric id in the first target position only, so that numbers in the reason
+/// stay part of it; an unknown one is left to the reason when the message replies to someone.
+/// Without any, the author of the replied-to message is the target: the original
+/// author for forwarded messages and the channel for messages posted on behalf of one. Users the
+/// bot only learns about from the message itself are registered on the way.
+pub async fn resolve(
+    repo: &Repo,
+    roles: &RoleSelector,
+    msg: &Message,
+    args: &str,
+    skip: usize,
+) -> Result<Targets, RepoError> {
+    let text = msg.text().or_else(|| msg.caption()).unwrap_or_default();
+    let entities = msg
+        .parse_entities()
+        .or_else(|| msg.parse_caption_entities())
+        .unwrap_or_default();
+    let start = text
+        .len()
+        .checked_sub(args.len())
+        .filter(|&start| text.get(start..) == Some(args))
+        .unwrap_or_else(|| skip_words(text, 0, 1));
+    let mut pos = skip_words(text, start, skip);
+    let mut users = Vec::new();
+    loop {
+        pos = skip_whitespace(text, pos);
+        if pos == text.len() {
+            break;
+        }
+        if let Some(entity) = entities.iter().find(|e| e.start() == pos) {
+            if let Some(user) = from_entity(repo, roles, entity).await? {
+                users.push(user);
+                pos = entity.end();
+                continue;
+            }
+        }
+        let word = text[pos..].split(char::is_whitespace).next().unwrap_or_default();
+        let in_position = users.is_empty() || word.starts_with(USER_LINK);
+        let Some(id) = parse_id(word).filter(|_| in_position) else {
+            break;
+        };
+        let user = match repo.get_user(id).await {
+            Ok(user) => user,
+            // An unknown number before a reply is taken for part of the reason instead.
+            Err(RepoError::NotFound { .. })
+                if users.is_empty()
+                    && !word.starts_with(USER_LINK)
+                    && msg.reply_to_message().is_some() =>
+            {
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        users.push(user);
+        pos += word.len();
+    }
+    if users.is_empty() {
+        if let Some(user) = reply_target(repo, roles, msg).await? {
+            users.push(user);
+        }
+    }
+    Ok(Targets {
+        users,
+        rest: text[pos..].trim().to_owned(),
+    })
+}
+
+async fn from_entity(
+    repo: &Repo,
+    roles: &RoleSelector,
+    entity: &MessageEntityRef<'_>,
+) -> Result<Option<UserModel>, RepoError> {
+    match entity.kind() {
+        MessageEntityKind::Mention => {
+            let username = entity.text().trim_start_matches('@').to_owned();
+            repo.get_user_by_username(username).await.map(Some)
+        }
+        MessageEntityKind::TextMention { user } => known(repo, roles, user).await.map(Some),
+        MessageEntityKind::TextLink { url } => match parse_id(url.as_str()) {
+            Some(id) => repo.get_user(id).await.map(Some),
+            None => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Author of the message `msg` replies to, see [`resolve`].
+pub async fn reply_target(
+    repo: &Repo,
+    roles: &RoleSelector,
+    msg: &Message,
+) -> Result<Option<UserModel>, RepoError> {
+    let Some(reply) = msg.reply_to_message() else {
+        return Ok(None);
+    };
+    let channel = reply
+        .forward_from_chat()
+        .or_else(|| reply.sender_chat.as_ref().filter(|c| c.id != reply.chat.id));
+    if let Some(user) = reply.forward_from_user().filter(|u| is_person(u)) {
+        known(repo, roles, user).await.map(Some)
+    } else if let Some(chat) = channel {
+        register_chat(repo, chat).await?;
+        repo.get_user(chat.id.0).await.map(Some)
+    } else if let Some(user) = reply.from.as_ref().filter(|u| is_person(u)) {
+        known(repo, roles, user).await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+async fn known(repo: &Repo, roles: &RoleSelector, user: &User) -> Result<UserModel, RepoError> {
+    register(repo, roles, user).await?;
+    repo.get_user(user.id.0 as i64).await
+}
+
+/// Parses `123456789`, `-1001234567890` or `tg://user?id=123456789`.
+fn parse_id(word: &str) -> Option<i64> {
+    let digits = word.strip_prefix(USER_LINK).unwrap_or(word);
+    let len = digits.trim_start_matches('-').len();
+    if len < MIN_ID_DIGITS {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+fn skip_whitespace(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(text.len(), |offset| pos + offset)
+}
+
+/// Byte position after the next `count` words.
+fn skip_words(text: &str, mut pos: usize, count: usize) -> usize {
+    for _ in 0..count {
+        pos = skip_whitespace(text, pos);
+        pos = text[pos..]
+            .find(char::is_whitespace)
+            .map_or(text.len(), |offset| pos + offset);
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::users::Role;
+    use crate::repository::cache::CacheOptions;
+    use crate::repository::db::RepositoryOptions;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use serde_json::{json, Value};
+
+    fn user(id: i64, username: Option<&str>) -> UserModel {
+        UserModel {
+            id,
+            role: Role::User,
+            username: username.map(str::to_owned),
+            nickname: format!("user{id}"),
+            ..Default::default()
+        }
+    }
+
+    /// A repository answering its queries with `results`, in order.
+    fn repo(results: Vec<Vec<UserModel>>) -> Repo {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(results)
+            .into_connection();
+        Repo::new(CacheOptions {
+            inner: RepositoryOptions {
+                database: db,
+                ..Default::default()
+            },
+            capacity: None,
+            ttl: None,
+        })
+    }
+
+    fn message(text: &str, entities: Value, reply_to: Option<Value>) -> Message {
+        let mut msg = json!({
+            "message_id": 2,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup", "title": "chat"},
+            "from": {"id": 1, "is_bot": false, "first_name": "mod"},
+            "text": text,
+            "entities": entities,
+        });
+        if let Some(reply) = reply_to {
+            msg["reply_to_message"] = reply;
+        }
+        serde_json::from_value(msg).unwrap()
+    }
+
+    /// Resolves the targets of a `/role` command, after the subcommand and its argument.
+    async fn resolve_role(repo: &Repo, msg: &Message) -> Targets {
+        let text = msg.text().unwrap();
+        let args = &text["/role ".len()..];
+        resolve(repo, &RoleSelector::new(vec![]), msg, args, 2).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn finds_mentions_after_cyrillic_text() {
+        let text = "/role assign модератор @alice за спам";
+        let entities = json!([
+            {"type": "bot_command", "offset": 0, "length": 5},
+            {"type": "mention", "offset": 23, "length": 6}
+        ]);
+        let alice = user(5, Some("alice"));
+        let repo = repo(vec![vec![alice.clone()]]);
+        let targets = resolve_role(&repo, &message(text, entities, None)).await;
+        assert_eq!(targets.users, [alice]);
+        assert_eq!(targets.rest, "за спам");
+    }
+
+    #[tokio::test]
+    async fn finds_text_mentions_after_emoji() {
+        let text = "/role assign 🦊лис Алиса 🙂 флуд";
+        let entities = json!([
+            {"type": "bot_command", "offset": 0, "length": 5},
+            {
+                "type": "text_mention",
+                "offset": 19,
+                "length": 5,
+                "user": {"id": 5, "is_bot": false, "first_name": "Алиса"}
+            }
+        ]);
+        let alice = user(5, None);
+        let repo = repo(vec![vec![alice.clone()]]);
+        let targets = resolve_role(&repo, &message(text, entities, None)).await;
+        assert_eq!(targets.users, [alice]);
+        assert_eq!(targets.rest, "🙂 флуд");
+    }
+
+    #[tokio::test]
+    async fn unknown_ids_fall_back_to_the_reply() {
+        let text = "/warn 300000 spam";
+        let reply = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup", "title": "chat"},
+            "from": {"id": 7, "is_bot": false, "first_name": "spammer"},
+            "text": "buy now",
+        });
+        let msg = message(text, json!([]), Some(reply));
+        let spammer = user(7, None);
+        let repo = repo(vec![vec![], vec![spammer.clone()]]);
+        let targets = resolve(&repo, &RoleSelector::new(vec![]), &msg, "300000 spam", 0)
+            .await
+            .unwrap();
+        assert_eq!(targets.users, [spammer]);
+        assert_eq!(targets.rest, "300000 spam");
+    }
+
+    #[tokio::test]
+    async fn unknown_ids_without_a_reply_are_not_found() {
+        let msg = message("/warn 300000 spam", json!([]), None);
+        let repo = repo(vec![vec![]]);
+        let result = resolve(&repo, &RoleSelector::new(vec![]), &msg, "300000 spam", 0).await;
+        assert!(matches!(result, Err(RepoError::NotFound { .. })));
+    }
+
+    #[test]
+    fn parses_ids_and_links() {
+        assert_eq!(parse_id("123456789"), Some(123456789));
+        assert_eq!(parse_id("-1001234567890"), Some(-1001234567890));
+        assert_eq!(parse_id("tg://user?id=123456789"), Some(123456789));
+        assert_eq!(parse_id("1234"), None);
+        assert_eq!(parse_id("@someone"), None);
+    }
+
+    #[test]
+    fn skips_words() {
+        let text = "/role  show @x 12345";
+        assert_eq!(&text[skip_words(text, 0, 1)..], "  show @x 12345");
+        assert_eq!(&text[skip_words(text, 5, 1)..], " @x 12345");
+        assert_eq!(skip_words(text, 0, 9), text.len());
+    }
+}
@@ -0,0 +1,49 @@
+use teloxide::types::{Chat, User};
+use teloxide::utils::html;
+
+/// Values of the placeholders a user-written template may contain.
+pub struct Placeholders<'a> {
+    /// Replaces `{user}` with a mention.
+    pub user: &'a User,
+    /// Replaces `{chat}` with the chat's title.
+    pub chat: &'a Chat,
+    /// Replaces `{count}` with the number of members.
+    pub count: Option<u32>,
+}
+
+impl Placeholders<'_> {
+    fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "user" => Some(html::user_mention(self.user.id, &self.user.full_name())),
+            "chat" => Some(html::escape(self.chat.title().unwrap_or_default())),
+            "count" => Some(self.count.map_or_else(|| "?".to_owned(), |c| c.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// Renders a plain-text `template` as HTML, filling in the known placeholders.
+///
+/// Unknown placeholders are left as they are.
+pub fn render(template: &str, values: &Placeholders) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&html::escape(&rest[..start]));
+        let value = rest[start + 1..]
+            .find('}')
+            .and_then(|end| Some((end, values.value(&rest[start + 1..start + 1 + end])?)));
+        match value {
+            Some((end, value)) => {
+                rendered.push_str(&value);
+                rest = &rest[start + end + 2..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    rendered.push_str(&html::escape(rest));
+    rendered
+}
@@ -0,0 +1,291 @@
+//! Times and durations as people write them in commands, in English and Russian.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use sea_orm::prelude::DateTimeWithTimeZone;
+
+/// Time of day of a date given without one.
+const DEFAULT_TIME: (u32, u32) = (9, 0);
+
+/// The first word of `text` and the rest, which keeps its line breaks.
+pub fn word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+/// A compact duration such as `30s`, `45m`, `2h`, `1d12h`, `2ч` or `2мин`.
+pub fn duration(text: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let end = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |end| digits + end);
+        let value: i32 = rest[..digits].parse().ok()?;
+        let part = match rest[digits..end].to_lowercase().as_str() {
+            "с" => Duration::seconds(1),
+            "м" => Duration::minutes(1),
+            "н" => Duration::weeks(1),
+            name => unit(name)?,
+        };
+        total = total.checked_add(&part.checked_mul(value)?)?;
+        rest = &rest[end..];
+    }
+    (total > Duration::zero()).then_some(total)
+}
+
+/// One of a unit word, e.g. `hours` or `минуты`.
+fn unit(word: &str) -> Option<Duration> {
+    Some(match word {
+        "s" | "sec" | "secs" | "second" | "seconds" | "сек" | "секунда" | "секунду" | "секунды"
+        | "секунд" => Duration::seconds(1),
+        "m" | "min" | "mins" | "minute" | "minutes" | "мин" | "минута" | "минуту" | "минуты"
+        | "минут" => Duration::minutes(1),
+        "полчаса" => Duration::minutes(30),
+        "h" | "hr" | "hrs" | "hour" | "hours" | "ч" | "час" | "часа" | "часов" => {
+            Duration::hours(1)
+        }
+        "d" | "day" | "days" | "д" | "день" | "дня" | "дней" | "сутки" | "суток" => {
+            Duration::days(1)
+        }
+        "w" | "week" | "weeks" | "нед" | "неделя" | "неделю" | "недели" | "недель" => {
+            Duration::weeks(1)
+        }
+        _ => return None,
+    })
+}
+
+/// A duration from now, e.g. `2h`, `in 2 hours`, `in an hour and 30 minutes` or
+/// `через 2 часа 30 минут`, followed by the rest of `text`.
+pub fn relative(text: &str) -> Option<(Duration, &str)> {
+    let (first, after) = word(text);
+    let prefixed = matches!(first.to_lowercase().as_str(), "in" | "через");
+    let mut rest = if prefixed { after } else { text };
+    let mut total = Duration::zero();
+    loop {
+        let (current, after) = word(rest);
+        let current = current.to_lowercase();
+        if let Some(compact) = duration(&current) {
+            total = total.checked_add(&compact)?;
+            rest = after;
+            continue;
+        }
+        if matches!(current.as_str(), "and" | "и") && !total.is_zero() {
+            let (next, _) = word(after);
+            if duration(next).is_none() && next.parse::<i32>().is_err() {
+                break;
+            }
+            rest = after;
+            continue;
+        }
+        // A unit without an amount counts once, as in "через час", but only right after the
+        // prefix, so that the text of a reminder is not taken for one.
+        let bare = prefixed && total.is_zero();
+        let (amount, unit_word, after) = match current.parse::<i32>() {
+            Ok(amount) => {
+                let (unit_word, after) = word(after);
+                (amount, unit_word.to_lowercase(), after)
+            }
+            Err(_) if bare && matches!(current.as_str(), "a" | "an" | "one") => {
+                let (unit_word, after) = word(after);
+                (1, unit_word.to_lowercase(), after)
+            }
+            Err(_) if bare => (1, current, after),
+            Err(_) => break,
+        };
+        let Some(unit) = unit(&unit_word) else {
+            break;
+        };
+        total = total.checked_add(&unit.checked_mul(amount)?)?;
+        rest = after;
+    }
+    (total > Duration::zero()).then_some((total, rest))
+}
+
+/// A date such as `2026-11-01`, `01.11.2026` or `01.11`, the latter in the coming year.
+fn date(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%d.%m.%Y") {
+        return Some(date);
+    }
+    let (day, month) = text.split_once('.')?;
+    let (day, month) = (day.parse().ok()?, month.parse().ok()?);
+    let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+    match date < today {
+        true => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+        false => Some(date),
+    }
+}
+
+/// A time such as `10:30`, `10am` or `3pm`, or a bare hour like `10` if `bare_hour` is set.
+fn time(text: &str, bare_hour: bool) -> Option<NaiveTime> {
+    let text = text.to_lowercase();
+    if let Ok(time) = NaiveTime::parse_from_str(&text, "%H:%M") {
+        return Some(time);
+    }
+    let twelve_hour = |hour: &str| hour.parse::<u32>().ok().filter(|h| (1..=12).contains(h));
+    let hour = if let Some(hour) = text.strip_suffix("am") {
+        twelve_hour(hour)? % 12
+    } else if let Some(hour) = text.strip_suffix("pm") {
+        twelve_hour(hour)? % 12 + 12
+    } else if bare_hour {
+        text.parse().ok()?
+    } else {
+        return None;
+    };
+    NaiveTime::from_hms_opt(hour, 0, 0)
+}
+
+/// A point in time in `tz`, e.g. `2026-11-01 10:00`, `10:00`, `tomorrow at 10` or
+/// `завтра в 10`, followed by the rest of `text`.
+///
+/// A time without a date is the next such time, a date without a time is at nine in the morning.
+pub fn absolute(text: &str, tz: Tz, now: DateTime<Utc>) -> Option<(DateTime<Utc>, &str)> {
+    let today = now.with_timezone(&tz).date_naive();
+    let mut rest = text;
+    let (first, after) = word(rest);
+    let day = match first.to_lowercase().as_str() {
+        "today" | "сегодня" => Some(today),
+        "tomorrow" | "завтра" => today.succ_opt(),
+        "послезавтра" => today.succ_opt()?.succ_opt(),
+        other => date(other, today),
+    };
+    if day.is_some() {
+        rest = after;
+    }
+    let (first, after) = word(rest);
+    let at = matches!(first.to_lowercase().as_str(), "at" | "в" | "во");
+    let (time_word, after) = if at { word(after) } else { (first, after) };
+    let time = match time(time_word, at || day.is_some()) {
+        Some(time) => {
+            rest = after;
+            Some(time)
+        }
+        None if at => return None,
+        None => None,
+    };
+    if day.is_none() && time.is_none() {
+        return None;
+    }
+    let (hour, minute) = DEFAULT_TIME;
+    let time = time.or_else(|| NaiveTime::from_hms_opt(hour, minute, 0))?;
+    let local = |day: NaiveDate| tz.from_local_datetime(&day.and_time(time)).earliest();
+    let moment = match day {
+        Some(day) => local(day)?,
+        None => match local(today)? {
+            moment if moment > now => moment,
+            _ => local(today.succ_opt()?)?,
+        },
+    };
+    Some((moment.with_timezone(&Utc), rest))
+}
+
+/// A relative or absolute time, followed by the rest of `text`.
+pub fn parse(text: &str, tz: Tz, now: DateTime<Utc>) -> Option<(DateTime<Utc>, &str)> {
+    match relative(text) {
+        Some((duration, rest)) => Some((now.checked_add_signed(duration)?, rest)),
+        None => absolute(text, tz, now),
+    }
+}
+
+/// `at` as shown to users, in `tz`.
+pub fn format_time(at: DateTimeWithTimeZone, tz: Tz) -> String {
+    at.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// `seconds` in the form [`duration`] reads, e.g. `1h30m`.
+pub fn format_duration(seconds: u64) -> String {
+    let units = [(7 * 24 * 3600, 'w'), (24 * 3600, 'd'), (3600, 'h'), (60, 'm'), (1, 's')];
+    let mut rest = seconds;
+    let mut text = String::new();
+    for (size, unit) in units {
+        if rest >= size {
+            text.push_str(&format!("{}{unit}", rest / size));
+            rest %= size;
+        }
+    }
+    if text.is_empty() {
+        text.push_str("0s");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Europe::Moscow;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    /// Monday 2026-10-19, 15:00 in Moscow.
+    fn now() -> DateTime<Utc> {
+        at(2026, 10, 19, 12, 0)
+    }
+
+    #[test]
+    fn compact_durations() {
+        assert_eq!(duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(duration("1d12h"), Some(Duration::hours(36)));
+        assert_eq!(duration("2ч"), Some(Duration::hours(2)));
+        assert_eq!(duration("2мин"), Some(Duration::minutes(2)));
+        assert_eq!(duration("1ч30мин"), Some(Duration::minutes(90)));
+        assert_eq!(duration("2нед"), Some(Duration::weeks(2)));
+        assert_eq!(duration("0m"), None);
+        assert_eq!(duration("h"), None);
+        assert_eq!(duration("2"), None);
+        assert_eq!(duration("2x"), None);
+    }
+
+    #[test]
+    fn formatted_durations_read_back() {
+        for seconds in [1, 90, 3600, 5400, 86400 + 60, 8 * 24 * 3600] {
+            let text = format_duration(seconds);
+            assert_eq!(duration(&text), Some(Duration::seconds(seconds as i64)), "{text}");
+        }
+    }
+
+    #[test]
+    fn relative_times() {
+        assert_eq!(relative("2h take a break"), Some((Duration::hours(2), "take a break")));
+        assert_eq!(relative("через 2 часа позвонить"), Some((Duration::hours(2), "позвонить")));
+        assert_eq!(relative("через 2мин чай"), Some((Duration::minutes(2), "чай")));
+        assert_eq!(relative("2 мин чай"), Some((Duration::minutes(2), "чай")));
+        assert_eq!(relative("через час"), Some((Duration::hours(1), "")));
+        assert_eq!(
+            relative("in an hour and 30 minutes stretch"),
+            Some((Duration::minutes(90), "stretch"))
+        );
+        assert_eq!(relative("an hour later"), None);
+        assert_eq!(relative("call mom"), None);
+    }
+
+    #[test]
+    fn absolute_times() {
+        assert_eq!(
+            absolute("завтра в 10 позвонить", Moscow, now()),
+            Some((at(2026, 10, 20, 7, 0), "позвонить"))
+        );
+        assert_eq!(
+            absolute("2026-11-01 10:00 vote", Moscow, now()),
+            Some((at(2026, 11, 1, 7, 0), "vote"))
+        );
+        // Past today, so tomorrow.
+        assert_eq!(absolute("10:00", Moscow, now()), Some((at(2026, 10, 20, 7, 0), "")));
+        assert_eq!(absolute("18:30", Moscow, now()), Some((at(2026, 10, 19, 15, 30), "")));
+        assert_eq!(absolute("01.11", Moscow, now()), Some((at(2026, 11, 1, 6, 0), "")));
+        assert_eq!(absolute("tomorrow at noon", Moscow, now()), None);
+        assert_eq!(absolute("lunch", Moscow, now()), None);
+    }
+
+    #[test]
+    fn parse_prefers_relative_times() {
+        assert_eq!(parse("2h", Moscow, now()), Some((at(2026, 10, 19, 14, 0), "")));
+        assert_eq!(parse("завтра в 10", Moscow, now()), Some((at(2026, 10, 20, 7, 0), "")));
+    }
+}